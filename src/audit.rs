@@ -0,0 +1,379 @@
+use crate::client::Client;
+use crate::server::constants::{AUDIT_LOG_ROTATIONS, MAX_AUDIT_HISTORY};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::oneshot;
+use utoipa::ToSchema;
+
+/// Who made an administrative change
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuditActor {
+    /// An http api token, basic authentication shows up with the name of the http user
+    Api { token: String },
+    /// A privileged mumble client
+    Client { session: u32, name: String },
+}
+
+impl AuditActor {
+    pub fn name(&self) -> &str {
+        match self {
+            AuditActor::Api { token } => token,
+            AuditActor::Client { name, .. } => name,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Mute,
+    Deaf,
+    PrioritySpeaker,
+    Instance,
+    GlobalTargets,
+    ChannelProximity,
+    ChannelGlobal,
+    ClientSettings,
+    ContextActionAdded,
+    ContextActionRemoved,
+    Kick,
+    Ban,
+}
+
+/// What an administrative change was made to
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuditTarget {
+    User { session: u32, name: String },
+    Channel { channel_id: u32 },
+    ClientSettings,
+    ContextAction { action: String },
+}
+
+impl AuditTarget {
+    pub fn user(client: &Client) -> Self {
+        AuditTarget::User {
+            session: client.session_id,
+            name: client.get_name().as_ref().clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    pub id: u64,
+    /// Unix timestamp in milliseconds
+    pub timestamp: u128,
+    pub actor: AuditActor,
+    pub action: AuditAction,
+    pub target: AuditTarget,
+    /// Value before the change, null when there was none
+    #[schema(value_type = Object)]
+    pub before: serde_json::Value,
+    /// Value after the change, null when it got removed
+    #[schema(value_type = Object)]
+    pub after: serde_json::Value,
+}
+
+struct AuditFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+}
+
+/// Path of the `index`th rotated file, `path.1` being the newest
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut path = path.to_path_buf().into_os_string();
+    path.push(format!(".{}", index));
+
+    PathBuf::from(path)
+}
+
+impl AuditFile {
+    /// Moves `path` to `path.1`, `path.1` to `path.2` and so on, the oldest file gets overwritten
+    fn rotate(&mut self) -> std::io::Result<()> {
+        for index in (1..AUDIT_LOG_ROTATIONS).rev() {
+            let from = rotated_path(&self.path, index);
+
+            if from.exists() {
+                std::fs::rename(from, rotated_path(&self.path, index + 1))?;
+            }
+        }
+
+        std::fs::rename(&self.path, rotated_path(&self.path, 1))?;
+
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+
+        Ok(())
+    }
+
+    fn append(&mut self, line: &[u8]) -> std::io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        self.file.write_all(line)?;
+        self.size += line.len() as u64;
+
+        Ok(())
+    }
+}
+
+enum AuditWrite {
+    Line(Vec<u8>),
+    /// Answered once every line sent before reached the disk
+    Sync(oneshot::Sender<()>),
+}
+
+/// Writes the lines it receives to the audit file, on its own thread so the blocking writes and
+/// rotations don't hold up the runtime
+fn audit_writer(mut file: AuditFile, receiver: mpsc::Receiver<AuditWrite>) {
+    for write in receiver {
+        match write {
+            AuditWrite::Line(line) => {
+                if let Err(e) = file.append(&line) {
+                    tracing::error!("failed to write audit entry: {}", e);
+                }
+            }
+            AuditWrite::Sync(done) => {
+                if let Err(e) = file.file.sync_all() {
+                    tracing::error!("failed to sync the audit log: {}", e);
+                }
+
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+/// Append only record of the administrative changes, written as json lines when a file is
+/// configured and kept in memory for the most recent ones
+pub struct AuditLog {
+    writer: Option<mpsc::Sender<AuditWrite>>,
+    recent: Mutex<VecDeque<Arc<AuditEntry>>>,
+    next_id: AtomicU64,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self {
+            writer: None,
+            recent: Mutex::new(VecDeque::with_capacity(MAX_AUDIT_HISTORY)),
+            next_id: AtomicU64::new(1),
+        }
+    }
+}
+
+impl AuditLog {
+    /// Opens the log file, the entries it and the newest rotated file already have are loaded so
+    /// they can still be queried after a restart and ids carry on after the last one, even when
+    /// the current file is empty after a rotation
+    pub fn open(path: PathBuf, max_size: u64) -> std::io::Result<Self> {
+        let mut recent = VecDeque::with_capacity(MAX_AUDIT_HISTORY);
+
+        for existing in [rotated_path(&path, 1), path.clone()] {
+            if !existing.exists() {
+                continue;
+            }
+
+            for line in BufReader::new(File::open(&existing)?).lines() {
+                let Ok(entry) = serde_json::from_str::<AuditEntry>(&line?) else {
+                    continue;
+                };
+
+                if recent.len() >= MAX_AUDIT_HISTORY {
+                    recent.pop_front();
+                }
+
+                recent.push_back(Arc::new(entry));
+            }
+        }
+
+        let next_id = recent.back().map(|entry| entry.id + 1).unwrap_or(1);
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        let file = AuditFile {
+            path,
+            file,
+            size,
+            max_size,
+        };
+
+        let (writer, receiver) = mpsc::channel();
+
+        std::thread::Builder::new()
+            .name("audit-log".to_string())
+            .spawn(move || audit_writer(file, receiver))?;
+
+        Ok(Self {
+            writer: Some(writer),
+            recent: Mutex::new(recent),
+            next_id: AtomicU64::new(next_id),
+        })
+    }
+
+    pub fn record(&self, actor: AuditActor, action: AuditAction, target: AuditTarget, before: impl Serialize, after: impl Serialize) {
+        // the id is taken under the lock so entries reach the history and the file in id order
+        let mut recent = self.recent.lock();
+
+        let entry = AuditEntry {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis(),
+            actor,
+            action,
+            target,
+            before: serde_json::to_value(before).unwrap_or_default(),
+            after: serde_json::to_value(after).unwrap_or_default(),
+        };
+
+        tracing::info!(
+            "audit: {} changed {:?} of {:?} from {} to {}",
+            entry.actor.name(),
+            entry.action,
+            entry.target,
+            entry.before,
+            entry.after
+        );
+
+        if let Some(writer) = &self.writer {
+            match serde_json::to_vec(&entry) {
+                Ok(mut line) => {
+                    line.push(b'\n');
+
+                    if writer.send(AuditWrite::Line(line)).is_err() {
+                        tracing::error!("audit log writer stopped, entry {} is only kept in memory", entry.id);
+                    }
+                }
+                Err(e) => tracing::error!("failed to serialize audit entry {}: {}", entry.id, e),
+            }
+        }
+
+        if recent.len() >= MAX_AUDIT_HISTORY {
+            recent.pop_front();
+        }
+
+        recent.push_back(Arc::new(entry));
+    }
+
+    /// Makes sure every entry reached the disk, called before the server exits
+    pub async fn sync(&self) {
+        if let Some(writer) = &self.writer {
+            let (done, synced) = oneshot::channel();
+
+            if writer.send(AuditWrite::Sync(done)).is_ok() {
+                let _ = synced.await;
+            }
+        }
+    }
+
+    /// Returns the most recent entries matching the filter, newest first
+    pub fn query(&self, filter: impl Fn(&AuditEntry) -> bool, limit: usize) -> Vec<Arc<AuditEntry>> {
+        self.recent
+            .lock()
+            .iter()
+            .rev()
+            .filter(|entry| filter(entry))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("zumble-audit-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    fn record_mute(log: &AuditLog, session: u32) {
+        log.record(
+            AuditActor::Api {
+                token: "admin".to_string(),
+            },
+            AuditAction::Mute,
+            AuditTarget::User {
+                session,
+                name: format!("user {}", session),
+            },
+            false,
+            true,
+        );
+    }
+
+    #[tokio::test]
+    async fn rotates_and_reloads() {
+        let dir = test_dir("rotate");
+        let path = dir.join("audit.log");
+
+        let log = AuditLog::open(path.clone(), 512).unwrap();
+
+        for session in 1..=20 {
+            record_mute(&log, session);
+        }
+
+        log.sync().await;
+
+        assert!(path.exists());
+        assert!(dir.join("audit.log.1").exists());
+        assert!(!dir.join(format!("audit.log.{}", AUDIT_LOG_ROTATIONS + 1)).exists());
+
+        // ids carry on from the newest entry
+        let reopened = AuditLog::open(path.clone(), 512).unwrap();
+        let newest = reopened.query(|_| true, 1);
+
+        assert_eq!(newest[0].id, 20);
+
+        record_mute(&reopened, 21);
+        reopened.sync().await;
+        assert_eq!(reopened.query(|_| true, 1)[0].id, 21);
+
+        // a current file that is still empty after a rotation doesn't restart the ids
+        drop(reopened);
+        std::fs::rename(&path, dir.join("audit.log.1")).unwrap();
+        File::create(&path).unwrap();
+
+        let reopened = AuditLog::open(path, 512).unwrap();
+        record_mute(&reopened, 22);
+        assert_eq!(reopened.query(|_| true, 1)[0].id, 22);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn query_is_newest_first() {
+        let log = AuditLog::default();
+
+        for session in 1..=5 {
+            record_mute(&log, session);
+        }
+
+        let ids: Vec<u64> = log
+            .query(
+                |entry| matches!(entry.target, AuditTarget::User { session, .. } if session % 2 == 1),
+                2,
+            )
+            .iter()
+            .map(|entry| entry.id)
+            .collect();
+
+        assert_eq!(ids, vec![5, 3]);
+    }
+}
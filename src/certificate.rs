@@ -0,0 +1,104 @@
+use ring::digest::{digest, SHA1_FOR_LEGACY_USE_ONLY, SHA256};
+use rustls::client::danger::HandshakeSignatureValid;
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, WebPkiSupportedAlgorithms};
+use rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use rustls::{DigitallySignedStruct, DistinguishedName, SignatureScheme};
+use rustls_pki_types::{CertificateDer, UnixTime};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Fingerprints of the certificate a client presented during the TLS handshake.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct CertificateHash {
+    /// Lowercase hex SHA1 of the DER certificate, this is what mumble uses to identify users
+    pub sha1: String,
+    /// Lowercase hex SHA256 of the DER certificate
+    pub sha256: String,
+}
+
+impl CertificateHash {
+    pub fn from_der(certificate: &CertificateDer<'_>) -> Self {
+        Self {
+            sha1: to_hex(digest(&SHA1_FOR_LEGACY_USE_ONLY, certificate.as_ref()).as_ref()),
+            sha256: to_hex(digest(&SHA256, certificate.as_ref()).as_ref()),
+        }
+    }
+
+    /// Checks if the given hex fingerprint (either SHA1 or SHA256) matches this certificate
+    pub fn matches(&self, fingerprint: &str) -> bool {
+        let fingerprint = normalize_fingerprint(fingerprint);
+
+        fingerprint == self.sha1 || fingerprint == self.sha256
+    }
+}
+
+/// Lowercases the fingerprint and strips the `:` separators some tools add between bytes
+pub fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint.chars().filter(|c| *c != ':').collect::<String>().to_lowercase()
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Requests a certificate from the client but doesn't require one, and accepts any certificate the
+/// client sends (including self signed ones) like murmur does.
+///
+/// We never trust the certificate for anything more than a stable identifier, the client still has
+/// to prove it owns the key so the fingerprint can't be spoofed.
+#[derive(Debug)]
+pub struct AcceptAnyClientCert {
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl AcceptAnyClientCert {
+    pub fn new(algorithms: WebPkiSupportedAlgorithms) -> Self {
+        Self { algorithms }
+    }
+}
+
+impl ClientCertVerifier for AcceptAnyClientCert {
+    fn offer_client_auth(&self) -> bool {
+        true
+    }
+
+    // FiveM doesn't send a certificate, so we can't require one
+    fn client_auth_mandatory(&self) -> bool {
+        false
+    }
+
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _now: UnixTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
@@ -1,7 +1,10 @@
+use arc_swap::ArcSwapOption;
 use scc::HashMap;
 
 use crate::client::ClientRef;
 use crate::proto::mumble::ChannelState;
+use crate::proximity::ProximitySettings;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 pub type ChannelRef = Arc<Channel>;
@@ -15,6 +18,10 @@ pub struct Channel {
     pub temporary: bool,
     pub listeners: HashMap<u32, ClientRef>,
     pub clients: HashMap<u32, ClientRef>,
+    /// Set when the channel uses proximity voice
+    pub proximity: ArcSwapOption<ProximitySettings>,
+    /// Global channels can be heard across instances, e.g. for an admin radio
+    pub global: AtomicBool,
     channel_state_cache: Arc<ChannelState>,
 }
 
@@ -42,6 +49,8 @@ impl Channel {
             temporary,
             clients: HashMap::new(),
             listeners: HashMap::new(),
+            proximity: ArcSwapOption::from(None),
+            global: AtomicBool::new(false),
         }
     }
 
@@ -49,6 +58,14 @@ impl Channel {
         self.channel_state_cache.clone()
     }
 
+    pub fn is_global(&self) -> bool {
+        self.global.load(Ordering::Relaxed)
+    }
+
+    pub fn set_global(&self, global: bool) {
+        self.global.store(global, Ordering::Release);
+    }
+
     pub fn get_listeners(&self) -> &HashMap<u32, ClientRef> {
         &self.listeners
     }
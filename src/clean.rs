@@ -1,10 +1,16 @@
+use crate::deadline::DeadlineKind;
 use crate::error::MumbleError;
-use crate::message::ClientMessage;
+use crate::server::constants::{CLEAN_INTERVAL, CLIENT_METRICS_INTERVAL, CRYPT_RESET_WEBHOOK_THRESHOLD, DEADLINE_RESOLUTION};
 use crate::state::{ServerState, ServerStateRef};
+use crate::webhook::{DisconnectReason, WebhookEvent};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Instant;
 
 pub async fn clean_loop(state: ServerStateRef) {
+    let mut housekeeping_at = Instant::now();
+    let mut metrics_at = Instant::now();
+
     loop {
         tracing::trace!("cleaning clients");
 
@@ -15,40 +21,133 @@ pub async fn clean_loop(state: ServerStateRef) {
             }
         }
 
-        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        // the loop wakes up for every deadline, the rest only needs to run once in a while
+        if housekeeping_at.elapsed() >= CLEAN_INTERVAL {
+            housekeeping(&state);
+            housekeeping_at = Instant::now();
+        }
+
+        if metrics_at.elapsed() >= CLIENT_METRICS_INTERVAL {
+            update_client_metrics(&state);
+            metrics_at = Instant::now();
+        }
+
+        state.health.clean_ran();
+
+        // deadlines close to each other are handled in the same run
+        let now = Instant::now();
+        let wake_at = state
+            .deadlines
+            .next_at()
+            .map_or(now + CLEAN_INTERVAL, |at| at.clamp(now + DEADLINE_RESOLUTION, now + CLEAN_INTERVAL));
+
+        tokio::time::sleep_until(wake_at.into()).await;
     }
 }
 
+fn housekeeping(state: &ServerState) {
+    // rate limits that refilled completely don't hold any information anymore
+    state.server_list_ping_limits.retain(|_, limiter| !limiter.is_full());
+
+    state.clear_expired_restores();
+}
+
+/// Refreshes the gauges that need a look at every channel
+fn update_client_metrics(state: &ServerState) {
+    let mut channels = Vec::new();
+
+    state.channels.scan(|id, channel| {
+        channels.push((*id, channel.name.clone(), channel.clients.len() as i64));
+    });
+
+    crate::metrics::set_channel_clients(channels);
+}
+
 async fn clean_run(state: &ServerState) -> Result<(), MumbleError> {
     let mut clients_to_remove = Vec::new();
     let mut clients_to_reset_crypt = Vec::new();
 
-    let mut iter = state.clients.first_entry();
-    while let Some(client) = iter {
-        if client.publisher.is_closed() {
-            clients_to_remove.push(client.session_id);
-        }
+    let now = Instant::now();
 
-        let now = Instant::now();
+    for (session_id, kind) in state.deadlines.take_expired(now) {
+        // the client already left, its deadlines are dropped with it
+        let Some(client) = state.clients.read(&session_id, |_, client| client.clone()) else {
+            continue;
+        };
 
-        let duration = now.duration_since(client.last_ping.load());
+        match kind {
+            DeadlineKind::Liveness => {
+                let ping_deadline = client.last_ping.load() + state.config.ping_timeout;
 
-        if duration.as_secs() > 30 {
-            clients_to_remove.push(client.session_id);
-        }
+                if client.publisher.is_closed() {
+                    clients_to_remove.push((session_id, DisconnectReason::Closed));
 
-        let last_good = { client.crypt_state.lock().last_good };
+                    continue;
+                }
 
-        if now.duration_since(last_good).as_millis() > 8000 {
-            clients_to_reset_crypt.push(client.clone())
-        }
+                if ping_deadline <= now {
+                    clients_to_remove.push((session_id, DisconnectReason::Timeout));
+
+                    continue;
+                }
+
+                client.clear_expired_server_position(state.config.position_ttl);
+
+                let mut udp_deadline = client.crypt_state.lock().last_good + state.config.udp_timeout;
+
+                if udp_deadline <= now {
+                    clients_to_reset_crypt.push(client.clone());
+
+                    // the reset gives the client a fresh crypt state to set up UDP with
+                    udp_deadline = now + state.config.udp_timeout;
+                }
+
+                state
+                    .deadlines
+                    .schedule(ping_deadline.min(udp_deadline), session_id, DeadlineKind::Liveness);
+            }
+            DeadlineKind::Talking => {
+                let (talking_change, timeout_at) = {
+                    let mut talking = client.talking.lock();
+
+                    (talking.check_timeout(), talking.timeout_at())
+                };
 
-        iter = client.next();
+                if let Some(change) = talking_change {
+                    state.emit_talking_change(&client, change);
+                }
+
+                // voice came in since the deadline was set, check again when the spurt can time out
+                if let Some(at) = timeout_at {
+                    state.deadlines.schedule(at, session_id, DeadlineKind::Talking);
+                }
+            }
+        }
     }
 
+    let tunnel_clients = state.clients_without_udp.len();
+
+    crate::metrics::CLIENTS_BY_TRANSPORT
+        .with_label_values(&["udp"])
+        .set(state.clients.len().saturating_sub(tunnel_clients) as i64);
+    crate::metrics::CLIENTS_BY_TRANSPORT
+        .with_label_values(&["tcp_tunnel"])
+        .set(tunnel_clients as i64);
+
     for client in clients_to_reset_crypt {
         let log_name = Arc::clone(&client);
         let session_id = client.session_id;
+
+        let resets = client.crypt_resets.fetch_add(1, Ordering::Relaxed) + 1;
+
+        if resets == CRYPT_RESET_WEBHOOK_THRESHOLD {
+            state.webhooks.send(WebhookEvent::CryptResetsRepeated {
+                session: session_id,
+                name: client.get_name().as_ref().clone(),
+                resets,
+            });
+        }
+
         if let Err(e) = state.reset_client_crypt(client).await {
             tracing::error!("failed to send crypt setup for {}: {:?}", e, session_id);
         } else {
@@ -56,9 +155,62 @@ async fn clean_run(state: &ServerState) -> Result<(), MumbleError> {
         }
     }
 
-    for session_id in clients_to_remove {
-        state.disconnect(session_id);
+    for (session_id, reason) in clients_to_remove {
+        state.disconnect(session_id, reason);
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{connect_client, test_state};
+    use std::time::Duration;
+
+    #[actix_web::test]
+    async fn client_without_ping_is_removed() {
+        let state = test_state().await;
+        let client = connect_client(&state, "quiet").await;
+        let session_id = client.session_id;
+
+        client
+            .last_ping
+            .store(Instant::now() - state.config.ping_timeout - Duration::from_secs(1));
+        state.deadlines.schedule(Instant::now(), session_id, DeadlineKind::Liveness);
+
+        clean_run(&state).await.unwrap();
+
+        assert!(!state.clients.contains(&session_id));
+    }
+
+    #[actix_web::test]
+    async fn crypt_is_reset_once_per_udp_timeout() {
+        let state = test_state().await;
+        let client = connect_client(&state, "tunnel").await;
+        let session_id = client.session_id;
+        let udp_timeout = state.config.udp_timeout;
+
+        client.crypt_state.lock().last_good = Instant::now() - udp_timeout - Duration::from_secs(1);
+        state.deadlines.schedule(Instant::now(), session_id, DeadlineKind::Liveness);
+
+        clean_run(&state).await.unwrap();
+
+        assert_eq!(client.crypt_resets.load(Ordering::Relaxed), 1);
+        assert!(state.clients.contains(&session_id));
+        assert!(state.deadlines.next_at().is_some_and(|at| at <= Instant::now() + udp_timeout));
+
+        // an earlier check doesn't reset the fresh crypt state again
+        state.deadlines.schedule(Instant::now(), session_id, DeadlineKind::Liveness);
+        clean_run(&state).await.unwrap();
+
+        assert_eq!(client.crypt_resets.load(Ordering::Relaxed), 1);
+
+        // another udp timeout without a good packet does
+        client.crypt_state.lock().last_good = Instant::now() - udp_timeout - Duration::from_secs(1);
+        state.deadlines.schedule(Instant::now(), session_id, DeadlineKind::Liveness);
+        clean_run(&state).await.unwrap();
+
+        assert_eq!(client.crypt_resets.load(Ordering::Relaxed), 2);
+    }
+}
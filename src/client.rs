@@ -1,21 +1,28 @@
+use crate::certificate::CertificateHash;
+use crate::config::{ClientSettings, Config};
 use crate::crypt::CryptState;
 use crate::error::MumbleError;
 use crate::message::ClientMessage;
-use crate::proto::mumble::{Authenticate, ServerConfig, ServerSync, UDPTunnel, UserState, Version};
+use crate::position::{Position, PositionSource};
+use crate::proto::mumble::{Authenticate, ServerSync, UDPTunnel, UserState, Version};
 use crate::proto::{expected_message, message_to_bytes, send_message, MessageKind};
-use crate::server::constants::MAX_BANDWIDTH_IN_BITS;
+use crate::ratelimit::RateLimiter;
+use crate::server::constants::{MAX_BANDWIDTH_IN_BITS, PLUGIN_MESSAGE_BURST, PLUGIN_MESSAGE_RATE, PRIORITY_SPEAKER_DUCKING_DURATION};
 use crate::state::ServerStateRef;
+use crate::talking::TalkingState;
 use crate::target::VoiceTarget;
-use crate::voice::{encode_voice_packet, ClientBound, VoicePacket};
+use crate::version::ClientVersion;
+use crate::voice::{ClientBound, VoicePacket, VoicePacketFormat};
 use arc_swap::ArcSwapOption;
 use bytes::BytesMut;
 use crossbeam::atomic::AtomicCell;
 use parking_lot::Mutex;
 use protobuf::Message;
 use tokio::time::timeout;
+use std::collections::HashSet;
 use std::fmt::Display;
-use std::net::SocketAddr;
-use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::io::{AsyncWriteExt, WriteHalf};
@@ -28,17 +35,33 @@ pub type ClientRef = Arc<Client>;
 type VoiceTargetArray = [Arc<VoiceTarget>; 29];
 
 pub struct Client {
-    // pub version: Version,
+    pub version: ClientVersion,
+    /// Format of the voice packets this client sends and expects, over UDP and the TCP tunnel
+    pub udp_format: VoicePacketFormat,
     name: Arc<String>,
     pub log_name: Arc<String>,
     pub authenticate: Authenticate,
     pub session_id: u32,
+    /// FiveM server id of the player, extracted from the username
+    pub server_id: Option<u32>,
     pub channel_id: AtomicU32,
     pub mute: AtomicBool,
     pub deaf: AtomicBool,
+    pub priority_speaker: AtomicBool,
+    pub talking: Mutex<TalkingState>,
+    /// Last time this client heard a priority speaker, used to duck the other speakers
+    pub priority_speaker_heard_at: AtomicCell<Option<Instant>>,
+    /// Routing bucket of the player in the game, voice is never routed between instances unless
+    /// it goes through a global channel
+    pub instance: AtomicI32,
+    /// Voice target ids (bit `id`) whose sessions hear this client in every instance, like a
+    /// global channel, for talk groups like a radio or a call
+    pub global_targets: AtomicU32,
     pub write: tokio::sync::Mutex<WriteHalf<TlsStream<TcpStream>>>,
     // pub tokens: Vec<String>,
     pub crypt_state: Mutex<CryptState>,
+    /// Crypt resets in a row without the client getting its UDP connection back
+    pub crypt_resets: AtomicU32,
     pub udp_socket_addr: ArcSwapOption<SocketAddr>,
     // pub use_opus: bool,
     pub codecs: Vec<i32>,
@@ -46,6 +69,19 @@ pub struct Client {
     pub publisher: Sender<ClientMessage>,
     pub targets: VoiceTargetArray,
     pub last_ping: AtomicCell<Instant>,
+    /// Fingerprint of the TLS client certificate, if the client sent one
+    pub certificate_hash: Option<CertificateHash>,
+    /// Set when the client certificate matches one of the configured admin certificates
+    pub admin: bool,
+    pub peer_ip: IpAddr,
+    pub connected_at: Instant,
+    pub plugin_rate_limit: Mutex<RateLimiter>,
+    /// Last position sent by the client in its voice packets
+    pub client_position: AtomicCell<Option<Position>>,
+    /// Last position pushed by the game server
+    pub server_position: AtomicCell<Option<Position>>,
+    /// Sessions that heard the last voice packet of this client in a proximity channel
+    pub proximity_listeners: Mutex<HashSet<u32>>,
 }
 
 impl Display for Client {
@@ -76,31 +112,46 @@ impl Client {
         Ok((version, authenticate, crypt))
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        _version: Version,
+        version: Version,
         authenticate: Authenticate,
         session_id: u32,
+        server_id: Option<u32>,
         channel_id: u32,
         crypt_state: CryptState,
         write: WriteHalf<TlsStream<TcpStream>>,
         udp_socket: Arc<UdpSocket>,
         publisher: Sender<ClientMessage>,
+        certificate_hash: Option<CertificateHash>,
+        admin: bool,
+        peer_ip: IpAddr,
     ) -> Self {
         // let tokens = authenticate.get_tokens().iter().map(|token| token.to_string()).collect();
         let targets: VoiceTargetArray = core::array::from_fn(|_v| Arc::new(VoiceTarget::default()));
 
+        let version = ClientVersion::from(&version);
+
         Self {
-            // version,
+            udp_format: VoicePacketFormat::for_version(version.protocol_version()),
+            version,
             session_id,
+            server_id,
             log_name: Arc::new(format!("{} [session id: {}]", authenticate.get_username(), session_id)),
 
             name: Arc::new(authenticate.get_username().to_string()),
             channel_id: AtomicU32::new(channel_id),
             crypt_state: Mutex::new(crypt_state),
+            crypt_resets: AtomicU32::new(0),
             write: tokio::sync::Mutex::new(write),
             // tokens,
             deaf: AtomicBool::new(false),
             mute: AtomicBool::new(false),
+            instance: AtomicI32::new(0),
+            global_targets: AtomicU32::new(0),
+            priority_speaker: AtomicBool::new(false),
+            talking: Mutex::new(TalkingState::default()),
+            priority_speaker_heard_at: AtomicCell::new(None),
             udp_socket_addr: ArcSwapOption::from(None),
             // use_opus: if authenticate.has_opus() { authenticate.get_opus() } else { false },
             codecs: authenticate.get_celt_versions().to_vec(),
@@ -109,6 +160,14 @@ impl Client {
             publisher,
             targets,
             last_ping: AtomicCell::new(Instant::now()),
+            certificate_hash,
+            admin,
+            peer_ip,
+            connected_at: Instant::now(),
+            plugin_rate_limit: Mutex::new(RateLimiter::new(PLUGIN_MESSAGE_RATE, PLUGIN_MESSAGE_BURST)),
+            client_position: AtomicCell::new(None),
+            server_position: AtomicCell::new(None),
+            proximity_listeners: Mutex::new(HashSet::new()),
         }
     }
 
@@ -131,6 +190,16 @@ impl Client {
         }
     }
 
+    /// Flushes what is left to send and closes the TLS session, the client sees a clean disconnect
+    pub async fn close(&self) -> Result<(), MumbleError> {
+        let mut writer = self.write.lock().await;
+
+        writer.flush().await?;
+        writer.shutdown().await?;
+
+        Ok(())
+    }
+
     pub fn is_muted(&self) -> bool {
         self.mute.load(Ordering::Relaxed)
     }
@@ -147,6 +216,51 @@ impl Client {
         self.deaf.store(deaf, Ordering::Release);
     }
 
+    pub fn is_priority_speaker(&self) -> bool {
+        self.priority_speaker.load(Ordering::Relaxed)
+    }
+
+    pub fn set_priority_speaker(&self, priority_speaker: bool) {
+        self.priority_speaker.store(priority_speaker, Ordering::Release);
+    }
+
+    /// Checks if this client heard a priority speaker recently, audio from other speakers gets
+    /// dropped for this client in that case when ducking is enabled
+    pub fn is_ducked(&self) -> bool {
+        self.priority_speaker_heard_at
+            .load()
+            .is_some_and(|heard_at| heard_at.elapsed() < PRIORITY_SPEAKER_DUCKING_DURATION)
+    }
+
+    pub fn get_instance(&self) -> i32 {
+        self.instance.load(Ordering::Relaxed)
+    }
+
+    pub fn set_instance(&self, instance: i32) {
+        self.instance.store(instance, Ordering::Release);
+    }
+
+    pub fn is_global_target(&self, target: u8) -> bool {
+        self.global_targets.load(Ordering::Relaxed) & (1 << target) != 0
+    }
+
+    /// Ids of the voice targets that cross instances
+    pub fn get_global_targets(&self) -> Vec<u8> {
+        (1..=30).filter(|target| self.is_global_target(*target)).collect()
+    }
+
+    /// Only the voice targets 1 to 30 can be global, other ids are ignored
+    pub fn set_global_target(&self, target: u8, global: bool) {
+        if !(1..=30).contains(&target) {
+            return;
+        }
+
+        match global {
+            true => self.global_targets.fetch_or(1 << target, Ordering::AcqRel),
+            false => self.global_targets.fetch_and(!(1 << target), Ordering::AcqRel),
+        };
+    }
+
     pub async fn send_message<T: Message>(&self, kind: MessageKind, message: &T) -> Result<(), MumbleError> {
         tracing::trace!(
             "[{}] [{}] send message: {:?}, {:?}",
@@ -222,30 +336,52 @@ impl Client {
         Ok(())
     }
 
-    pub async fn send_server_sync(&self) -> Result<(), MumbleError> {
+    pub async fn send_context_actions(&self, state: &ServerStateRef) -> Result<(), MumbleError> {
+        let mut iter = state.context_actions.first_entry_async().await;
+        while let Some(definition) = iter {
+            let modify = definition.get_context_action_add();
+
+            self.send_message(MessageKind::ContextActionModify, &modify).await?;
+
+            iter = definition.next_async().await;
+        }
+
+        Ok(())
+    }
+
+    pub async fn send_server_sync(&self, settings: &ClientSettings) -> Result<(), MumbleError> {
         let mut server_sync = ServerSync::default();
         server_sync.set_max_bandwidth(MAX_BANDWIDTH_IN_BITS);
         server_sync.set_session(self.session_id);
-        server_sync.set_welcome_text("SoZ Mumble Server".to_string());
+        server_sync.set_welcome_text(settings.welcome_text.clone());
 
         self.send_message(MessageKind::ServerSync, &server_sync).await
     }
 
-    pub async fn send_server_config(&self) -> Result<(), MumbleError> {
-        let mut server_config = ServerConfig::default();
-        server_config.set_allow_html(true);
-        server_config.set_message_length(512);
-        server_config.set_image_message_length(0);
+    pub async fn send_server_config(&self, settings: &ClientSettings) -> Result<(), MumbleError> {
+        let server_config = settings.get_server_config();
 
         self.send_message(MessageKind::ServerConfig, &server_config).await
     }
 
+    pub async fn send_suggest_config(&self, settings: &ClientSettings) -> Result<(), MumbleError> {
+        match settings.get_suggest_config() {
+            Some(suggest_config) => self.send_message(MessageKind::SuggestConfig, &suggest_config).await,
+            None => Ok(()),
+        }
+    }
+
     pub async fn send_voice_packet(&self, packet: Arc<VoicePacket<ClientBound>>) -> Result<(), MumbleError> {
+        // older codecs can't be translated to the protobuf format, the client couldn't play them anyways
+        if !packet.supports_format(self.udp_format) {
+            return Ok(());
+        }
+
         if let Some(addr) = self.udp_socket_addr.load_full() {
             let mut dest = BytesMut::new();
 
             {
-                self.crypt_state.lock().encrypt(&packet, &mut dest);
+                self.crypt_state.lock().encrypt(&packet, &mut dest, self.udp_format);
             }
 
             let buf = &dest.freeze()[..];
@@ -253,7 +389,11 @@ impl Client {
             match timeout(Duration::from_millis(250), self.udp_socket.send_to(buf, addr.as_ref())).await {
                 Ok(Ok(_)) => Ok(()),
                 Ok(Err(e)) => Err(MumbleError::Io(e)),
-                Err(_) => Err(MumbleError::PacketDiscarded)
+                Err(_) => {
+                    crate::metrics::VOICE_DROPPED_TOTAL.with_label_values(&["send_timeout"]).inc();
+
+                    Err(MumbleError::PacketDiscarded)
+                }
             }?;
 
             crate::metrics::MESSAGES_TOTAL
@@ -268,7 +408,7 @@ impl Client {
         }
 
         let mut data = BytesMut::new();
-        encode_voice_packet(&packet, &mut data);
+        self.udp_format.encode(packet.as_ref(), &mut data);
         let bytes = data.freeze();
 
         let mut tunnel_message = UDPTunnel::default();
@@ -277,13 +417,38 @@ impl Client {
         self.send_message(MessageKind::UDPTunnel, &tunnel_message).await
     }
 
-    pub fn update(&self, state: &UserState) {
-        if state.has_mute() {
-            self.set_mute(state.get_mute());
+    /// Returns the position used for routing, the server position wins over the one sent by the
+    /// client as long as it isn't stale
+    pub fn get_position(&self, config: &Config) -> Option<Position> {
+        if let Some(position) = self.server_position.load() {
+            if !position.is_expired(config.position_ttl) {
+                return Some(position);
+            }
         }
 
-        if state.has_deaf() {
-            self.set_deaf(state.get_deaf());
+        if config.trusted_positions_only {
+            return None;
+        }
+
+        self.client_position.load()
+    }
+
+    /// Stores the position the client sent, positions that aren't finite are ignored
+    pub fn set_client_position(&self, coordinates: [f32; 3]) {
+        if coordinates.iter().all(|v| v.is_finite()) {
+            self.client_position.store(Some(Position::new(coordinates, None, PositionSource::Client)));
+        }
+    }
+
+    pub fn set_server_position(&self, coordinates: [f32; 3], routing_bucket: Option<i32>) {
+        self.server_position
+            .store(Some(Position::new(coordinates, routing_bucket, PositionSource::Server)));
+    }
+
+    /// Removes the server position once the game server stopped updating it
+    pub fn clear_expired_server_position(&self, ttl: Duration) {
+        if self.server_position.load().is_some_and(|position| position.is_expired(ttl)) {
+            self.server_position.store(None);
         }
     }
 
@@ -307,6 +472,14 @@ impl Client {
         user_state.set_session(self.session_id);
         user_state.set_name(self.get_name().as_ref().clone());
 
+        if let Some(certificate_hash) = &self.certificate_hash {
+            user_state.set_hash(certificate_hash.sha1.clone());
+        }
+
+        if self.is_priority_speaker() {
+            user_state.set_priority_speaker(true);
+        }
+
         user_state
     }
 }
@@ -0,0 +1,127 @@
+use crate::certificate::CertificateHash;
+use crate::proto::mumble::{ServerConfig, SuggestConfig};
+use crate::server::constants::{MAX_BANDWIDTH_IN_BITS, MAX_CLIENTS};
+use crate::version::{parse_version, v2_to_v1};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use utoipa::ToSchema;
+
+/// Server settings that are decided at startup
+pub struct Config {
+    /// Certificate fingerprints (SHA1 or SHA256) of clients that are server admins
+    pub admin_certificates: Vec<String>,
+    /// Certificate fingerprints (SHA1 or SHA256) of clients that aren't allowed to connect
+    pub banned_certificates: Vec<String>,
+    /// Clients with an older protocol version (in the v2 format) get rejected
+    pub min_client_version: Option<u64>,
+    /// How many server list pings per second we answer for a single ip
+    pub ping_rate_limit: Option<f64>,
+    /// Clients that didn't send a ping over their TCP connection for this long are disconnected
+    pub ping_timeout: Duration,
+    /// Clients that didn't send a good UDP packet for this long get their crypt reset, they fall
+    /// back to tunneling voice over TCP until they set up UDP again
+    pub udp_timeout: Duration,
+    /// Positions pushed by the game server are ignored once they are older than this
+    pub position_ttl: Duration,
+    /// Ignore the positions sent by clients, only use the ones pushed by the game server
+    pub trusted_positions_only: bool,
+    /// Drop the audio of other speakers for listeners while they hear a priority speaker
+    pub priority_speaker_ducking: bool,
+    /// Extracts the FiveM server id from the username with its first capture group
+    pub server_id_pattern: Regex,
+}
+
+impl Config {
+    pub fn is_admin_certificate(&self, hash: &CertificateHash) -> bool {
+        self.admin_certificates.iter().any(|fingerprint| hash.matches(fingerprint))
+    }
+
+    pub fn is_banned_certificate(&self, hash: &CertificateHash) -> bool {
+        self.banned_certificates.iter().any(|fingerprint| hash.matches(fingerprint))
+    }
+
+    pub fn get_server_id(&self, name: &str) -> Option<u32> {
+        self.server_id_pattern
+            .captures(name)?
+            .get(1)
+            .and_then(|id| id.as_str().parse().ok())
+    }
+}
+
+/// Limits and suggestions sent to clients in ServerConfig and SuggestConfig, these can be changed
+/// at runtime through the http api
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct ClientSettings {
+    pub welcome_text: String,
+    pub allow_html: bool,
+    /// Maximum text message length
+    pub message_length: u32,
+    /// Maximum image message length, 0 disables images
+    pub image_message_length: u32,
+    /// Maximum amount of users, can't go over [MAX_CLIENTS]
+    pub max_users: u32,
+    /// Suggested client version (e.g. 1.4.0)
+    pub suggest_version: Option<String>,
+    pub suggest_positional: Option<bool>,
+    pub suggest_push_to_talk: Option<bool>,
+}
+
+impl Default for ClientSettings {
+    fn default() -> Self {
+        Self {
+            welcome_text: "SoZ Mumble Server".to_string(),
+            allow_html: true,
+            message_length: 512,
+            image_message_length: 0,
+            max_users: MAX_CLIENTS as u32,
+            suggest_version: None,
+            suggest_positional: None,
+            suggest_push_to_talk: None,
+        }
+    }
+}
+
+impl ClientSettings {
+    pub fn get_max_users(&self) -> usize {
+        (self.max_users as usize).min(MAX_CLIENTS)
+    }
+
+    pub fn get_server_config(&self) -> ServerConfig {
+        let mut server_config = ServerConfig::default();
+        server_config.set_max_bandwidth(MAX_BANDWIDTH_IN_BITS);
+        server_config.set_welcome_text(self.welcome_text.clone());
+        server_config.set_allow_html(self.allow_html);
+        server_config.set_message_length(self.message_length);
+        server_config.set_image_message_length(self.image_message_length);
+        server_config.set_max_users(self.get_max_users() as u32);
+
+        server_config
+    }
+
+    /// Returns the SuggestConfig message, or none if nothing is suggested
+    pub fn get_suggest_config(&self) -> Option<SuggestConfig> {
+        let version = self.suggest_version.as_deref().and_then(|version| parse_version(version).ok());
+
+        if version.is_none() && self.suggest_positional.is_none() && self.suggest_push_to_talk.is_none() {
+            return None;
+        }
+
+        let mut suggest_config = SuggestConfig::default();
+
+        if let Some(version) = version {
+            suggest_config.set_version_v1(v2_to_v1(version));
+            suggest_config.set_version_v2(version);
+        }
+
+        if let Some(positional) = self.suggest_positional {
+            suggest_config.set_positional(positional);
+        }
+
+        if let Some(push_to_talk) = self.suggest_push_to_talk {
+            suggest_config.set_push_to_talk(push_to_talk);
+        }
+
+        Some(suggest_config)
+    }
+}
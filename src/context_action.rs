@@ -0,0 +1,69 @@
+use crate::proto::mumble::{ContextActionModify, ContextActionModify_Context, ContextActionModify_Operation};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Where a context action shows up in the client
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ContextActionTarget {
+    Server,
+    Channel,
+    User,
+}
+
+impl ContextActionTarget {
+    fn flag(&self) -> u32 {
+        match self {
+            ContextActionTarget::Server => ContextActionModify_Context::Server as u32,
+            ContextActionTarget::Channel => ContextActionModify_Context::Channel as u32,
+            ContextActionTarget::User => ContextActionModify_Context::User as u32,
+        }
+    }
+}
+
+/// A right click action registered through the http api
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct ContextActionDefinition {
+    /// Identifier sent back by the client when the action is triggered
+    pub action: String,
+    /// Display name of the action
+    pub text: String,
+    pub targets: Vec<ContextActionTarget>,
+}
+
+impl ContextActionDefinition {
+    pub fn get_context_action_add(&self) -> ContextActionModify {
+        let mut modify = ContextActionModify::new();
+        modify.set_action(self.action.clone());
+        modify.set_text(self.text.clone());
+        modify.set_context(self.targets.iter().fold(0, |flags, target| flags | target.flag()));
+        modify.set_operation(ContextActionModify_Operation::Add);
+
+        modify
+    }
+
+    pub fn get_context_action_remove(&self) -> ContextActionModify {
+        let mut modify = ContextActionModify::new();
+        modify.set_action(self.action.clone());
+        modify.set_operation(ContextActionModify_Operation::Remove);
+
+        modify
+    }
+}
+
+/// A context action triggered by a client, queued until the game server picks it up
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct ContextActionEvent {
+    /// Increasing id, used by consumers to only fetch new events
+    pub id: u64,
+    pub action: String,
+    pub actor_session: u32,
+    pub actor_name: String,
+    /// Set when the actor is identified as an admin by their certificate
+    pub actor_admin: bool,
+    pub target_session: Option<u32>,
+    pub target_name: Option<String>,
+    pub channel_id: Option<u32>,
+    /// Unix timestamp in milliseconds
+    pub timestamp: u128,
+}
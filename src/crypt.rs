@@ -1,6 +1,6 @@
 use crate::error::DecryptError;
 use crate::proto::mumble::CryptSetup;
-use crate::voice::{decode_voice_packet, encode_voice_packet, VoicePacket, VoicePacketDst};
+use crate::voice::{VoicePacket, VoicePacketDst, VoicePacketFormat};
 use actix_web::web::BytesMut;
 use aes::cipher::generic_array::GenericArray;
 use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
@@ -89,14 +89,14 @@ impl CryptState {
     }
 
     /// Encrypts an encoded voice packet and returns the resulting bytes.
-    pub fn encrypt<EncodeDst: VoicePacketDst>(&mut self, packet: &VoicePacket<EncodeDst>, dst: &mut BytesMut) {
+    pub fn encrypt<EncodeDst: VoicePacketDst>(&mut self, packet: &VoicePacket<EncodeDst>, dst: &mut BytesMut, format: VoicePacketFormat) {
         self.encrypt_nonce = self.encrypt_nonce.wrapping_add(1);
 
         // Leave four bytes for header
         dst.resize(4, 0);
         let mut inner = dst.split_off(4);
 
-        encode_voice_packet(packet, &mut inner);
+        format.encode(packet, &mut inner);
 
         let tag = self.ocb_encrypt(inner.as_mut());
         dst.unsplit(inner);
@@ -106,7 +106,11 @@ impl CryptState {
     }
 
     /// Decrypts a voice packet and (if successful) returns the `Result` of parsing the packet.
-    pub fn decrypt<DecodeDst: VoicePacketDst>(&mut self, buf: &mut BytesMut) -> Result<VoicePacket<DecodeDst>, DecryptError> {
+    pub fn decrypt<DecodeDst: VoicePacketDst>(
+        &mut self,
+        buf: &mut BytesMut,
+        format: VoicePacketFormat,
+    ) -> Result<VoicePacket<DecodeDst>, DecryptError> {
         if buf.len() < 4 {
             return Err(DecryptError::Eof);
         }
@@ -154,15 +158,23 @@ impl CryptState {
         self.decrypt_history[nonce_0 as usize] = (self.decrypt_nonce >> 8) as u8;
         self.good += 1;
         self.last_good = Instant::now();
+        crate::metrics::VOICE_PACKETS_TOTAL.with_label_values(&["good"]).inc();
 
         if late {
             self.late += 1;
             self.decrypt_nonce = saved_nonce;
+            crate::metrics::VOICE_PACKETS_TOTAL.with_label_values(&["late"]).inc();
         }
 
         self.lost = (self.lost as i32 + lost) as u32;
 
-        decode_voice_packet(buf)
+        // a late packet was already counted in a gap, unlike the count of the client the counter
+        // can't take it back
+        if lost > 0 {
+            crate::metrics::VOICE_PACKETS_TOTAL.with_label_values(&["lost"]).inc_by(lost as u64);
+        }
+
+        format.decode(buf)
     }
 
     /// Encrypt the provided buffer using AES-OCB, returning the tag.
@@ -0,0 +1,95 @@
+use parking_lot::Mutex;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::time::Instant;
+
+/// What the clean loop has to check on a client when its deadline passes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DeadlineKind {
+    /// Ping timeout of the TCP connection and crypt reset of the UDP one
+    Liveness,
+    /// End of a talk spurt that didn't get an end of transmission bit
+    Talking,
+}
+
+/// Per client deadlines of the clean loop, so it only looks at the clients that may have timed out
+/// instead of every client on every run
+///
+/// Deadlines aren't moved when a client shows a sign of life, the check finds out it didn't time
+/// out and schedules the next one instead. Deadlines of disconnected clients are dropped when they
+/// pass.
+#[derive(Default)]
+pub struct Deadlines {
+    queue: Mutex<BinaryHeap<Reverse<(Instant, u32, DeadlineKind)>>>,
+}
+
+impl Deadlines {
+    pub fn schedule(&self, at: Instant, session_id: u32, kind: DeadlineKind) {
+        self.queue.lock().push(Reverse((at, session_id, kind)));
+    }
+
+    /// Removes the deadlines that passed, a check scheduled more than once for the same client is
+    /// only returned once
+    pub fn take_expired(&self, now: Instant) -> Vec<(u32, DeadlineKind)> {
+        let mut expired = Vec::new();
+
+        {
+            let mut queue = self.queue.lock();
+
+            while queue.peek().is_some_and(|Reverse((at, _, _))| *at <= now) {
+                if let Some(Reverse((_, session_id, kind))) = queue.pop() {
+                    expired.push((session_id, kind));
+                }
+            }
+        }
+
+        expired.sort_unstable();
+        expired.dedup();
+
+        expired
+    }
+
+    /// When the earliest deadline passes
+    pub fn next_at(&self) -> Option<Instant> {
+        self.queue.lock().peek().map(|Reverse((at, _, _))| *at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn only_passed_deadlines_are_taken() {
+        let deadlines = Deadlines::default();
+        let now = Instant::now();
+
+        deadlines.schedule(now + Duration::from_secs(2), 1, DeadlineKind::Liveness);
+        deadlines.schedule(now, 2, DeadlineKind::Liveness);
+        deadlines.schedule(now - Duration::from_secs(1), 3, DeadlineKind::Talking);
+
+        assert_eq!(
+            deadlines.take_expired(now),
+            vec![(2, DeadlineKind::Liveness), (3, DeadlineKind::Talking)]
+        );
+        assert_eq!(deadlines.next_at(), Some(now + Duration::from_secs(2)));
+        assert!(deadlines.take_expired(now).is_empty());
+    }
+
+    #[test]
+    fn duplicate_checks_are_merged() {
+        let deadlines = Deadlines::default();
+        let now = Instant::now();
+
+        deadlines.schedule(now, 1, DeadlineKind::Talking);
+        deadlines.schedule(now - Duration::from_millis(100), 1, DeadlineKind::Talking);
+        deadlines.schedule(now, 1, DeadlineKind::Liveness);
+
+        assert_eq!(
+            deadlines.take_expired(now),
+            vec![(1, DeadlineKind::Liveness), (1, DeadlineKind::Talking)]
+        );
+        assert_eq!(deadlines.next_at(), None);
+    }
+}
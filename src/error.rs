@@ -1,4 +1,8 @@
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use utoipa::ToSchema;
 
 use crate::message::ClientMessage;
 
@@ -10,6 +14,8 @@ pub enum MumbleError {
     Io(#[from] tokio::io::Error),
     #[error("protobuf error: {0}")]
     Parse(#[from] protobuf::ProtobufError),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
     #[error("voice decrypt error: {0}")]
     Decrypt(#[from] DecryptError),
     #[error("Client Forcefully disconnected from server")]
@@ -21,10 +27,70 @@ pub enum MumbleError {
     #[error("channel doesn't exist")]
     ChannelDoesntExist,
     #[error("voice packet took to long to send, discarding")]
-    PacketDiscarded
+    PacketDiscarded,
+    #[error("user not found")]
+    UserNotFound,
+    #[error("user has no position")]
+    PositionNotFound,
+    #[error("context action not found")]
+    ContextActionNotFound,
+    #[error("invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("payload too large")]
+    PayloadTooLarge,
+    #[error("missing or invalid credentials")]
+    Unauthorized,
+    #[error("credentials are missing the {0} scope")]
+    Forbidden(String),
 }
 
-impl actix_web::error::ResponseError for MumbleError {}
+impl MumbleError {
+    /// Stable identifier of the error in the json body returned by the http api
+    pub fn code(&self) -> &'static str {
+        match self {
+            MumbleError::UserNotFound => "user_not_found",
+            MumbleError::ChannelDoesntExist => "channel_not_found",
+            MumbleError::PositionNotFound => "position_not_found",
+            MumbleError::ContextActionNotFound => "context_action_not_found",
+            MumbleError::InvalidRequest(_) => "invalid_request",
+            MumbleError::PayloadTooLarge => "payload_too_large",
+            MumbleError::Unauthorized => "unauthorized",
+            MumbleError::Forbidden(_) => "forbidden",
+            _ => "internal_error",
+        }
+    }
+}
+
+/// Body of every error returned by the http api
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ErrorBody {
+    /// Stable identifier of the error, e.g. `user_not_found`
+    pub code: String,
+    pub message: String,
+}
+
+impl actix_web::error::ResponseError for MumbleError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            MumbleError::UserNotFound
+            | MumbleError::ChannelDoesntExist
+            | MumbleError::PositionNotFound
+            | MumbleError::ContextActionNotFound => StatusCode::NOT_FOUND,
+            MumbleError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            MumbleError::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            MumbleError::Unauthorized => StatusCode::UNAUTHORIZED,
+            MumbleError::Forbidden(_) => StatusCode::FORBIDDEN,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(ErrorBody {
+            code: self.code().to_string(),
+            message: self.to_string(),
+        })
+    }
+}
 
 #[derive(Error, Debug)]
 pub enum DecryptError {
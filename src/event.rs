@@ -0,0 +1,146 @@
+use crate::server::constants::{EVENT_CHANNEL_CAPACITY, MAX_EVENT_HISTORY};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+
+/// Something that happened on the server, pushed to the consumers of the event stream
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    UserConnected {
+        session: u32,
+        name: String,
+    },
+    UserDisconnected {
+        session: u32,
+        name: String,
+    },
+    ChannelJoined {
+        session: u32,
+        channel_id: u32,
+    },
+    ChannelLeft {
+        session: u32,
+        channel_id: u32,
+    },
+    MuteChanged {
+        session: u32,
+        mute: bool,
+    },
+    DeafChanged {
+        session: u32,
+        deaf: bool,
+    },
+    ChannelCreated {
+        channel_id: u32,
+        parent_id: Option<u32>,
+        name: String,
+    },
+    ChannelRemoved {
+        channel_id: u32,
+    },
+    TalkingStarted {
+        session: u32,
+        target: u8,
+    },
+    /// `talk_time` is the length of the talk spurt in milliseconds
+    TalkingStopped {
+        session: u32,
+        talk_time: u128,
+    },
+    CryptReset {
+        session: u32,
+    },
+}
+
+impl Event {
+    /// Name of the event, same as the `type` field of the json
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::UserConnected { .. } => "user_connected",
+            Event::UserDisconnected { .. } => "user_disconnected",
+            Event::ChannelJoined { .. } => "channel_joined",
+            Event::ChannelLeft { .. } => "channel_left",
+            Event::MuteChanged { .. } => "mute_changed",
+            Event::DeafChanged { .. } => "deaf_changed",
+            Event::ChannelCreated { .. } => "channel_created",
+            Event::ChannelRemoved { .. } => "channel_removed",
+            Event::TalkingStarted { .. } => "talking_started",
+            Event::TalkingStopped { .. } => "talking_stopped",
+            Event::CryptReset { .. } => "crypt_reset",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct EventRecord {
+    /// Sequence number of the event, consumers can use it to resume the stream
+    pub id: u64,
+    /// Unix timestamp in milliseconds
+    pub timestamp: u128,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// Publishes events to the live subscribers and keeps the most recent ones so a reconnecting
+/// consumer can catch up on what it missed
+pub struct EventBus {
+    sender: broadcast::Sender<Arc<EventRecord>>,
+    history: Mutex<VecDeque<Arc<EventRecord>>>,
+    next_id: AtomicU64,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+
+        Self {
+            sender,
+            history: Mutex::new(VecDeque::with_capacity(MAX_EVENT_HISTORY)),
+            next_id: AtomicU64::new(1),
+        }
+    }
+}
+
+impl EventBus {
+    pub fn emit(&self, event: Event) {
+        // the history lock is held while sending so subscribers never see an event twice or miss one
+        // between the history and the live events
+        let mut history = self.history.lock();
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+
+        let record = Arc::new(EventRecord {
+            id,
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis(),
+            event,
+        });
+
+        if history.len() >= MAX_EVENT_HISTORY {
+            history.pop_front();
+        }
+
+        history.push_back(record.clone());
+
+        // an error only means nobody is subscribed
+        let _ = self.sender.send(record);
+    }
+
+    /// Subscribes to new events, and returns the events in history with an id greater than `after`
+    pub fn subscribe(&self, after: Option<u64>) -> (Vec<Arc<EventRecord>>, broadcast::Receiver<Arc<EventRecord>>) {
+        let history = self.history.lock();
+        let receiver = self.sender.subscribe();
+
+        let missed = match after {
+            Some(after) => history.iter().filter(|record| record.id > after).cloned().collect(),
+            None => Vec::new(),
+        };
+
+        (missed, receiver)
+    }
+}
@@ -0,0 +1,36 @@
+use crate::client::ClientRef;
+use crate::error::MumbleError;
+use crate::handler::Handler;
+use crate::proto::mumble::ContextAction;
+use crate::state::ServerStateRef;
+
+impl Handler for ContextAction {
+    async fn handle(&self, state: ServerStateRef, client: ClientRef) -> Result<(), MumbleError> {
+        if !state.context_actions.contains(self.get_action()) {
+            tracing::warn!("{} triggered unknown context action {}", client, self.get_action());
+
+            return Ok(());
+        }
+
+        let target = if self.has_session() {
+            match state.clients.get(&self.get_session()) {
+                Some(target) => Some(target.clone()),
+                None => {
+                    tracing::debug!("{} triggered context action on unknown session {}", client, self.get_session());
+
+                    return Ok(());
+                }
+            }
+        } else {
+            None
+        };
+
+        let channel_id = self.has_channel_id().then(|| self.get_channel_id());
+
+        tracing::info!("{} triggered context action {}", client, self.get_action());
+
+        state.push_context_action_event(self.get_action(), &client, target.as_ref(), channel_id);
+
+        Ok(())
+    }
+}
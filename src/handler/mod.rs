@@ -1,22 +1,24 @@
 mod authenticate;
 mod channel_state;
+mod context_action;
 mod crypt_setup;
 mod permission_query;
 mod ping;
+mod plugin_data_transmission;
+mod user_remove;
 mod user_state;
+mod user_stats;
 mod version;
 mod voice_packet;
 mod voice_target;
 
-use anyhow::anyhow;
-
 use crate::client::ClientRef;
 use crate::error::MumbleError;
 use crate::message::ClientMessage;
 use crate::proto::mumble;
 use crate::proto::MessageKind;
 use crate::state::ServerStateRef;
-use crate::voice::{decode_voice_packet, ServerBound};
+use crate::voice::ServerBound;
 use anyhow::Context;
 use bytes::BytesMut;
 use protobuf::Message;
@@ -68,7 +70,7 @@ impl MessageHandler {
                 match message_kind {
                     MessageKind::Version => Self::try_handle::<mumble::Version>(&buf, state, client).await.context("kind: Version"),
                     MessageKind::UDPTunnel => {
-                        let voice_packet = match decode_voice_packet::<ServerBound>(&mut buf) {
+                        let voice_packet = match client.udp_format.decode::<ServerBound>(&mut buf) {
                             Ok(voice_packet) => voice_packet,
                             Err(e) => {
                                 tracing::error!("error decoding voice packet: {}", e);
@@ -87,7 +89,11 @@ impl MessageHandler {
                     MessageKind::CryptSetup => Self::try_handle::<mumble::CryptSetup>(&buf, state, client).await.context("kind: CryptSetup"),
                     MessageKind::PermissionQuery => Self::try_handle::<mumble::PermissionQuery>(&buf, state, client).await.context("kind: PermissionQuery"),
                     MessageKind::UserState => Self::try_handle::<mumble::UserState>(&buf, state, client).await.context("kind: UserState"),
+                    MessageKind::UserRemove => Self::try_handle::<mumble::UserRemove>(&buf, state, client).await.context("kind: UserRemove"),
                     MessageKind::VoiceTarget => Self::try_handle::<mumble::VoiceTarget>(&buf, state, client).await.context("kind: VoiceTarget"),
+                    MessageKind::ContextAction => Self::try_handle::<mumble::ContextAction>(&buf, state, client).await.context("kind: ContextAction"),
+                    MessageKind::PluginDataTransmission => Self::try_handle::<mumble::PluginDataTransmission>(&buf, state, client).await.context("kind: PluginDataTransmission"),
+                    MessageKind::UserStats => Self::try_handle::<mumble::UserStats>(&buf, state, client).await.context("kind: UserStats"),
                     _ => {
                         tracing::warn!("unsupported message kind: {:?}", message_kind);
 
@@ -0,0 +1,45 @@
+use crate::client::ClientRef;
+use crate::error::MumbleError;
+use crate::handler::Handler;
+use crate::proto::mumble::PluginDataTransmission;
+use crate::server::constants::{MAX_PLUGIN_DATA_ID_SIZE, MAX_PLUGIN_DATA_RECEIVERS, MAX_PLUGIN_DATA_SIZE};
+use crate::state::ServerStateRef;
+
+impl Handler for PluginDataTransmission {
+    async fn handle(&self, state: ServerStateRef, client: ClientRef) -> Result<(), MumbleError> {
+        if self.get_data().len() > MAX_PLUGIN_DATA_SIZE || self.get_dataID().len() > MAX_PLUGIN_DATA_ID_SIZE {
+            tracing::warn!(
+                "{} sent plugin data that is too large ({} bytes, id {} bytes), dropping",
+                client,
+                self.get_data().len(),
+                self.get_dataID().len()
+            );
+
+            return Ok(());
+        }
+
+        if self.get_receiverSessions().len() > MAX_PLUGIN_DATA_RECEIVERS {
+            tracing::warn!(
+                "{} sent plugin data to {} receivers, dropping",
+                client,
+                self.get_receiverSessions().len()
+            );
+
+            return Ok(());
+        }
+
+        let allowed = { client.plugin_rate_limit.lock().try_acquire() };
+
+        if !allowed {
+            tracing::debug!("{} is sending plugin data too fast, dropping", client);
+
+            return Ok(());
+        }
+
+        let mut message = self.clone();
+        // never trust the sender session the client gives us
+        message.set_senderSession(client.session_id);
+
+        state.send_plugin_data(&message)
+    }
+}
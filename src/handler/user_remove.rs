@@ -0,0 +1,22 @@
+use crate::client::ClientRef;
+use crate::error::MumbleError;
+use crate::handler::Handler;
+use crate::proto::mumble::UserRemove;
+use crate::state::ServerStateRef;
+
+impl Handler for UserRemove {
+    async fn handle(&self, state: ServerStateRef, client: ClientRef) -> Result<(), MumbleError> {
+        // only admins can kick or ban, of others or of themselves
+        if !client.admin {
+            tracing::warn!("client {} tried to remove a user without being an admin", client);
+
+            return Ok(());
+        }
+
+        let Some(target) = state.clients.read(&self.get_session(), |_, target| target.clone()) else {
+            return Ok(());
+        };
+
+        state.remove_client(&client, &target, self.get_reason(), self.get_ban())
+    }
+}
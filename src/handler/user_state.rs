@@ -1,3 +1,4 @@
+use crate::audit::{AuditAction, AuditActor, AuditTarget};
 use crate::client::ClientRef;
 use crate::error::MumbleError;
 use crate::handler::Handler;
@@ -8,11 +9,44 @@ impl Handler for UserState {
     async fn handle(&self, state: ServerStateRef, client: ClientRef) -> Result<(), MumbleError> {
         let session_id = { client.session_id };
 
+        if self.has_priority_speaker() {
+            // only admins can change the priority speaker status, of themselves or of others
+            if client.admin {
+                if let Some(target) = state.clients.get(&self.get_session()).map(|target| target.get().clone()) {
+                    if target.is_priority_speaker() != self.get_priority_speaker() {
+                        state.audit.record(
+                            AuditActor::Client {
+                                session: client.session_id,
+                                name: client.get_name().as_ref().clone(),
+                            },
+                            AuditAction::PrioritySpeaker,
+                            AuditTarget::user(&target),
+                            target.is_priority_speaker(),
+                            self.get_priority_speaker(),
+                        );
+                    }
+
+                    state.set_client_priority_speaker(&target, self.get_priority_speaker())?;
+                }
+            } else {
+                tracing::warn!(
+                    "client {} tried to change the priority speaker status without being an admin",
+                    client
+                );
+            }
+        }
+
         if self.get_session() != session_id {
             return Ok(());
         }
 
-        client.update(self);
+        if self.has_mute() {
+            state.set_client_mute(&client, self.get_mute());
+        }
+
+        if self.has_deaf() {
+            state.set_client_deaf(&client, self.get_deaf());
+        }
 
         if self.has_channel_id() {
             state.set_client_channel(client.clone(), self.get_channel_id())?;
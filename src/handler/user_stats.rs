@@ -0,0 +1,51 @@
+use crate::client::ClientRef;
+use crate::error::MumbleError;
+use crate::handler::Handler;
+use crate::proto::mumble::{UserStats, UserStats_Stats};
+use crate::proto::MessageKind;
+use crate::state::ServerStateRef;
+use std::net::IpAddr;
+use std::time::Instant;
+
+impl Handler for UserStats {
+    async fn handle(&self, state: ServerStateRef, client: ClientRef) -> Result<(), MumbleError> {
+        let target = match state.clients.get(&self.get_session()) {
+            Some(target) => target.clone(),
+            None => return Ok(()),
+        };
+
+        let mut stats = UserStats::new();
+        stats.set_session(target.session_id);
+        stats.set_stats_only(self.get_stats_only());
+
+        {
+            let crypt_state = target.crypt_state.lock();
+            let mut from_client = UserStats_Stats::new();
+            from_client.set_good(crypt_state.good);
+            from_client.set_late(crypt_state.late);
+            from_client.set_lost(crypt_state.lost);
+            from_client.set_resync(crypt_state.resync);
+            stats.set_from_client(from_client);
+        }
+
+        stats.set_onlinesecs(Instant::now().duration_since(target.connected_at).as_secs() as u32);
+
+        if !self.get_stats_only() {
+            stats.set_version(target.version.to_version());
+            stats.set_celt_versions(target.codecs.clone());
+            stats.set_opus(target.authenticate.get_opus());
+
+            // only admins and the user themselves get to see the ip address
+            if client.admin || client.session_id == target.session_id {
+                let address = match target.peer_ip {
+                    IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+                    IpAddr::V6(ip) => ip,
+                };
+
+                stats.set_address(address.octets().to_vec());
+            }
+        }
+
+        client.send_message(MessageKind::UserStats, &stats).await
+    }
+}
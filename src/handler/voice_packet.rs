@@ -1,11 +1,15 @@
+use crate::channel::ChannelRef;
 use crate::client::ClientRef;
 use crate::error::MumbleError;
 use crate::message::ClientMessage;
+use crate::proximity::ProximitySettings;
 use crate::state::ServerStateRef;
-use crate::voice::{ClientBound, VoicePacket};
-use std::collections::HashMap;
+use crate::voice::{parse_position_info, ClientBound, VoicePacket, VoicePacketPayload};
+use std::collections::{HashMap, HashSet};
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc::error::TrySendError;
 
 use super::Handler;
 
@@ -17,11 +21,34 @@ impl Handler for VoicePacket<ClientBound> {
             return Ok(());
         }
 
-        if let VoicePacket::<ClientBound>::Audio { target, session_id, .. } = self {
+        let routing_started = Instant::now();
+
+        if let VoicePacket::<ClientBound>::Audio {
+            target,
+            session_id,
+            payload,
+            position_info,
+            ..
+        } = self
+        {
+            let end_of_transmission = matches!(payload, VoicePacketPayload::Opus(_, true));
+
+            let talking_changes = { client.talking.lock().on_voice_packet(*target, end_of_transmission) };
+
+            for change in talking_changes {
+                state.emit_talking_change(&client, change);
+            }
+
+            if let Some(position) = position_info.as_deref().and_then(parse_position_info) {
+                client.set_client_position(position);
+            }
+
             // copy the data into an arc so we can reuse the packet for each client
             let packet = Arc::new(self.clone());
 
             let mut listening_clients = HashMap::new();
+            // listeners reached through a global channel, they can hear speakers of other instances
+            let mut global_listeners = HashSet::new();
 
             match *target {
                 // Channel
@@ -30,13 +57,25 @@ impl Handler for VoicePacket<ClientBound> {
                     let channel_result = state.channels.get(&channel_id);
 
                     if let Some(channel) = channel_result {
-                        channel.get_clients().scan(|k, v| {
-                            listening_clients.insert(*k, v.clone());
-                        });
+                        let global = channel.is_global();
+
+                        match channel.proximity.load_full() {
+                            Some(proximity) => add_proximity_listeners(&state, &client, &channel, &proximity, &mut listening_clients),
+                            None => {
+                                channel.get_clients().scan(|k, v| {
+                                    listening_clients.insert(*k, v.clone());
+                                });
+                            }
+                        }
+
+                        if global {
+                            global_listeners.extend(listening_clients.keys().copied());
+                        }
                     }
                 }
                 // Voice target (whisper)
                 1..=30 => {
+                    let global_target = client.is_global_target(*target);
                     let target = client.get_target(*target);
 
                     if let Some(target) = target {
@@ -45,6 +84,10 @@ impl Handler for VoicePacket<ClientBound> {
 
                             if let Some(client) = client_result {
                                 listening_clients.insert(*client_id, client.clone());
+
+                                if global_target {
+                                    global_listeners.insert(*client_id);
+                                }
                             }
                         });
 
@@ -52,12 +95,22 @@ impl Handler for VoicePacket<ClientBound> {
                             let channel_result = state.channels.get(channel_id);
 
                             if let Some(channel) = channel_result {
+                                let global = channel.is_global();
+
                                 channel.get_listeners().scan(|k, v| {
                                     listening_clients.insert(*k, v.clone());
+
+                                    if global {
+                                        global_listeners.insert(*k);
+                                    }
                                 });
 
                                 channel.get_clients().scan(|k, v| {
                                     listening_clients.insert(*k, v.clone());
+
+                                    if global {
+                                        global_listeners.insert(*k);
+                                    }
                                 });
                             }
                         });
@@ -74,22 +127,149 @@ impl Handler for VoicePacket<ClientBound> {
                 }
             }
 
+            let instance = client.get_instance();
+            let priority_speaker = client.is_priority_speaker();
+            let ducking = state.config.priority_speaker_ducking;
+            let mut queued = 0;
+
             for client in listening_clients.values() {
                 if client.is_deaf() {
                     continue;
                 }
 
-                if client.session_id != *session_id {
-                    match client.publisher.try_send(ClientMessage::SendVoicePacket(packet.clone())) {
-                        Ok(_) => {}
-                        Err(err) => {
-                            tracing::error!("error sending voice packet message to {}: {}", client, err);
+                // players in other instances are in a different world, they shouldn't hear each other
+                if client.get_instance() != instance && !global_listeners.contains(&client.session_id) {
+                    continue;
+                }
+
+                if client.session_id == *session_id {
+                    continue;
+                }
+
+                // ducking only happens on the server when enabled, the clients duck by themselves
+                if ducking {
+                    if priority_speaker {
+                        client.priority_speaker_heard_at.store(Some(Instant::now()));
+                    } else if client.is_ducked() {
+                        continue;
+                    }
+                }
+
+                match client.publisher.try_send(ClientMessage::SendVoicePacket(packet.clone())) {
+                    Ok(_) => queued += 1,
+                    Err(err) => {
+                        tracing::error!("error sending voice packet message to {}: {}", client, err);
+
+                        if let TrySendError::Full(_) = err {
+                            crate::metrics::VOICE_DROPPED_TOTAL.with_label_values(&["queue_full"]).inc();
                         }
                     }
                 }
             }
+
+            crate::metrics::VOICE_FANOUT.observe(queued as f64);
+            crate::metrics::VOICE_ROUTING_SECONDS.observe(routing_started.elapsed().as_secs_f64());
         }
 
         Ok(())
     }
 }
+
+/// Adds the clients of a proximity channel that are close enough to hear the speaker, clients
+/// without a known position can't hear or be heard in these channels
+fn add_proximity_listeners(
+    state: &ServerStateRef,
+    speaker: &ClientRef,
+    channel: &ChannelRef,
+    proximity: &ProximitySettings,
+    listening_clients: &mut HashMap<u32, ClientRef>,
+) {
+    let mut previous_listeners = speaker.proximity_listeners.lock();
+    let mut current_listeners = HashSet::new();
+
+    if let Some(speaker_position) = speaker.get_position(&state.config) {
+        channel.get_clients().scan(|k, v| {
+            let Some(listener_position) = v.get_position(&state.config) else {
+                return;
+            };
+
+            if proximity.is_audible(
+                speaker_position.coordinates,
+                listener_position.coordinates,
+                previous_listeners.contains(k),
+            ) {
+                current_listeners.insert(*k);
+                listening_clients.insert(*k, v.clone());
+            }
+        });
+    }
+
+    *previous_listeners = current_listeners;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{connect_client, connect_listener, test_state};
+    use crate::voice::ServerBound;
+    use bytes::Bytes;
+    use std::marker::PhantomData;
+    use tokio::sync::mpsc::Receiver;
+
+    fn audio(target: u8, session_id: u32) -> VoicePacket<ClientBound> {
+        VoicePacket::<ServerBound>::Audio {
+            _dst: PhantomData,
+            target,
+            session_id: (),
+            seq_num: 0,
+            payload: VoicePacketPayload::Opus(Bytes::from_static(&[0; 8]), false),
+            position_info: None,
+        }
+        .into_client_bound(session_id)
+    }
+
+    fn received_voice(messages: &mut Receiver<ClientMessage>) -> bool {
+        let mut voice = false;
+
+        while let Ok(message) = messages.try_recv() {
+            voice |= matches!(message, ClientMessage::SendVoicePacket(_));
+        }
+
+        voice
+    }
+
+    #[actix_web::test]
+    async fn voice_only_crosses_instances_through_global_channels_and_targets() {
+        let state = test_state().await;
+        let speaker = connect_client(&state, "speaker").await;
+        let (listener, mut messages) = connect_listener(&state, "listener").await;
+
+        state.set_client_channel(speaker.clone(), 0).unwrap();
+        state.set_client_channel(listener.clone(), 0).unwrap();
+        listener.set_instance(1);
+
+        for target in [1, 2] {
+            speaker.get_target(target).unwrap().sessions.insert(listener.session_id).unwrap();
+        }
+
+        for target in [0, 1, 2] {
+            audio(target, speaker.session_id)
+                .handle(state.clone(), speaker.clone())
+                .await
+                .unwrap();
+            assert!(!received_voice(&mut messages), "target {} crossed instances", target);
+        }
+
+        state.channels.get(&0).unwrap().set_global(true);
+        audio(0, speaker.session_id).handle(state.clone(), speaker.clone()).await.unwrap();
+        assert!(received_voice(&mut messages));
+
+        // only the flagged target crosses, a whisper on another target stays in the instance
+        speaker.set_global_target(1, true);
+        audio(1, speaker.session_id).handle(state.clone(), speaker.clone()).await.unwrap();
+        assert!(received_voice(&mut messages));
+
+        audio(2, speaker.session_id).handle(state.clone(), speaker.clone()).await.unwrap();
+        assert!(!received_voice(&mut messages));
+    }
+}
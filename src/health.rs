@@ -0,0 +1,114 @@
+use crate::server::constants::{CLEAN_LOOP_STALE_AFTER, UDP_LOOP_STALE_AFTER};
+use crossbeam::atomic::AtomicCell;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+use utoipa::ToSchema;
+
+/// Heartbeats of the server loops, used by the health and readiness endpoints
+pub struct Health {
+    started_at: Instant,
+    /// Set once every listener is up, and cleared again when the server shuts down
+    ready: AtomicBool,
+    tcp_running: AtomicBool,
+    udp_received_at: AtomicCell<Option<Instant>>,
+    clean_ran_at: AtomicCell<Option<Instant>>,
+    decrypted_at: AtomicCell<Option<Instant>>,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            started_at: Instant::now(),
+            ready: AtomicBool::new(false),
+            tcp_running: AtomicBool::new(false),
+            udp_received_at: AtomicCell::new(None),
+            clean_ran_at: AtomicCell::new(None),
+            decrypted_at: AtomicCell::new(None),
+        }
+    }
+}
+
+impl Health {
+    pub fn set_ready(&self, ready: bool) {
+        self.ready.store(ready, Ordering::Relaxed);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Relaxed)
+    }
+
+    pub fn set_tcp_running(&self, running: bool) {
+        self.tcp_running.store(running, Ordering::Relaxed);
+    }
+
+    pub fn udp_received(&self) {
+        self.udp_received_at.store(Some(Instant::now()));
+    }
+
+    pub fn clean_ran(&self) {
+        self.clean_ran_at.store(Some(Instant::now()));
+    }
+
+    pub fn decrypted(&self) {
+        self.decrypted_at.store(Some(Instant::now()));
+    }
+
+    /// Checks the loops, `udp_clients` is the number of clients that have a UDP connection, the
+    /// UDP loop is only expected to advance when there is one
+    pub fn report(&self, clients: usize, max_clients: usize, udp_clients: usize) -> HealthReport {
+        let elapsed = |at: Option<Instant>| at.map(|at| at.elapsed().as_millis());
+
+        let udp_received = elapsed(self.udp_received_at.load());
+        let clean_ran = elapsed(self.clean_ran_at.load());
+
+        // the loops get some time to run once before they count as stalled
+        let grace = self.started_at.elapsed();
+
+        HealthReport {
+            ready: self.is_ready(),
+            tcp_running: self.tcp_running.load(Ordering::Relaxed),
+            udp_advancing: udp_clients == 0 || udp_received.unwrap_or(grace.as_millis()) <= UDP_LOOP_STALE_AFTER.as_millis(),
+            clean_running: clean_ran.unwrap_or(grace.as_millis()) <= CLEAN_LOOP_STALE_AFTER.as_millis(),
+            clients_within_limit: clients <= max_clients,
+            clients,
+            max_clients,
+            udp_clients,
+            last_udp_receive: udp_received,
+            last_clean_run: clean_ran,
+            last_decrypt: elapsed(self.decrypted_at.load()),
+            uptime: grace.as_secs(),
+        }
+    }
+}
+
+/// State of the server as returned by the health endpoints, durations are in milliseconds since
+/// the last time it happened
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct HealthReport {
+    pub ready: bool,
+    pub tcp_running: bool,
+    pub udp_advancing: bool,
+    pub clean_running: bool,
+    pub clients_within_limit: bool,
+    pub clients: usize,
+    pub max_clients: usize,
+    pub udp_clients: usize,
+    pub last_udp_receive: Option<u128>,
+    pub last_clean_run: Option<u128>,
+    pub last_decrypt: Option<u128>,
+    /// Seconds since the server started
+    pub uptime: u64,
+}
+
+impl HealthReport {
+    /// The server is alive as long as its loops are running
+    pub fn is_healthy(&self) -> bool {
+        self.tcp_running && self.udp_advancing && self.clean_running
+    }
+
+    /// The server takes new clients when it is healthy, done starting and not full
+    pub fn is_ready(&self) -> bool {
+        self.ready && self.is_healthy() && self.clients_within_limit
+    }
+}
@@ -0,0 +1,55 @@
+use crate::audit::{AuditAction, AuditEntry, AuditTarget};
+use crate::{error::MumbleError, state::ServerStateRef};
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 1000;
+
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    /// Name of the api token or of the client that made the change
+    actor: Option<String>,
+    action: Option<AuditAction>,
+    /// Session of the user the change was made to
+    session: Option<u32>,
+    channel_id: Option<u32>,
+    /// Only return entries with an id lower than this one, to fetch the next page
+    before: Option<u64>,
+    limit: Option<usize>,
+}
+
+impl AuditQuery {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.actor.as_deref().is_none_or(|actor| entry.actor.name() == actor)
+            && self.action.is_none_or(|action| entry.action == action)
+            && self
+                .session
+                .is_none_or(|session| matches!(entry.target, AuditTarget::User { session: s, .. } if s == session))
+            && self
+                .channel_id
+                .is_none_or(|channel_id| matches!(entry.target, AuditTarget::Channel { channel_id: c } if c == channel_id))
+            && self.before.is_none_or(|before| entry.id < before)
+    }
+}
+
+/// Returns the most recent administrative changes, newest first
+#[utoipa::path(
+    get,
+    path = "/audit",
+    tag = "audit",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "Audit entries matching the filters", body = Vec<AuditEntry>),
+    )
+)]
+#[actix_web::get("/audit")]
+pub async fn get_audit(query: web::Query<AuditQuery>, state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+    let entries = state.audit.query(|entry| query.matches(entry), limit);
+    let entries: Vec<&AuditEntry> = entries.iter().map(AsRef::as_ref).collect();
+
+    Ok(HttpResponse::Ok().json(&entries))
+}
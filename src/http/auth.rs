@@ -0,0 +1,255 @@
+use crate::audit::AuditActor;
+use crate::error::MumbleError;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{Header, HeaderValue, WWW_AUTHENTICATE};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, FromRequest, HttpMessage, HttpRequest, HttpResponse, ResponseError};
+use actix_web_httpauth::headers::authorization::{Authorization, Basic, Bearer};
+use futures::future::{ready, Ready};
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// What an api token is allowed to do
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiScope {
+    /// Read the state of the server and its users, and the event stream
+    Status,
+    /// Read the prometheus metrics
+    Metrics,
+    /// Change the state of users: mute, deaf, positions, instances, plugin data and context actions
+    Moderate,
+    /// Change the proximity and global settings of channels
+    Channels,
+    /// Everything, including the client settings and the audit log
+    Admin,
+}
+
+impl FromStr for ApiScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "status" => Ok(ApiScope::Status),
+            "metrics" => Ok(ApiScope::Metrics),
+            "moderate" => Ok(ApiScope::Moderate),
+            "channels" => Ok(ApiScope::Channels),
+            "admin" => Ok(ApiScope::Admin),
+            _ => Err(format!(
+                "unknown scope {}, expected status, metrics, moderate, channels or admin",
+                s
+            )),
+        }
+    }
+}
+
+/// A named bearer token for the http api
+#[derive(Clone, Debug)]
+pub struct ApiToken {
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    pub token: String,
+}
+
+impl ApiToken {
+    pub fn allows(&self, scope: ApiScope) -> bool {
+        self.scopes.iter().any(|s| *s == scope || *s == ApiScope::Admin)
+    }
+}
+
+/// Parses a token given as `name:scope,scope:token`
+pub fn parse_api_token(value: &str) -> Result<ApiToken, String> {
+    let mut parts = value.splitn(3, ':');
+
+    let (Some(name), Some(scopes), Some(token)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err("expected name:scope,scope:token".to_string());
+    };
+
+    if name.is_empty() || token.is_empty() {
+        return Err("the name and the token can't be empty".to_string());
+    }
+
+    Ok(ApiToken {
+        name: name.to_string(),
+        scopes: scopes.split(',').map(ApiScope::from_str).collect::<Result<_, _>>()?,
+        token: token.to_string(),
+    })
+}
+
+/// Credentials accepted by the http api, the health endpoints never need any
+pub struct ApiAuth {
+    /// Realm sent back to clients that didn't authenticate
+    pub realm: &'static str,
+    /// User for basic authentication, it has every scope
+    pub user: String,
+    /// Basic authentication is disabled without a password
+    pub password: Option<String>,
+    pub tokens: Vec<ApiToken>,
+    /// Serve `/metrics` without authentication
+    pub public_metrics: bool,
+}
+
+impl ApiAuth {
+    /// Returns the scope needed for a request, or none if it doesn't need authentication
+    fn required_scope(&self, method: &Method, path: &str) -> Option<ApiScope> {
+        let resource = path.trim_start_matches('/').split('/').next().unwrap_or_default();
+
+        match (resource, *method == Method::GET) {
+            ("healthz" | "readyz", _) => None,
+            ("metrics", _) if self.public_metrics => None,
+            ("metrics", _) => Some(ApiScope::Metrics),
+            ("channels", false) => Some(ApiScope::Channels),
+            ("config", false) => Some(ApiScope::Admin),
+            ("audit", _) => Some(ApiScope::Admin),
+            (_, true) => Some(ApiScope::Status),
+            (_, false) => Some(ApiScope::Moderate),
+        }
+    }
+
+    /// Returns the token matching the credentials sent with the request, basic authentication is
+    /// treated as a token with every scope
+    fn identify(&self, req: &ServiceRequest) -> Option<ApiToken> {
+        if let Ok(bearer) = Authorization::<Bearer>::parse(req) {
+            let token = bearer.as_ref().token();
+
+            // every token is compared so the timing doesn't tell which one almost matched
+            return self
+                .tokens
+                .iter()
+                .fold(None, |found, t| if constant_time_eq(&t.token, token) { Some(t) } else { found })
+                .cloned();
+        }
+
+        if let Ok(basic) = Authorization::<Basic>::parse(req) {
+            let basic = basic.as_ref();
+            let password = self.password.as_deref()?;
+
+            let user_matches = constant_time_eq(basic.user_id(), &self.user);
+            let password_matches = constant_time_eq(basic.password().unwrap_or_default(), password);
+
+            if user_matches && password_matches {
+                return Some(ApiToken {
+                    name: self.user.clone(),
+                    scopes: vec![ApiScope::Admin],
+                    token: password.to_string(),
+                });
+            }
+        }
+
+        None
+    }
+}
+
+/// Compares the hashes of both values so the time taken doesn't depend on the content or the
+/// length of the secret
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    let a = digest(&SHA256, a.as_bytes());
+    let b = digest(&SHA256, b.as_bytes());
+
+    a.as_ref().iter().zip(b.as_ref()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub async fn authenticate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let Some(auth) = req.app_data::<web::Data<ApiAuth>>().cloned() else {
+        return Ok(req
+            .into_response(HttpResponse::InternalServerError().finish())
+            .map_into_right_body());
+    };
+
+    // the router matches the percent decoded path, the scope has to be chosen from the same one
+    // or `/%61udit` would only need the status scope
+    let Some(scope) = auth.required_scope(req.method(), req.match_info().unprocessed()) else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+
+    match auth.identify(&req) {
+        Some(token) if token.allows(scope) => {
+            // handlers making administrative changes record the token in the audit log
+            req.extensions_mut().insert(token);
+
+            Ok(next.call(req).await?.map_into_left_body())
+        }
+        Some(token) => {
+            tracing::warn!(
+                "api token {} tried to use {} {} without the {:?} scope",
+                token.name,
+                req.method(),
+                req.path(),
+                scope
+            );
+
+            let error = MumbleError::Forbidden(format!("{:?}", scope).to_lowercase());
+
+            Ok(req.into_response(error.error_response()).map_into_right_body())
+        }
+        None => {
+            let mut response = MumbleError::Unauthorized.error_response();
+            let headers = response.headers_mut();
+
+            for scheme in ["Bearer", "Basic"] {
+                if let Ok(value) = HeaderValue::from_str(&format!("{} realm=\"{}\"", scheme, auth.realm)) {
+                    headers.append(WWW_AUTHENTICATE, value);
+                }
+            }
+
+            Ok(req.into_response(response).map_into_right_body())
+        }
+    }
+}
+
+/// Handlers changing the state of the server take the actor from the credentials accepted by the
+/// authentication middleware
+impl FromRequest for AuditActor {
+    type Error = MumbleError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let actor = req
+            .extensions()
+            .get::<ApiToken>()
+            .map(|token| AuditActor::Api { token: token.name.clone() });
+
+        ready(actor.ok_or(MumbleError::Unauthorized))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{middleware, test, App};
+
+    #[actix_web::test]
+    async fn scope_follows_the_decoded_path() {
+        let auth = ApiAuth {
+            realm: "zumble",
+            user: "admin".to_string(),
+            password: None,
+            tokens: vec![parse_api_token("monitoring:status:secret").unwrap()],
+            public_metrics: false,
+        };
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(auth))
+                .wrap(middleware::from_fn(authenticate))
+                .route("/audit", web::get().to(HttpResponse::Ok))
+                .route("/status", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        for (path, status) in [("/status", 200), ("/audit", 403), ("/%61udit", 403)] {
+            let request = test::TestRequest::get()
+                .uri(path)
+                .insert_header(("Authorization", "Bearer secret"))
+                .to_request();
+
+            assert_eq!(test::call_service(&app, request).await.status().as_u16(), status, "{}", path);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use crate::audit::{AuditAction, AuditActor, AuditTarget};
+use crate::error::ErrorBody;
+use crate::proximity::ProximitySettings;
+use crate::{error::MumbleError, state::ServerStateRef};
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ChannelGlobal {
+    /// Global channels are heard across instances
+    global: bool,
+}
+
+/// Returns the proximity settings of a channel, null when proximity voice is disabled
+#[utoipa::path(
+    get,
+    path = "/channels/{id}/proximity",
+    tag = "channels",
+    params(("id" = u32, Path, description = "Channel id")),
+    responses(
+        (status = 200, description = "Proximity settings of the channel", body = Option<ProximitySettings>),
+        (status = 404, description = "The channel doesn't exist", body = ErrorBody),
+    )
+)]
+#[actix_web::get("/channels/{id}/proximity")]
+pub async fn get_channel_proximity(id: web::Path<u32>, state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let channel = state.channels.get(&id.into_inner()).ok_or(MumbleError::ChannelDoesntExist)?;
+
+    Ok(HttpResponse::Ok().json(channel.proximity.load_full().as_deref()))
+}
+
+/// Enables proximity voice for a channel
+#[utoipa::path(
+    post,
+    path = "/channels/{id}/proximity",
+    tag = "channels",
+    params(("id" = u32, Path, description = "Channel id")),
+    request_body = ProximitySettings,
+    responses(
+        (status = 200, description = "Proximity voice got enabled"),
+        (status = 400, description = "The radius or the hysteresis is invalid", body = ErrorBody),
+        (status = 404, description = "The channel doesn't exist", body = ErrorBody),
+    )
+)]
+#[actix_web::post("/channels/{id}/proximity")]
+pub async fn post_channel_proximity(
+    id: web::Path<u32>,
+    proximity: web::Json<ProximitySettings>,
+    actor: AuditActor,
+    state: web::Data<ServerStateRef>,
+) -> Result<HttpResponse, MumbleError> {
+    if !proximity.is_valid() {
+        return Err(MumbleError::InvalidRequest(
+            "radius must be positive and hysteresis can't be negative".to_string(),
+        ));
+    }
+
+    let channel = state.channels.get(&id.into_inner()).ok_or(MumbleError::ChannelDoesntExist)?;
+    let proximity = Arc::new(proximity.into_inner());
+
+    let previous = channel.proximity.swap(Some(proximity.clone()));
+
+    state.audit.record(
+        actor,
+        AuditAction::ChannelProximity,
+        AuditTarget::Channel { channel_id: channel.id },
+        previous.as_deref(),
+        proximity.as_ref(),
+    );
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Disables proximity voice for a channel
+#[utoipa::path(
+    delete,
+    path = "/channels/{id}/proximity",
+    tag = "channels",
+    params(("id" = u32, Path, description = "Channel id")),
+    responses(
+        (status = 200, description = "Proximity voice got disabled"),
+        (status = 404, description = "The channel doesn't exist", body = ErrorBody),
+    )
+)]
+#[actix_web::delete("/channels/{id}/proximity")]
+pub async fn delete_channel_proximity(
+    id: web::Path<u32>,
+    actor: AuditActor,
+    state: web::Data<ServerStateRef>,
+) -> Result<HttpResponse, MumbleError> {
+    let channel = state.channels.get(&id.into_inner()).ok_or(MumbleError::ChannelDoesntExist)?;
+
+    if let Some(previous) = channel.proximity.swap(None) {
+        state.audit.record(
+            actor,
+            AuditAction::ChannelProximity,
+            AuditTarget::Channel { channel_id: channel.id },
+            previous.as_ref(),
+            None::<ProximitySettings>,
+        );
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[utoipa::path(
+    get,
+    path = "/channels/{id}/global",
+    tag = "channels",
+    params(("id" = u32, Path, description = "Channel id")),
+    responses(
+        (status = 200, description = "Whether the channel is global", body = ChannelGlobal),
+        (status = 404, description = "The channel doesn't exist", body = ErrorBody),
+    )
+)]
+#[actix_web::get("/channels/{id}/global")]
+pub async fn get_channel_global(id: web::Path<u32>, state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let channel = state.channels.get(&id.into_inner()).ok_or(MumbleError::ChannelDoesntExist)?;
+
+    Ok(HttpResponse::Ok().json(&ChannelGlobal {
+        global: channel.is_global(),
+    }))
+}
+
+/// Makes a channel heard across instances or not
+#[utoipa::path(
+    post,
+    path = "/channels/{id}/global",
+    tag = "channels",
+    params(("id" = u32, Path, description = "Channel id")),
+    request_body = ChannelGlobal,
+    responses(
+        (status = 200, description = "The channel got changed"),
+        (status = 404, description = "The channel doesn't exist", body = ErrorBody),
+    )
+)]
+#[actix_web::post("/channels/{id}/global")]
+pub async fn post_channel_global(
+    id: web::Path<u32>,
+    global: web::Json<ChannelGlobal>,
+    actor: AuditActor,
+    state: web::Data<ServerStateRef>,
+) -> Result<HttpResponse, MumbleError> {
+    let channel = state.channels.get(&id.into_inner()).ok_or(MumbleError::ChannelDoesntExist)?;
+
+    if channel.is_global() != global.global {
+        state.audit.record(
+            actor,
+            AuditAction::ChannelGlobal,
+            AuditTarget::Channel { channel_id: channel.id },
+            channel.is_global(),
+            global.global,
+        );
+    }
+
+    channel.set_global(global.global);
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -0,0 +1,123 @@
+use crate::audit::{AuditAction, AuditActor, AuditTarget};
+use crate::config::ClientSettings;
+use crate::error::ErrorBody;
+use crate::version::parse_version;
+use crate::{error::MumbleError, state::ServerStateRef};
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Deserializer, Serialize};
+use utoipa::ToSchema;
+
+/// Partial update of the client settings, fields that are left out keep their current value.
+/// Suggestions can be removed by setting them to null.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ClientSettingsUpdate {
+    welcome_text: Option<String>,
+    allow_html: Option<bool>,
+    message_length: Option<u32>,
+    image_message_length: Option<u32>,
+    max_users: Option<u32>,
+    #[serde(default, deserialize_with = "double_option")]
+    #[schema(value_type = Option<String>)]
+    suggest_version: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    #[schema(value_type = Option<bool>)]
+    suggest_positional: Option<Option<bool>>,
+    #[serde(default, deserialize_with = "double_option")]
+    #[schema(value_type = Option<bool>)]
+    suggest_push_to_talk: Option<Option<bool>>,
+}
+
+impl ClientSettingsUpdate {
+    fn apply(&self, settings: &mut ClientSettings) {
+        if let Some(welcome_text) = &self.welcome_text {
+            settings.welcome_text = welcome_text.clone();
+        }
+
+        if let Some(allow_html) = self.allow_html {
+            settings.allow_html = allow_html;
+        }
+
+        if let Some(message_length) = self.message_length {
+            settings.message_length = message_length;
+        }
+
+        if let Some(image_message_length) = self.image_message_length {
+            settings.image_message_length = image_message_length;
+        }
+
+        if let Some(max_users) = self.max_users {
+            settings.max_users = max_users;
+        }
+
+        if let Some(suggest_version) = &self.suggest_version {
+            settings.suggest_version = suggest_version.clone();
+        }
+
+        if let Some(suggest_positional) = self.suggest_positional {
+            settings.suggest_positional = suggest_positional;
+        }
+
+        if let Some(suggest_push_to_talk) = self.suggest_push_to_talk {
+            settings.suggest_push_to_talk = suggest_push_to_talk;
+        }
+    }
+}
+
+/// Lets us tell apart a field set to null (`Some(None)`) from a missing field (`None`)
+fn double_option<'de, T: Deserialize<'de>, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Option<T>>, D::Error> {
+    Deserialize::deserialize(deserializer).map(Some)
+}
+
+/// Returns the limits and suggestions sent to clients
+#[utoipa::path(
+    get,
+    path = "/config",
+    tag = "config",
+    responses(
+        (status = 200, description = "Current client settings", body = ClientSettings),
+    )
+)]
+#[actix_web::get("/config")]
+pub async fn get_config(state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let settings = state.client_settings.load_full();
+
+    Ok(HttpResponse::Ok().json(settings.as_ref()))
+}
+
+/// Changes the limits and suggestions sent to clients, connected clients get them right away
+#[utoipa::path(
+    post,
+    path = "/config",
+    tag = "config",
+    request_body = ClientSettingsUpdate,
+    responses(
+        (status = 200, description = "The new client settings", body = ClientSettings),
+        (status = 400, description = "The suggested version is invalid", body = ErrorBody),
+    )
+)]
+#[actix_web::post("/config")]
+pub async fn post_config(
+    update: web::Json<ClientSettingsUpdate>,
+    actor: AuditActor,
+    state: web::Data<ServerStateRef>,
+) -> Result<HttpResponse, MumbleError> {
+    let update = update.into_inner();
+
+    if let Some(Some(version)) = &update.suggest_version {
+        if parse_version(version).is_err() {
+            return Err(MumbleError::InvalidRequest(format!("invalid version {}", version)));
+        }
+    }
+
+    let (previous, settings) = state.update_client_settings(|settings| update.apply(settings))?;
+
+    state.audit.record(
+        actor,
+        AuditAction::ClientSettings,
+        AuditTarget::ClientSettings,
+        previous.as_ref(),
+        settings.as_ref(),
+    );
+
+    Ok(HttpResponse::Ok().json(settings.as_ref()))
+}
@@ -0,0 +1,134 @@
+use crate::audit::{AuditAction, AuditActor, AuditTarget};
+use crate::context_action::{ContextActionDefinition, ContextActionEvent};
+use crate::error::ErrorBody;
+use crate::{error::MumbleError, state::ServerStateRef};
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ContextActionEventsQuery {
+    /// Only return events with an id greater than this one
+    #[serde(default)]
+    after: u64,
+}
+
+/// Lists the registered context actions
+#[utoipa::path(
+    get,
+    path = "/context-actions",
+    tag = "context actions",
+    responses(
+        (status = 200, description = "Registered context actions", body = Vec<ContextActionDefinition>),
+    )
+)]
+#[actix_web::get("/context-actions")]
+pub async fn get_context_actions(state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let mut definitions = Vec::new();
+
+    let mut iter = state.context_actions.first_entry_async().await;
+    while let Some(definition) = iter {
+        definitions.push(definition.get().clone());
+
+        iter = definition.next_async().await;
+    }
+
+    Ok(HttpResponse::Ok().json(&definitions))
+}
+
+/// Registers a context action and sends it to every client
+#[utoipa::path(
+    post,
+    path = "/context-actions",
+    tag = "context actions",
+    request_body = ContextActionDefinition,
+    responses(
+        (status = 200, description = "The context action got registered"),
+        (status = 400, description = "The action or the targets are empty", body = ErrorBody),
+    )
+)]
+#[actix_web::post("/context-actions")]
+pub async fn post_context_action(
+    definition: web::Json<ContextActionDefinition>,
+    actor: AuditActor,
+    state: web::Data<ServerStateRef>,
+) -> Result<HttpResponse, MumbleError> {
+    if definition.action.is_empty() || definition.targets.is_empty() {
+        return Err(MumbleError::InvalidRequest("action and targets can't be empty".to_string()));
+    }
+
+    let definition = definition.into_inner();
+    let previous = state.context_actions.read(&definition.action, |_, previous| previous.clone());
+
+    state.audit.record(
+        actor,
+        AuditAction::ContextActionAdded,
+        AuditTarget::ContextAction {
+            action: definition.action.clone(),
+        },
+        previous,
+        &definition,
+    );
+
+    state.add_context_action(definition)?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Removes a context action from every client
+#[utoipa::path(
+    delete,
+    path = "/context-actions/{action}",
+    tag = "context actions",
+    params(("action" = String, Path, description = "Identifier of the action")),
+    responses(
+        (status = 200, description = "The context action got removed"),
+        (status = 404, description = "The context action isn't registered", body = ErrorBody),
+    )
+)]
+#[actix_web::delete("/context-actions/{action}")]
+pub async fn delete_context_action(
+    action: web::Path<String>,
+    actor: AuditActor,
+    state: web::Data<ServerStateRef>,
+) -> Result<HttpResponse, MumbleError> {
+    let previous = state.context_actions.read(action.as_str(), |_, previous| previous.clone());
+
+    match state.remove_context_action(action.as_str())? {
+        true => {
+            state.audit.record(
+                actor,
+                AuditAction::ContextActionRemoved,
+                AuditTarget::ContextAction {
+                    action: action.into_inner(),
+                },
+                previous,
+                None::<ContextActionDefinition>,
+            );
+
+            Ok(HttpResponse::Ok().finish())
+        }
+        false => Err(MumbleError::ContextActionNotFound),
+    }
+}
+
+/// Returns the context actions triggered by clients
+#[utoipa::path(
+    get,
+    path = "/context-actions/events",
+    tag = "context actions",
+    params(ContextActionEventsQuery),
+    responses(
+        (status = 200, description = "Triggered context actions, oldest first", body = Vec<ContextActionEvent>),
+    )
+)]
+#[actix_web::get("/context-actions/events")]
+pub async fn get_context_action_events(
+    query: web::Query<ContextActionEventsQuery>,
+    state: web::Data<ServerStateRef>,
+) -> Result<HttpResponse, MumbleError> {
+    let events = state.get_context_action_events(query.after);
+
+    Ok(HttpResponse::Ok().json(&events))
+}
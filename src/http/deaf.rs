@@ -1,42 +1,111 @@
+use super::mute::BatchResult;
+use crate::audit::{AuditAction, AuditActor, AuditTarget};
+use crate::error::ErrorBody;
+use crate::user_ref::UserRef;
 use crate::{error::MumbleError, state::ServerStateRef};
 use actix_web::{web, HttpResponse};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct Deaf {
     deaf: bool,
-    user: String,
+    user: UserRef,
 }
 
+/// Deafens or undeafens a user
+#[utoipa::path(
+    post,
+    path = "/deaf",
+    tag = "users",
+    request_body = Deaf,
+    responses(
+        (status = 200, description = "The deaf of the user got changed"),
+        (status = 404, description = "The user isn't connected", body = ErrorBody),
+    )
+)]
 #[actix_web::post("/deaf")]
-pub async fn post_deaf(deaf: web::Json<Deaf>, state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
-    let client = { state.get_client_by_name(deaf.user.as_str()) };
+pub async fn post_deaf(deaf: web::Json<Deaf>, actor: AuditActor, state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let client = state.get_client(&deaf.user).ok_or(MumbleError::UserNotFound)?;
 
-    Ok(match client {
-        Some(client) => {
-            client.set_deaf(deaf.deaf);
+    if client.is_deaf() != deaf.deaf {
+        state
+            .audit
+            .record(actor, AuditAction::Deaf, AuditTarget::user(&client), client.is_deaf(), deaf.deaf);
+    }
 
-            HttpResponse::Ok().finish()
+    state.set_client_deaf(&client, deaf.deaf);
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DeafBatch {
+    deaf: bool,
+    users: Vec<UserRef>,
+}
+
+/// Deafens or undeafens several users at once, users that aren't connected are skipped
+#[utoipa::path(
+    post,
+    path = "/deaf/batch",
+    tag = "users",
+    request_body = DeafBatch,
+    responses(
+        (status = 200, description = "The deaf of the connected users got changed", body = BatchResult),
+    )
+)]
+#[actix_web::post("/deaf/batch")]
+pub async fn post_deaf_batch(
+    batch: web::Json<DeafBatch>,
+    actor: AuditActor,
+    state: web::Data<ServerStateRef>,
+) -> Result<HttpResponse, MumbleError> {
+    let mut result = BatchResult {
+        updated: 0,
+        not_found: Vec::new(),
+    };
+
+    for user in &batch.users {
+        match state.get_client(user) {
+            Some(client) => {
+                if client.is_deaf() != batch.deaf {
+                    state.audit.record(
+                        actor.clone(),
+                        AuditAction::Deaf,
+                        AuditTarget::user(&client),
+                        client.is_deaf(),
+                        batch.deaf,
+                    );
+                }
+
+                state.set_client_deaf(&client, batch.deaf);
+                result.updated += 1;
+            }
+            None => result.not_found.push(user.clone()),
         }
-        None => HttpResponse::NotFound().finish(),
-    })
+    }
+
+    Ok(HttpResponse::Ok().json(&result))
 }
 
+#[utoipa::path(
+    get,
+    path = "/deaf/{user}",
+    tag = "users",
+    params(("user" = UserRef, Path)),
+    responses(
+        (status = 200, description = "Whether the user is deaf", body = Deaf),
+        (status = 404, description = "The user isn't connected", body = ErrorBody),
+    )
+)]
 #[actix_web::get("/deaf/{user}")]
-pub async fn get_deaf(user: web::Path<String>, state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
-    let username = user.into_inner();
-    let client = { state.get_client_by_name(username.as_str()) };
-
-    let var_name = match client {
-        Some(client) => {
-            let deaf = Deaf {
-                deaf: client.is_deaf(),
-                user: username,
-            };
-
-            HttpResponse::Ok().json(&deaf)
-        }
-        None => HttpResponse::NotFound().finish(),
-    };
-    Ok(var_name)
+pub async fn get_deaf(user: web::Path<UserRef>, state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let user = user.into_inner();
+    let client = state.get_client(&user).ok_or(MumbleError::UserNotFound)?;
+
+    Ok(HttpResponse::Ok().json(&Deaf {
+        deaf: client.is_deaf(),
+        user,
+    }))
 }
@@ -0,0 +1,73 @@
+use crate::event::EventRecord;
+use crate::server::constants::EVENT_KEEP_ALIVE_INTERVAL;
+use crate::{error::MumbleError, state::ServerStateRef};
+use actix_web::{web, HttpRequest, HttpResponse};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use utoipa::IntoParams;
+
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EventsQuery {
+    /// Replay the events in history with an id greater than this one, the `Last-Event-ID` header
+    /// sent by a reconnecting EventSource takes precedence
+    after: Option<u64>,
+}
+
+fn to_sse(record: &EventRecord) -> Bytes {
+    let data = serde_json::to_string(record).unwrap_or_default();
+
+    Bytes::from(format!("id: {}\nevent: {}\ndata: {}\n\n", record.id, record.event.name(), data))
+}
+
+/// Streams the server events as server sent events, the stream ends when the consumer falls too
+/// far behind and it should reconnect with the id of the last event it got
+#[utoipa::path(
+    get,
+    path = "/events",
+    tag = "events",
+    params(
+        EventsQuery,
+        ("Last-Event-ID" = Option<u64>, Header, description = "Id of the last event the consumer got"),
+    ),
+    responses(
+        (status = 200, description = "Server sent events, the data of each event is an event record", body = EventRecord, content_type = "text/event-stream"),
+    )
+)]
+#[actix_web::get("/events")]
+pub async fn get_events(
+    req: HttpRequest,
+    query: web::Query<EventsQuery>,
+    state: web::Data<ServerStateRef>,
+) -> Result<HttpResponse, MumbleError> {
+    let last_event_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok());
+
+    let (missed, receiver) = state.events.subscribe(last_event_id.or(query.after));
+
+    let replay = futures::stream::iter(missed.into_iter().map(|record| Ok::<_, MumbleError>(to_sse(&record))));
+
+    let live = futures::stream::unfold(receiver, |mut receiver: Receiver<Arc<EventRecord>>| async move {
+        match tokio::time::timeout(EVENT_KEEP_ALIVE_INTERVAL, receiver.recv()).await {
+            Ok(Ok(record)) => Some((Ok(to_sse(&record)), receiver)),
+            Ok(Err(RecvError::Lagged(skipped))) => {
+                tracing::warn!("event stream consumer lagged behind by {} events, closing stream", skipped);
+
+                None
+            }
+            Ok(Err(RecvError::Closed)) => None,
+            // comments are ignored by consumers, this keeps proxies from closing an idle connection
+            Err(_) => Some((Ok(Bytes::from_static(b": keep-alive\n\n")), receiver)),
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(futures::StreamExt::chain(replay, live)))
+}
@@ -0,0 +1,53 @@
+use crate::health::HealthReport;
+use crate::{error::MumbleError, state::ServerStateRef};
+use actix_web::{web, HttpResponse};
+
+fn health_report(state: &ServerStateRef) -> HealthReport {
+    state.health.report(
+        state.clients.len(),
+        state.client_settings.load().get_max_users(),
+        state.clients_by_socket.len(),
+    )
+}
+
+/// Fails when one of the server loops stopped, the process should be restarted
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "health",
+    security(()),
+    responses(
+        (status = 200, description = "The server is healthy", body = HealthReport),
+        (status = 503, description = "A server loop stopped", body = HealthReport),
+    )
+)]
+#[actix_web::get("/healthz")]
+pub async fn get_healthz(state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let report = health_report(&state);
+
+    Ok(match report.is_healthy() {
+        true => HttpResponse::Ok().json(&report),
+        false => HttpResponse::ServiceUnavailable().json(&report),
+    })
+}
+
+/// Fails while the server starts or shuts down, when it is unhealthy or full
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "health",
+    security(()),
+    responses(
+        (status = 200, description = "The server takes new clients", body = HealthReport),
+        (status = 503, description = "The server doesn't take new clients", body = HealthReport),
+    )
+)]
+#[actix_web::get("/readyz")]
+pub async fn get_readyz(state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let report = health_report(&state);
+
+    Ok(match report.is_ready() {
+        true => HttpResponse::Ok().json(&report),
+        false => HttpResponse::ServiceUnavailable().json(&report),
+    })
+}
@@ -0,0 +1,148 @@
+use crate::audit::{AuditAction, AuditActor, AuditTarget};
+use crate::error::ErrorBody;
+use crate::user_ref::UserRef;
+use crate::{error::MumbleError, state::ServerStateRef};
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Instance {
+    /// Routing bucket of the player, players in different instances can't hear each other
+    instance: i32,
+    user: UserRef,
+}
+
+/// Moves a user to another routing bucket
+#[utoipa::path(
+    post,
+    path = "/instance",
+    tag = "users",
+    request_body = Instance,
+    responses(
+        (status = 200, description = "The instance of the user got changed"),
+        (status = 404, description = "The user isn't connected", body = ErrorBody),
+    )
+)]
+#[actix_web::post("/instance")]
+pub async fn post_instance(
+    instance: web::Json<Instance>,
+    actor: AuditActor,
+    state: web::Data<ServerStateRef>,
+) -> Result<HttpResponse, MumbleError> {
+    let client = state.get_client(&instance.user).ok_or(MumbleError::UserNotFound)?;
+
+    if client.get_instance() != instance.instance {
+        state.audit.record(
+            actor,
+            AuditAction::Instance,
+            AuditTarget::user(&client),
+            client.get_instance(),
+            instance.instance,
+        );
+    }
+
+    client.set_instance(instance.instance);
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[utoipa::path(
+    get,
+    path = "/instance/{user}",
+    tag = "users",
+    params(("user" = UserRef, Path)),
+    responses(
+        (status = 200, description = "Routing bucket of the user", body = Instance),
+        (status = 404, description = "The user isn't connected", body = ErrorBody),
+    )
+)]
+#[actix_web::get("/instance/{user}")]
+pub async fn get_instance(user: web::Path<UserRef>, state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let user = user.into_inner();
+    let client = state.get_client(&user).ok_or(MumbleError::UserNotFound)?;
+
+    Ok(HttpResponse::Ok().json(&Instance {
+        instance: client.get_instance(),
+        user,
+    }))
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct GlobalTarget {
+    /// Voice target id, 1 to 30, as set by the VoiceTarget message of the client
+    target: u8,
+    /// Voice the user sends to the sessions of this target (a radio, a call) reaches them in every
+    /// instance
+    global: bool,
+    user: UserRef,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct GlobalTargets {
+    /// Voice targets whose sessions hear the user in every instance
+    targets: Vec<u8>,
+    user: UserRef,
+}
+
+/// Lets one voice target of a user cross instances, for talk groups like a radio or a call. The
+/// other targets of the user, like whispers, stay in its instance
+#[utoipa::path(
+    post,
+    path = "/instance/global-targets",
+    tag = "users",
+    request_body = GlobalTarget,
+    responses(
+        (status = 200, description = "The voice target of the user got changed"),
+        (status = 400, description = "The target isn't between 1 and 30", body = ErrorBody),
+        (status = 404, description = "The user isn't connected", body = ErrorBody),
+    )
+)]
+#[actix_web::post("/instance/global-targets")]
+pub async fn post_global_target(
+    global_target: web::Json<GlobalTarget>,
+    actor: AuditActor,
+    state: web::Data<ServerStateRef>,
+) -> Result<HttpResponse, MumbleError> {
+    if !(1..=30).contains(&global_target.target) {
+        return Err(MumbleError::InvalidRequest("the target has to be between 1 and 30".to_string()));
+    }
+
+    let client = state.get_client(&global_target.user).ok_or(MumbleError::UserNotFound)?;
+
+    if client.is_global_target(global_target.target) != global_target.global {
+        let before = client.get_global_targets();
+        client.set_global_target(global_target.target, global_target.global);
+
+        state.audit.record(
+            actor,
+            AuditAction::GlobalTargets,
+            AuditTarget::user(&client),
+            before,
+            client.get_global_targets(),
+        );
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[utoipa::path(
+    get,
+    path = "/instance/{user}/global-targets",
+    tag = "users",
+    params(("user" = UserRef, Path)),
+    responses(
+        (status = 200, description = "Voice targets of the user that cross instances", body = GlobalTargets),
+        (status = 404, description = "The user isn't connected", body = ErrorBody),
+    )
+)]
+#[actix_web::get("/instance/{user}/global-targets")]
+pub async fn get_global_targets(user: web::Path<UserRef>, state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let user = user.into_inner();
+    let client = state.get_client(&user).ok_or(MumbleError::UserNotFound)?;
+
+    Ok(HttpResponse::Ok().json(&GlobalTargets {
+        targets: client.get_global_targets(),
+        user,
+    }))
+}
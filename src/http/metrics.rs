@@ -1,6 +1,15 @@
 use actix_web::HttpResponse;
 use prometheus::{Encoder, TextEncoder};
 
+/// Prometheus metrics in the text format
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "metrics",
+    responses(
+        (status = 200, description = "Metrics", body = String, content_type = "text/plain"),
+    )
+)]
 #[actix_web::get("/metrics")]
 pub async fn get_metrics() -> HttpResponse {
     let encoder = TextEncoder::new();
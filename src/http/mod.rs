@@ -1,62 +1,100 @@
+mod audit;
+mod auth;
+mod channel;
+mod config;
+mod context_action;
 mod deaf;
+mod event;
+mod health;
+mod instance;
 mod metrics;
 mod mute;
+mod openapi;
+mod plugin_data;
+mod position;
+mod priority_speaker;
 mod status;
+mod talking;
+mod user;
 
+pub use auth::{parse_api_token, ApiAuth, ApiScope, ApiToken};
+
+use crate::error::MumbleError;
 use crate::state::ServerStateRef;
 use actix_server::Server;
 use actix_web::middleware::Condition;
 use actix_web::{middleware, web, App, HttpServer};
-use actix_web_httpauth::{extractors::AuthenticationError, headers::www_authenticate::basic::Basic, middleware::HttpAuthentication};
 
 pub fn create_http_server(
     listen: String,
     tls_config: rustls::ServerConfig,
     use_tls: bool,
     state: ServerStateRef,
-    user: String,
-    password: Option<String>,
+    auth: ApiAuth,
     log_requests: bool,
 ) -> Option<Server> {
-    let mut server = HttpServer::new(move || {
-        let user = user.clone();
-        let password = password.clone();
-
-        let auth = HttpAuthentication::basic(move |req, credentials| {
-            let user = user.clone();
-            let password = password.clone();
-
-            async move {
-                let password = password.clone();
-
-                if password.is_none() {
-                    return Err((AuthenticationError::new(Basic::with_realm("Restricted area")).into(), req));
-                }
-
-                let user = user.clone();
-
-                if credentials.user_id() == user.as_str() && credentials.password() == Some(password.unwrap().as_str()) {
-                    Ok(req)
-                } else {
-                    Err((AuthenticationError::new(Basic::with_realm("Restricted area")).into(), req))
-                }
-            }
-        });
+    let auth = web::Data::new(auth);
 
+    let mut server = HttpServer::new(move || {
         let mut logger = middleware::Logger::default();
-        logger = logger.exclude("/metrics").exclude("/status").log_target("log_http");
+        logger = logger
+            .exclude("/metrics")
+            .exclude("/status")
+            .exclude("/users")
+            .exclude("/events")
+            .exclude("/healthz")
+            .exclude("/readyz")
+            .exclude("/audit")
+            .log_target("log_http");
 
         App::new()
             .app_data(web::Data::new(state.clone()))
-            .wrap(auth)
+            .app_data(auth.clone())
+            .app_data(web::JsonConfig::default().error_handler(|err, _| MumbleError::InvalidRequest(err.to_string()).into()))
+            .app_data(web::QueryConfig::default().error_handler(|err, _| MumbleError::InvalidRequest(err.to_string()).into()))
+            .app_data(web::PathConfig::default().error_handler(|err, _| MumbleError::InvalidRequest(err.to_string()).into()))
+            .wrap(middleware::from_fn(auth::authenticate))
             .wrap(Condition::new(log_requests, logger))
             .service(metrics::get_metrics)
+            .service(health::get_healthz)
+            .service(health::get_readyz)
             .service(mute::get_mute)
             .service(mute::post_mute)
+            .service(mute::post_mute_batch)
             .service(deaf::get_deaf)
             .service(deaf::post_deaf)
+            .service(deaf::post_deaf_batch)
             .service(status::get_status)
-    });
+            .service(user::get_users)
+            .service(user::get_user)
+            .service(plugin_data::post_plugin_data)
+            .service(config::get_config)
+            .service(config::post_config)
+            .service(context_action::get_context_action_events)
+            .service(context_action::get_context_actions)
+            .service(context_action::post_context_action)
+            .service(context_action::delete_context_action)
+            .service(channel::get_channel_proximity)
+            .service(channel::post_channel_proximity)
+            .service(channel::delete_channel_proximity)
+            .service(channel::get_channel_global)
+            .service(channel::post_channel_global)
+            .service(instance::get_instance)
+            .service(instance::post_instance)
+            .service(instance::get_global_targets)
+            .service(instance::post_global_target)
+            .service(priority_speaker::get_priority_speaker)
+            .service(priority_speaker::post_priority_speaker)
+            .service(talking::get_user_talking)
+            .service(talking::get_talking)
+            .service(position::post_positions)
+            .service(position::get_positions)
+            .service(position::get_nearby)
+            .service(event::get_events)
+            .service(audit::get_audit)
+            .service(openapi::get_openapi)
+    })
+    .disable_signals();
 
     server = if use_tls {
         server
@@ -76,3 +114,84 @@ pub fn create_http_server(
 
     Some(server.run())
 }
+
+/// Serves only the metrics and health endpoints, so they can be exposed without the rest of the
+/// api. The address can be a unix socket given as `unix:<path>`
+pub fn create_metrics_server(listen: String, state: ServerStateRef, auth: ApiAuth) -> Option<Server> {
+    let auth = web::Data::new(auth);
+
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(state.clone()))
+            .app_data(auth.clone())
+            .wrap(middleware::from_fn(auth::authenticate))
+            .service(metrics::get_metrics)
+            .service(health::get_healthz)
+            .service(health::get_readyz)
+    })
+    .workers(1)
+    .disable_signals();
+
+    let server = match listen.strip_prefix("unix:") {
+        #[cfg(unix)]
+        Some(path) => {
+            use std::os::unix::fs::FileTypeExt;
+
+            // a socket left behind by a previous run would make the bind fail
+            if std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+                let _ = std::fs::remove_file(path);
+            }
+
+            server.bind_uds(path)
+        }
+        #[cfg(not(unix))]
+        Some(_) => {
+            tracing::error!("unix sockets are not supported on this platform");
+
+            return None;
+        }
+        None => server.bind(listen),
+    };
+
+    let server = server
+        .map_err(|e| {
+            tracing::error!("metrics bind error: {}", e);
+        })
+        .ok()?;
+
+    Some(server.run())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{connect_client, test_state};
+    use actix_web::test;
+
+    #[actix_web::test]
+    async fn session_routes_take_a_bare_session() {
+        let state = test_state().await;
+        // a name that is also a number, the routes still have to find the user by session
+        let client = connect_client(&state, "42").await;
+        client.set_server_position([0.0, 0.0, 0.0], None);
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state.clone()))
+                .service(user::get_user)
+                .service(talking::get_user_talking)
+                .service(position::get_nearby),
+        )
+        .await;
+
+        for uri in [
+            format!("/users/{}", client.session_id),
+            format!("/users/{}/talking", client.session_id),
+            format!("/positions/{}/nearby?radius=10", client.session_id),
+        ] {
+            let request = test::TestRequest::get().uri(&uri).to_request();
+
+            assert!(test::call_service(&app, request).await.status().is_success(), "{}", uri);
+        }
+    }
+}
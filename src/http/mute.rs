@@ -1,41 +1,117 @@
+use crate::audit::{AuditAction, AuditActor, AuditTarget};
+use crate::error::ErrorBody;
+use crate::user_ref::UserRef;
 use crate::{error::MumbleError, state::ServerStateRef};
 use actix_web::{web, HttpResponse};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct Mute {
     mute: bool,
-    user: String,
+    user: UserRef,
 }
 
+/// Mutes or unmutes a user
+#[utoipa::path(
+    post,
+    path = "/mute",
+    tag = "users",
+    request_body = Mute,
+    responses(
+        (status = 200, description = "The mute of the user got changed"),
+        (status = 404, description = "The user isn't connected", body = ErrorBody),
+    )
+)]
 #[actix_web::post("/mute")]
-pub async fn post_mute(mute: web::Json<Mute>, state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
-    let client = { state.get_client_by_name(mute.user.as_str()) };
+pub async fn post_mute(mute: web::Json<Mute>, actor: AuditActor, state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let client = state.get_client(&mute.user).ok_or(MumbleError::UserNotFound)?;
 
-    Ok(match client {
-        Some(client) => {
-            client.set_mute(mute.mute);
+    if client.is_muted() != mute.mute {
+        state
+            .audit
+            .record(actor, AuditAction::Mute, AuditTarget::user(&client), client.is_muted(), mute.mute);
+    }
 
-            HttpResponse::Ok().finish()
+    state.set_client_mute(&client, mute.mute);
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MuteBatch {
+    mute: bool,
+    users: Vec<UserRef>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BatchResult {
+    pub updated: usize,
+    /// Users that aren't connected
+    pub not_found: Vec<UserRef>,
+}
+
+/// Mutes or unmutes several users at once, users that aren't connected are skipped
+#[utoipa::path(
+    post,
+    path = "/mute/batch",
+    tag = "users",
+    request_body = MuteBatch,
+    responses(
+        (status = 200, description = "The mute of the connected users got changed", body = BatchResult),
+    )
+)]
+#[actix_web::post("/mute/batch")]
+pub async fn post_mute_batch(
+    batch: web::Json<MuteBatch>,
+    actor: AuditActor,
+    state: web::Data<ServerStateRef>,
+) -> Result<HttpResponse, MumbleError> {
+    let mut result = BatchResult {
+        updated: 0,
+        not_found: Vec::new(),
+    };
+
+    for user in &batch.users {
+        match state.get_client(user) {
+            Some(client) => {
+                if client.is_muted() != batch.mute {
+                    state.audit.record(
+                        actor.clone(),
+                        AuditAction::Mute,
+                        AuditTarget::user(&client),
+                        client.is_muted(),
+                        batch.mute,
+                    );
+                }
+
+                state.set_client_mute(&client, batch.mute);
+                result.updated += 1;
+            }
+            None => result.not_found.push(user.clone()),
         }
-        None => HttpResponse::NotFound().finish(),
-    })
+    }
+
+    Ok(HttpResponse::Ok().json(&result))
 }
 
+#[utoipa::path(
+    get,
+    path = "/mute/{user}",
+    tag = "users",
+    params(("user" = UserRef, Path)),
+    responses(
+        (status = 200, description = "Whether the user is muted", body = Mute),
+        (status = 404, description = "The user isn't connected", body = ErrorBody),
+    )
+)]
 #[actix_web::get("/mute/{user}")]
-pub async fn get_mute(user: web::Path<String>, state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
-    let username = user.into_inner();
-    let client = { state.get_client_by_name(username.as_str()) };
-
-    Ok(match client {
-        Some(client) => {
-            let mute = Mute {
-                mute: client.is_muted(),
-                user: username,
-            };
-
-            HttpResponse::Ok().json(&mute)
-        }
-        None => HttpResponse::NotFound().finish(),
-    })
+pub async fn get_mute(user: web::Path<UserRef>, state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let user = user.into_inner();
+    let client = state.get_client(&user).ok_or(MumbleError::UserNotFound)?;
+
+    Ok(HttpResponse::Ok().json(&Mute {
+        mute: client.is_muted(),
+        user,
+    }))
 }
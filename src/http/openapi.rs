@@ -0,0 +1,72 @@
+use super::{
+    audit, channel, config, context_action, deaf, event, health, instance, metrics, mute, plugin_data, position, priority_speaker, status,
+    talking, user,
+};
+use actix_web::HttpResponse;
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+/// Bearer api tokens and the basic admin credentials, either one is accepted by every endpoint
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+
+        components.add_security_scheme("bearer", SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)));
+        components.add_security_scheme("basic", SecurityScheme::Http(Http::new(HttpAuthScheme::Basic)));
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Zumble", description = "Http api to manage a Zumble voice server"),
+    paths(
+        metrics::get_metrics,
+        health::get_healthz,
+        health::get_readyz,
+        mute::get_mute,
+        mute::post_mute,
+        mute::post_mute_batch,
+        deaf::get_deaf,
+        deaf::post_deaf,
+        deaf::post_deaf_batch,
+        status::get_status,
+        user::get_users,
+        user::get_user,
+        plugin_data::post_plugin_data,
+        config::get_config,
+        config::post_config,
+        context_action::get_context_action_events,
+        context_action::get_context_actions,
+        context_action::post_context_action,
+        context_action::delete_context_action,
+        channel::get_channel_proximity,
+        channel::post_channel_proximity,
+        channel::delete_channel_proximity,
+        channel::get_channel_global,
+        channel::post_channel_global,
+        instance::get_instance,
+        instance::post_instance,
+        instance::get_global_targets,
+        instance::post_global_target,
+        priority_speaker::get_priority_speaker,
+        priority_speaker::post_priority_speaker,
+        talking::get_user_talking,
+        talking::get_talking,
+        position::post_positions,
+        position::get_positions,
+        position::get_nearby,
+        event::get_events,
+        audit::get_audit,
+    ),
+    modifiers(&SecurityAddon),
+    security(("bearer" = []), ("basic" = []))
+)]
+pub struct ApiDoc;
+
+/// OpenAPI description of the http api
+#[actix_web::get("/openapi.json")]
+pub async fn get_openapi() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
@@ -0,0 +1,144 @@
+use crate::error::ErrorBody;
+use crate::proto::mumble::PluginDataTransmission;
+use crate::server::constants::{MAX_PLUGIN_DATA_ID_SIZE, MAX_PLUGIN_DATA_RECEIVERS, MAX_PLUGIN_DATA_SIZE};
+use crate::user_ref::UserRef;
+use crate::{error::MumbleError, state::ServerStateRef};
+use actix_web::{web, HttpResponse};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct PluginData {
+    /// Sessions that should receive the data
+    #[serde(default)]
+    sessions: Vec<u32>,
+    /// Users that should receive the data
+    #[serde(default)]
+    users: Vec<UserRef>,
+    data_id: String,
+    /// Text data, either this or `data_base64` has to be set
+    #[serde(default)]
+    data: Option<String>,
+    /// Binary data encoded as standard base64 with padding
+    #[serde(default)]
+    data_base64: Option<String>,
+}
+
+impl PluginData {
+    fn decode_data(&self) -> Result<Vec<u8>, MumbleError> {
+        match (&self.data, &self.data_base64) {
+            (Some(data), None) => Ok(data.as_bytes().to_vec()),
+            (None, Some(data)) => STANDARD
+                .decode(data)
+                .map_err(|e| MumbleError::InvalidRequest(format!("data_base64 isn't valid base64: {}", e))),
+            _ => Err(MumbleError::InvalidRequest(
+                "exactly one of data and data_base64 has to be set".to_string(),
+            )),
+        }
+    }
+}
+
+/// Sends plugin data to clients, the sender session is 0 so plugins know it came from the server
+#[utoipa::path(
+    post,
+    path = "/plugin-data",
+    tag = "users",
+    request_body = PluginData,
+    responses(
+        (status = 200, description = "The data got sent"),
+        (status = 400, description = "Neither or both of data and data_base64 are set, or data_base64 isn't valid base64", body = ErrorBody),
+        (status = 404, description = "None of the receivers is connected", body = ErrorBody),
+        (status = 413, description = "The data or the data id is too long, or there are too many receivers", body = ErrorBody),
+    )
+)]
+#[actix_web::post("/plugin-data")]
+pub async fn post_plugin_data(plugin_data: web::Json<PluginData>, state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let data = plugin_data.decode_data()?;
+
+    if data.len() > MAX_PLUGIN_DATA_SIZE
+        || plugin_data.data_id.len() > MAX_PLUGIN_DATA_ID_SIZE
+        || plugin_data.sessions.len() + plugin_data.users.len() > MAX_PLUGIN_DATA_RECEIVERS
+    {
+        return Err(MumbleError::PayloadTooLarge);
+    }
+
+    let mut receivers = plugin_data.sessions.clone();
+
+    for user in &plugin_data.users {
+        if let Some(client) = state.get_client(user) {
+            receivers.push(client.session_id);
+        }
+    }
+
+    if receivers.is_empty() {
+        return Err(MumbleError::UserNotFound);
+    }
+
+    let mut message = PluginDataTransmission::new();
+    // session 0 is never given to a client, receivers can use it to know the data came from the server
+    message.set_senderSession(0);
+    message.set_receiverSessions(receivers);
+    message.set_dataID(plugin_data.data_id.clone());
+    message.set_data(data);
+
+    state.send_plugin_data(&message)?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::ClientMessage;
+    use crate::proto::MessageKind;
+    use crate::test_util::{connect_client, connect_listener, test_state};
+    use actix_web::{test, App};
+    use protobuf::Message;
+    use serde_json::json;
+
+    #[actix_web::test]
+    async fn binary_data_is_sent_from_base64() {
+        let state = test_state().await;
+        let (receiver, mut messages) = connect_listener(&state, "receiver").await;
+        let other = connect_client(&state, "other").await;
+
+        let app = test::init_service(App::new().app_data(web::Data::new(state.clone())).service(post_plugin_data)).await;
+
+        for (body, status) in [
+            (
+                json!({ "sessions": [other.session_id], "data_id": "radio", "data": "a", "data_base64": "YQ==" }),
+                400,
+            ),
+            (json!({ "sessions": [other.session_id], "data_id": "radio" }), 400),
+            (
+                json!({ "sessions": [other.session_id], "data_id": "radio", "data_base64": "not base64!" }),
+                400,
+            ),
+            (
+                json!({ "sessions": [receiver.session_id], "data_id": "radio", "data_base64": "AP+AAQ==" }),
+                200,
+            ),
+        ] {
+            let request = test::TestRequest::post().uri("/plugin-data").set_json(&body).to_request();
+
+            assert_eq!(test::call_service(&app, request).await.status().as_u16(), status, "{}", body);
+        }
+
+        let mut data = None;
+
+        while let Ok(message) = messages.try_recv() {
+            if let ClientMessage::SendMessage {
+                kind: MessageKind::PluginDataTransmission,
+                payload,
+            } = message
+            {
+                // the payload starts with the kind and the length of the message
+                data = Some(PluginDataTransmission::parse_from_bytes(&payload[6..]).unwrap().take_data());
+            }
+        }
+
+        assert_eq!(data, Some(vec![0x00, 0xff, 0x80, 0x01]));
+    }
+}
@@ -0,0 +1,133 @@
+use crate::error::ErrorBody;
+use crate::position::{PositionInfo, PositionUpdates};
+use crate::user_ref::SessionUserRef;
+use crate::{error::MumbleError, state::ServerStateRef};
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct PositionUpdateResult {
+    updated: usize,
+    /// Updates for clients that don't exist or with invalid coordinates
+    ignored: usize,
+}
+
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NearbyQuery {
+    /// Distance in game units
+    radius: f32,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct NearbyClient {
+    session: u32,
+    name: String,
+    distance: f32,
+}
+
+/// Stores positions pushed by the game server, either a single update or a list of them
+#[utoipa::path(
+    post,
+    path = "/positions",
+    tag = "positions",
+    request_body = PositionUpdates,
+    responses(
+        (status = 200, description = "How many updates got applied", body = PositionUpdateResult),
+    )
+)]
+#[actix_web::post("/positions")]
+pub async fn post_positions(updates: web::Json<PositionUpdates>, state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let mut result = PositionUpdateResult { updated: 0, ignored: 0 };
+
+    for update in updates.into_inner().into_vec() {
+        if state.apply_position_update(&update) {
+            result.updated += 1;
+        } else {
+            result.ignored += 1;
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(&result))
+}
+
+/// Returns the position used for routing of every user that has one, by session
+#[utoipa::path(
+    get,
+    path = "/positions",
+    tag = "positions",
+    responses(
+        (status = 200, description = "Positions by session", body = HashMap<u32, PositionInfo>),
+    )
+)]
+#[actix_web::get("/positions")]
+pub async fn get_positions(state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let mut positions = HashMap::new();
+
+    let mut iter = state.clients.first_entry_async().await;
+    while let Some(client) = iter {
+        if let Some(position) = client.get_position(&state.config) {
+            positions.insert(client.session_id, PositionInfo::from(&position));
+        }
+
+        iter = client.next_async().await;
+    }
+
+    Ok(HttpResponse::Ok().json(&positions))
+}
+
+/// Lists the clients around a client, clients in another routing bucket are never considered near
+#[utoipa::path(
+    get,
+    path = "/positions/{user}/nearby",
+    tag = "positions",
+    params(("user" = SessionUserRef, Path), NearbyQuery),
+    responses(
+        (status = 200, description = "Clients within the radius, nearest first", body = Vec<NearbyClient>),
+        (status = 404, description = "The user isn't connected or has no position", body = ErrorBody),
+    )
+)]
+#[actix_web::get("/positions/{user}/nearby")]
+pub async fn get_nearby(
+    user: web::Path<SessionUserRef>,
+    query: web::Query<NearbyQuery>,
+    state: web::Data<ServerStateRef>,
+) -> Result<HttpResponse, MumbleError> {
+    let client = state.get_client(&user).ok_or(MumbleError::UserNotFound)?;
+    let session = client.session_id;
+    let position = client.get_position(&state.config).ok_or(MumbleError::PositionNotFound)?;
+
+    let mut nearby = Vec::new();
+
+    let mut iter = state.clients.first_entry_async().await;
+    while let Some(client) = iter {
+        let other_position = match client.session_id == session {
+            true => None,
+            false => client.get_position(&state.config),
+        };
+
+        if let Some(other) = other_position {
+            let distance = position.distance(&other);
+            let same_bucket = match (position.routing_bucket, other.routing_bucket) {
+                (Some(bucket), Some(other_bucket)) => bucket == other_bucket,
+                _ => true,
+            };
+
+            if distance <= query.radius && same_bucket {
+                nearby.push(NearbyClient {
+                    session: client.session_id,
+                    name: client.get_name().as_ref().clone(),
+                    distance,
+                });
+            }
+        }
+
+        iter = client.next_async().await;
+    }
+
+    nearby.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+
+    Ok(HttpResponse::Ok().json(&nearby))
+}
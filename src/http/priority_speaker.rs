@@ -0,0 +1,68 @@
+use crate::audit::{AuditAction, AuditActor, AuditTarget};
+use crate::error::ErrorBody;
+use crate::user_ref::UserRef;
+use crate::{error::MumbleError, state::ServerStateRef};
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct PrioritySpeaker {
+    priority_speaker: bool,
+    user: UserRef,
+}
+
+/// Makes a user a priority speaker or a normal one again
+#[utoipa::path(
+    post,
+    path = "/priority-speaker",
+    tag = "users",
+    request_body = PrioritySpeaker,
+    responses(
+        (status = 200, description = "The priority speaker status of the user got changed"),
+        (status = 404, description = "The user isn't connected", body = ErrorBody),
+    )
+)]
+#[actix_web::post("/priority-speaker")]
+pub async fn post_priority_speaker(
+    priority_speaker: web::Json<PrioritySpeaker>,
+    actor: AuditActor,
+    state: web::Data<ServerStateRef>,
+) -> Result<HttpResponse, MumbleError> {
+    let client = state.get_client(&priority_speaker.user).ok_or(MumbleError::UserNotFound)?;
+
+    if client.is_priority_speaker() != priority_speaker.priority_speaker {
+        state.audit.record(
+            actor,
+            AuditAction::PrioritySpeaker,
+            AuditTarget::user(&client),
+            client.is_priority_speaker(),
+            priority_speaker.priority_speaker,
+        );
+    }
+
+    state.set_client_priority_speaker(&client, priority_speaker.priority_speaker)?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[utoipa::path(
+    get,
+    path = "/priority-speaker/{user}",
+    tag = "users",
+    params(("user" = UserRef, Path)),
+    responses(
+        (status = 200, description = "Whether the user is a priority speaker", body = PrioritySpeaker),
+        (status = 404, description = "The user isn't connected", body = ErrorBody),
+    )
+)]
+#[actix_web::get("/priority-speaker/{user}")]
+pub async fn get_priority_speaker(user: web::Path<UserRef>, state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let user = user.into_inner();
+    let client = state.get_client(&user).ok_or(MumbleError::UserNotFound)?;
+
+    Ok(HttpResponse::Ok().json(&PrioritySpeaker {
+        priority_speaker: client.is_priority_speaker(),
+        user,
+    }))
+}
@@ -1,31 +1,51 @@
+use crate::certificate::CertificateHash;
 use crate::error::MumbleError;
+use crate::position::PositionInfo;
 use crate::state::ServerStateRef;
+use crate::version::ClientVersion;
 use actix_web::{web, HttpResponse};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::sync::atomic::Ordering;
 use std::time::Instant;
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct MumbleClient {
     pub name: String,
     pub session_id: u32,
+    pub server_id: Option<u32>,
     pub channel: Option<String>,
     pub mute: bool,
+    pub instance: i32,
     pub good: u32,
     pub late: u32,
     pub lost: u32,
     pub resync: u32,
     pub last_good_duration: u128,
     pub targets: Vec<MumbleTarget>,
+    pub certificate_hash: Option<CertificateHash>,
+    pub admin: bool,
+    pub priority_speaker: bool,
+    pub version: ClientVersion,
+    pub position: Option<PositionInfo>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct MumbleTarget {
     pub sessions: HashSet<u32>,
     pub channels: HashSet<u32>,
 }
 
+/// Returns every client with all of its voice targets, `/users` is lighter for large servers
+#[utoipa::path(
+    get,
+    path = "/status",
+    tag = "users",
+    responses(
+        (status = 200, description = "Clients by session", body = HashMap<u32, MumbleClient>),
+    )
+)]
 #[actix_web::get("/status")]
 pub async fn get_status(state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
     let mut clients = HashMap::new();
@@ -51,14 +71,21 @@ pub async fn get_status(state: web::Data<ServerStateRef>) -> Result<HttpResponse
             let mut mumble_client = MumbleClient {
                 name: client.get_name().as_ref().clone(),
                 session_id: client.session_id,
+                server_id: client.server_id,
                 channel: channel_name,
                 mute: client.is_muted(),
+                instance: client.get_instance(),
                 good,
                 late,
                 lost,
                 resync,
                 last_good_duration: Instant::now().duration_since(last_good).as_millis(),
                 targets: Vec::new(),
+                certificate_hash: client.certificate_hash.clone(),
+                admin: client.admin,
+                priority_speaker: client.is_priority_speaker(),
+                version: client.version.clone(),
+                position: client.get_position(&state.config).as_ref().map(PositionInfo::from),
             };
 
             for target in &client.targets {
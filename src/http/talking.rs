@@ -0,0 +1,81 @@
+use crate::error::ErrorBody;
+use crate::talking::TalkingInfo;
+use crate::user_ref::SessionUserRef;
+use crate::{error::MumbleError, state::ServerStateRef};
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UserTalking {
+    session: u32,
+    name: String,
+    #[serde(flatten)]
+    talking: TalkingInfo,
+}
+
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TalkingQuery {
+    /// Only return the clients that are currently talking
+    #[serde(default)]
+    talking: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/{user}/talking",
+    tag = "users",
+    params(("user" = SessionUserRef, Path)),
+    responses(
+        (status = 200, description = "Talking state of the user", body = UserTalking),
+        (status = 404, description = "The user isn't connected", body = ErrorBody),
+    )
+)]
+#[actix_web::get("/users/{user}/talking")]
+pub async fn get_user_talking(user: web::Path<SessionUserRef>, state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let client = state.get_client(&user).ok_or(MumbleError::UserNotFound)?;
+    let talking = client.talking.lock().get_info();
+
+    Ok(HttpResponse::Ok().json(&UserTalking {
+        session: client.session_id,
+        name: client.get_name().as_ref().clone(),
+        talking,
+    }))
+}
+
+/// Returns the talking state of every user, by session
+#[utoipa::path(
+    get,
+    path = "/talking",
+    tag = "users",
+    params(TalkingQuery),
+    responses(
+        (status = 200, description = "Talking state of the users by session", body = HashMap<u32, UserTalking>),
+    )
+)]
+#[actix_web::get("/talking")]
+pub async fn get_talking(query: web::Query<TalkingQuery>, state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let mut users = HashMap::new();
+
+    let mut iter = state.clients.first_entry_async().await;
+    while let Some(client) = iter {
+        let talking = { client.talking.lock().get_info() };
+
+        if talking.talking || !query.talking {
+            users.insert(
+                client.session_id,
+                UserTalking {
+                    session: client.session_id,
+                    name: client.get_name().as_ref().clone(),
+                    talking,
+                },
+            );
+        }
+
+        iter = client.next_async().await;
+    }
+
+    Ok(HttpResponse::Ok().json(&users))
+}
@@ -0,0 +1,227 @@
+use crate::certificate::CertificateHash;
+use crate::client::ClientRef;
+use crate::error::{ErrorBody, MumbleError};
+use crate::position::PositionInfo;
+use crate::state::{ServerState, ServerStateRef};
+use crate::talking::TalkingInfo;
+use crate::user_ref::SessionUserRef;
+use crate::version::ClientVersion;
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+use utoipa::{IntoParams, ToSchema};
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 1000;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UserTarget {
+    /// Voice target id used by the client, between 1 and 30
+    pub id: u8,
+    pub sessions: HashSet<u32>,
+    pub channels: HashSet<u32>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UserDetail {
+    pub session_id: u32,
+    pub server_id: Option<u32>,
+    pub name: String,
+    pub channel_id: u32,
+    pub channel: Option<String>,
+    pub mute: bool,
+    pub deaf: bool,
+    pub priority_speaker: bool,
+    pub instance: i32,
+    /// Voice targets whose sessions hear the user in every instance
+    pub global_targets: Vec<u8>,
+    /// False while the voice goes through the TCP tunnel
+    pub udp: bool,
+    pub talking: TalkingInfo,
+    pub good: u32,
+    pub late: u32,
+    pub lost: u32,
+    pub resync: u32,
+    pub last_good_duration: u128,
+    /// Seconds since the client connected
+    pub connected_for: u64,
+    /// Only the voice targets the client configured
+    pub targets: Vec<UserTarget>,
+    pub certificate_hash: Option<CertificateHash>,
+    pub admin: bool,
+    pub version: ClientVersion,
+    pub position: Option<PositionInfo>,
+}
+
+impl UserDetail {
+    fn new(client: &ClientRef, state: &ServerState) -> Self {
+        let channel_id = client.channel_id.load(Ordering::Relaxed);
+        let channel = state.channels.get(&channel_id).map(|channel| channel.name.clone());
+
+        let (good, late, lost, resync, last_good) = {
+            let crypt = client.crypt_state.lock();
+            (crypt.good, crypt.late, crypt.lost, crypt.resync, crypt.last_good)
+        };
+
+        let mut targets = Vec::new();
+
+        for (index, target) in client.targets.iter().enumerate() {
+            let mut sessions = HashSet::new();
+            let mut channels = HashSet::new();
+
+            target.sessions.scan(|v| {
+                sessions.insert(*v);
+            });
+
+            target.channels.scan(|v| {
+                channels.insert(*v);
+            });
+
+            if !sessions.is_empty() || !channels.is_empty() {
+                targets.push(UserTarget {
+                    id: index as u8 + 1,
+                    sessions,
+                    channels,
+                });
+            }
+        }
+
+        Self {
+            session_id: client.session_id,
+            server_id: client.server_id,
+            name: client.get_name().as_ref().clone(),
+            channel_id,
+            channel,
+            mute: client.is_muted(),
+            deaf: client.is_deaf(),
+            priority_speaker: client.is_priority_speaker(),
+            instance: client.get_instance(),
+            global_targets: client.get_global_targets(),
+            udp: client.udp_socket_addr.load().is_some(),
+            talking: client.talking.lock().get_info(),
+            good,
+            late,
+            lost,
+            resync,
+            last_good_duration: Instant::now().duration_since(last_good).as_millis(),
+            connected_for: client.connected_at.elapsed().as_secs(),
+            targets,
+            certificate_hash: client.certificate_hash.clone(),
+            admin: client.admin,
+            version: client.version.clone(),
+            position: client.get_position(&state.config).as_ref().map(PositionInfo::from),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UsersQuery {
+    channel: Option<u32>,
+    muted: Option<bool>,
+    talking: Option<bool>,
+    /// True for clients sending their voice over UDP, false for the ones using the TCP tunnel
+    udp: Option<bool>,
+    name_prefix: Option<String>,
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+    /// Comma separated fields to return, `session_id` is always returned
+    fields: Option<String>,
+}
+
+impl UsersQuery {
+    fn matches(&self, client: &ClientRef) -> bool {
+        self.channel
+            .is_none_or(|channel| client.channel_id.load(Ordering::Relaxed) == channel)
+            && self.muted.is_none_or(|muted| client.is_muted() == muted)
+            && self.talking.is_none_or(|talking| client.talking.lock().is_talking() == talking)
+            && self.udp.is_none_or(|udp| client.udp_socket_addr.load().is_some() == udp)
+            && self
+                .name_prefix
+                .as_deref()
+                .is_none_or(|prefix| client.get_name().starts_with(prefix))
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UsersPage {
+    /// Number of users matching the filters
+    total: usize,
+    offset: usize,
+    limit: usize,
+    /// Users with only the selected fields
+    #[schema(value_type = Vec<Object>)]
+    users: Vec<serde_json::Value>,
+}
+
+/// Lists the users matching the filters, ordered by session
+#[utoipa::path(
+    get,
+    path = "/users",
+    tag = "users",
+    params(UsersQuery),
+    responses(
+        (status = 200, description = "A page of users, each user has the fields of the user detail", body = UsersPage),
+    )
+)]
+#[actix_web::get("/users")]
+pub async fn get_users(query: web::Query<UsersQuery>, state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let mut matching = Vec::new();
+
+    let mut iter = state.clients.first_entry_async().await;
+    while let Some(client) = iter {
+        if query.matches(client.get()) {
+            matching.push(client.get().clone());
+        }
+
+        iter = client.next_async().await;
+    }
+
+    // sessions never change, so pages stay stable between requests
+    matching.sort_by_key(|client| client.session_id);
+
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+    let fields = query
+        .fields
+        .as_deref()
+        .map(|fields| fields.split(',').map(str::trim).collect::<HashSet<_>>());
+
+    let mut users = Vec::new();
+
+    for client in matching.iter().skip(query.offset).take(limit) {
+        let mut user = serde_json::to_value(UserDetail::new(client, &state))?;
+
+        if let (Some(fields), Some(user)) = (&fields, user.as_object_mut()) {
+            user.retain(|key, _| key == "session_id" || fields.contains(key.as_str()));
+        }
+
+        users.push(user);
+    }
+
+    Ok(HttpResponse::Ok().json(&UsersPage {
+        total: matching.len(),
+        offset: query.offset,
+        limit,
+        users,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/{user}",
+    tag = "users",
+    params(("user" = SessionUserRef, Path)),
+    responses(
+        (status = 200, description = "Everything known about the user", body = UserDetail),
+        (status = 404, description = "The user isn't connected", body = ErrorBody),
+    )
+)]
+#[actix_web::get("/users/{user}")]
+pub async fn get_user(user: web::Path<SessionUserRef>, state: web::Data<ServerStateRef>) -> Result<HttpResponse, MumbleError> {
+    let client = state.get_client(&user).ok_or(MumbleError::UserNotFound)?;
+
+    Ok(HttpResponse::Ok().json(UserDetail::new(&client, &state)))
+}
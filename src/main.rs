@@ -1,35 +1,73 @@
 #[macro_use]
 extern crate lazy_static;
 
+mod audit;
+mod certificate;
 mod channel;
 mod check;
 mod clean;
 mod client;
+mod config;
+mod context_action;
 mod crypt;
+mod deadline;
 mod error;
+mod event;
 mod handler;
+mod health;
 mod http;
 mod message;
 mod metrics;
+mod position;
 mod proto;
+mod proximity;
+mod ratelimit;
 mod server;
+mod shutdown;
+mod snapshot;
 mod state;
+mod talking;
 mod target;
+#[cfg(test)]
+mod test_util;
+#[cfg(unix)]
+mod upgrade;
+mod user_ref;
 mod varint;
+mod version;
 mod voice;
+mod webhook;
 
+use crate::audit::AuditLog;
+use crate::certificate::{normalize_fingerprint, AcceptAnyClientCert};
 use crate::clean::clean_loop;
-use crate::http::create_http_server;
+use crate::config::{ClientSettings, Config};
+use crate::http::{create_http_server, create_metrics_server, parse_api_token, ApiAuth, ApiScope, ApiToken};
 use crate::proto::mumble::Version;
-use crate::server::{create_tcp_server, create_udp_server};
+use crate::ratelimit::parse_rate;
+use crate::server::constants::MAX_CLIENTS;
+use crate::server::{create_position_server, create_tcp_server, create_udp_server};
+use crate::shutdown::{graceful_shutdown, shutdown_signal};
+use crate::snapshot::StateSnapshot;
 use crate::state::ServerState;
+#[cfg(unix)]
+use crate::upgrade::serve_handover;
+use crate::version::{format_version, parse_version, v2_to_v1};
+use crate::voice::PROTOBUF_UDP_VERSION;
+use crate::webhook::Webhooks;
 
 use clap::Parser;
+use futures::FutureExt;
 use rcgen::{date_time_ymd, CertificateParams, DistinguishedName, DnType, KeyPair, PKCS_ECDSA_P384_SHA384};
+use regex::Regex;
 use rustls::crypto::{self, CryptoProvider};
 use rustls_pki_types::pem::PemObject;
 use rustls_pki_types::PrivateKeyDer;
+#[cfg(unix)]
+use std::os::fd::AsRawFd;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::{TcpListener, UdpSocket};
 use tokio_rustls::rustls::{self};
 use tokio_rustls::TlsAcceptor;
@@ -50,9 +88,30 @@ struct Args {
     /// User for the http server api basic authentification
     #[clap(long, value_parser, default_value = "admin")]
     http_user: String,
-    /// Password for the http server api basic authentification
+    /// Password for the http server api basic authentification, this user can use every endpoint
     #[clap(long, value_parser)]
     http_password: Option<String>,
+    /// Bearer token for the http server api given as name:scope,scope:token, can be repeated. Scopes
+    /// are status, metrics, moderate, channels and admin
+    #[clap(long = "api-token", value_parser = parse_api_token)]
+    api_tokens: Vec<ApiToken>,
+    /// Serve /metrics without authentication
+    #[clap(long)]
+    public_metrics: bool,
+    /// Listen address for a separate http server with only /metrics, /healthz and /readyz, use
+    /// unix:<path> for a unix socket
+    #[clap(long, value_parser)]
+    metrics_listen: Option<String>,
+    /// User for the basic authentification of the metrics listener
+    #[clap(long, value_parser, default_value = "metrics")]
+    metrics_user: String,
+    /// Password for the basic authentification of the metrics listener, it is public when neither
+    /// a password nor a token is set
+    #[clap(long, value_parser)]
+    metrics_password: Option<String>,
+    /// Bearer token for the metrics listener
+    #[clap(long, value_parser)]
+    metrics_token: Option<String>,
     /// Use TLS for the http server (https), will use the same certificate as the mumble server
     #[clap(long)]
     https: bool,
@@ -65,6 +124,100 @@ struct Args {
     /// Path to the certificate file for the TLS certificate
     #[clap(long, value_parser, default_value = "cert.pem")]
     cert: String,
+    /// Ask mumble clients for a TLS client certificate, clients without one (like FiveM) can still connect
+    #[clap(long)]
+    client_certificates: bool,
+    /// Certificate fingerprint (SHA1 or SHA256) of a client that should be treated as an admin, can be repeated
+    #[clap(long = "admin-certificate", value_parser)]
+    admin_certificates: Vec<String>,
+    /// Certificate fingerprint (SHA1 or SHA256) of a client that isn't allowed to connect, can be repeated
+    #[clap(long = "banned-certificate", value_parser)]
+    banned_certificates: Vec<String>,
+    /// Reject clients older than this protocol version (e.g. 1.3.0)
+    #[clap(long, value_parser = parse_version)]
+    min_client_version: Option<u64>,
+    /// Welcome text shown to clients when they connect
+    #[clap(long, value_parser, default_value = "SoZ Mumble Server")]
+    welcome_text: String,
+    /// Maximum length of a text message
+    #[clap(long, value_parser, default_value_t = 512)]
+    message_length: u32,
+    /// Maximum length of an image message, 0 disables images
+    #[clap(long, value_parser, default_value_t = 0)]
+    image_message_length: u32,
+    /// Maximum number of users on the server
+    #[clap(long, value_parser, default_value_t = MAX_CLIENTS as u32)]
+    max_users: u32,
+    /// Client version suggested to clients (e.g. 1.4.0)
+    #[clap(long, value_parser = parse_version)]
+    suggest_version: Option<u64>,
+    /// Suggest clients to enable or disable positional audio
+    #[clap(long, value_parser)]
+    suggest_positional: Option<bool>,
+    /// Suggest clients to enable or disable push to talk
+    #[clap(long, value_parser)]
+    suggest_push_to_talk: Option<bool>,
+    /// Maximum number of server list pings answered per second for a single ip, unlimited by default
+    #[clap(long, value_parser = parse_rate)]
+    ping_rate_limit: Option<f64>,
+    /// Seconds without a ping on the TCP connection after which a client is disconnected
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..), default_value_t = 30)]
+    ping_timeout: u64,
+    /// Milliseconds without a good UDP packet after which the crypt of a client is reset, it
+    /// tunnels voice over TCP until it sets up UDP again
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..), default_value_t = 8000)]
+    udp_timeout: u64,
+    /// Listen address for the JSON position updates of the game server over UDP, this isn't
+    /// authenticated so it should only be reachable by the game server
+    #[clap(long, value_parser)]
+    position_listen: Option<String>,
+    /// Seconds after which a position pushed by the game server is considered stale
+    #[clap(long, value_parser, default_value_t = 5)]
+    position_ttl: u64,
+    /// Ignore the positions sent by clients and only use the ones pushed by the game server
+    #[clap(long)]
+    trusted_positions_only: bool,
+    /// Drop the audio of other speakers for listeners while they hear a priority speaker
+    #[clap(long)]
+    priority_speaker_ducking: bool,
+    /// Regex extracting the FiveM server id from the username with its first capture group, users
+    /// can then be addressed as server:<id> in the http api
+    #[clap(long, value_parser = Regex::new, default_value = r"^\[(\d+)\]")]
+    server_id_pattern: Regex,
+    /// Url that gets a POST with a JSON body for notable server events, can be repeated
+    #[clap(long = "webhook-url", value_parser)]
+    webhook_urls: Vec<String>,
+    /// Secret used to sign the webhook bodies, the HMAC-SHA256 is sent in the X-Zumble-Signature header
+    #[clap(long, value_parser)]
+    webhook_secret: Option<String>,
+    /// Path of the JSON lines audit log of the administrative changes, they are only kept in
+    /// memory without it
+    #[clap(long, value_parser)]
+    audit_log: Option<PathBuf>,
+    /// Size in megabytes after which the audit log is rotated
+    #[clap(long, value_parser, default_value_t = 10)]
+    audit_log_max_size: u64,
+    /// Seconds given to clients to leave after being told the server shuts down, the ones still
+    /// connected after that are disconnected
+    #[clap(long, value_parser, default_value_t = 10)]
+    drain_timeout: u64,
+    /// File the client settings, context actions, channel settings and moderation are saved to on
+    /// shutdown and restored from on start, they are lost on restart without it
+    #[clap(long, value_parser)]
+    state_file: Option<PathBuf>,
+    /// Text message sent to clients when the server shuts down
+    #[clap(long, value_parser, default_value = "Server is restarting")]
+    shutdown_message: String,
+    /// Take over the mumble listeners, channels and moderation of the server listening for upgrades
+    /// on this unix socket instead of binding new ones, it then closes its clients so they
+    /// reconnect here and exits, live sessions aren't handed over
+    #[cfg(unix)]
+    #[clap(long, value_parser)]
+    upgrade_from: Option<PathBuf>,
+    /// Unix socket on which a new process started with --upgrade-from can take over this server
+    #[cfg(unix)]
+    #[clap(long, value_parser)]
+    upgrade_socket: Option<PathBuf>,
 }
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -78,7 +231,10 @@ async fn main() {
     // This doesn't really matter for us as this isn't checked for FiveM
     let cert = vec!["localhost".to_string()];
 
-    CryptoProvider::install_default(crypto::ring::default_provider()).expect("failed to install ring crypto provider");
+    let crypto_provider = crypto::ring::default_provider();
+    let signature_algorithms = crypto_provider.signature_verification_algorithms;
+
+    CryptoProvider::install_default(crypto_provider).expect("failed to install ring crypto provider");
 
     // TODO: Maybe store this? not really entirely that useful but who knows.
     let generate_key = KeyPair::generate_for(&PKCS_ECDSA_P384_SHA384);
@@ -99,32 +255,139 @@ async fn main() {
 
     let key_der = PrivateKeyDer::from_pem_slice(pem.as_bytes()).expect("Couldn't make key_der");
 
-    let config = rustls::ServerConfig::builder()
+    let mumble_tls_config = if args.client_certificates {
+        rustls::ServerConfig::builder().with_client_cert_verifier(Arc::new(AcceptAnyClientCert::new(signature_algorithms)))
+    } else {
+        rustls::ServerConfig::builder().with_no_client_auth()
+    }
+    .with_single_cert(vec![cert.der().clone()], key_der.clone_key())
+    .expect("Unable to create tlsconfig");
+
+    // the http api never asks for a client certificate, browsers would prompt the user for one
+    let http_tls_config = rustls::ServerConfig::builder()
         .with_no_client_auth()
         .with_single_cert(vec![cert.der().clone()], key_der)
         .expect("Unable to create tlsconfig");
 
-    let acceptor = TlsAcceptor::from(Arc::new(config.clone()));
+    let acceptor = TlsAcceptor::from(Arc::new(mumble_tls_config));
 
     tracing::info!("tcp/udp server start listening on {}", args.listen);
     tracing::info!("http server start listening on {}", args.http_listen);
 
-    // Simulate 1.4.0 protocol version
-    let version = 1 << 16 | 4 << 8 | 0;
+    // Simulate 1.5.0 protocol version, clients only use the protobuf voice format with 1.5.0 servers
+    let version_v2 = PROTOBUF_UDP_VERSION;
+    let version = v2_to_v1(version_v2);
 
     let mut server_version = Version::new();
     server_version.set_os(std::env::consts::FAMILY.to_string());
     server_version.set_os_version(std::env::consts::OS.to_string());
     server_version.set_release(VERSION.to_string());
-    server_version.set_version(version);
+    server_version.set_version_v1(version);
+    server_version.set_version_v2(version_v2);
+
+    // an upgrade takes over the listening sockets of the running server, its clients reconnect
+    #[cfg(unix)]
+    let (tcp_listener, udp_socket, snapshot, predecessor) = match &args.upgrade_from {
+        Some(path) => {
+            tracing::info!("taking over the server listening for upgrades on {}", path.display());
+
+            let (taken_over, predecessor) = upgrade::take_over(path).expect("Unable to take over the running server");
+
+            (
+                taken_over.tcp_listener,
+                taken_over.udp_socket,
+                Some(taken_over.snapshot),
+                Some(predecessor),
+            )
+        }
+        None => (
+            TcpListener::bind(&args.listen).await.unwrap(),
+            UdpSocket::bind(&args.listen).await.unwrap(),
+            None,
+            None,
+        ),
+    };
+
+    #[cfg(not(unix))]
+    let (tcp_listener, udp_socket, snapshot) = (
+        TcpListener::bind(&args.listen).await.unwrap(),
+        UdpSocket::bind(&args.listen).await.unwrap(),
+        None,
+    );
+
+    // the snapshot of a process handed over is newer than the one it saved on a previous shutdown
+    let snapshot = match (snapshot, &args.state_file) {
+        (None, Some(path)) => StateSnapshot::load(path).expect("Unable to read the state file"),
+        (snapshot, _) => snapshot,
+    };
+
+    #[cfg(unix)]
+    let handover_fds = (tcp_listener.as_raw_fd(), udp_socket.as_raw_fd());
+
+    let udp_socket = Arc::new(udp_socket);
+    let config = Config {
+        admin_certificates: args.admin_certificates.iter().map(|f| normalize_fingerprint(f)).collect(),
+        banned_certificates: args.banned_certificates.iter().map(|f| normalize_fingerprint(f)).collect(),
+        min_client_version: args.min_client_version,
+        ping_rate_limit: args.ping_rate_limit,
+        ping_timeout: Duration::from_secs(args.ping_timeout),
+        udp_timeout: Duration::from_millis(args.udp_timeout),
+        position_ttl: Duration::from_secs(args.position_ttl),
+        trusted_positions_only: args.trusted_positions_only,
+        priority_speaker_ducking: args.priority_speaker_ducking,
+        server_id_pattern: args.server_id_pattern,
+    };
+
+    let client_settings = ClientSettings {
+        welcome_text: args.welcome_text,
+        message_length: args.message_length,
+        image_message_length: args.image_message_length,
+        max_users: args.max_users,
+        suggest_version: args.suggest_version.map(format_version),
+        suggest_positional: args.suggest_positional,
+        suggest_push_to_talk: args.suggest_push_to_talk,
+        ..ClientSettings::default()
+    };
+
+    let webhooks = Webhooks::new(args.webhook_urls, args.webhook_secret);
+
+    let audit = match args.audit_log {
+        Some(path) => AuditLog::open(path, args.audit_log_max_size * 1024 * 1024).expect("Unable to open the audit log"),
+        None => AuditLog::default(),
+    };
+
+    let state = Arc::new(ServerState::new(udp_socket.clone(), config, client_settings, webhooks, audit));
+
+    if let Some(snapshot) = snapshot {
+        state.restore(snapshot);
+    }
 
-    let udp_socket = Arc::new(UdpSocket::bind(&args.listen).await.unwrap());
-    let state = Arc::new(ServerState::new(udp_socket.clone()));
     let udp_state = state.clone();
 
-    actix_rt::spawn(async move {
-        create_udp_server(version, udp_socket, udp_state).await;
-    });
+    let udp_server_version = server_version.clone();
+
+    let mut tasks = vec![actix_rt::spawn(async move {
+        create_udp_server(udp_server_version, udp_socket, udp_state).await;
+    })];
+
+    // the previous process keeps the other listeners until it got everything it has to hand over
+    #[cfg(unix)]
+    if let Some(predecessor) = predecessor {
+        predecessor
+            .wait_for_release()
+            .await
+            .expect("The previous process didn't release its listeners");
+    }
+
+    if let Some(position_listen) = args.position_listen {
+        tracing::info!("position server start listening on {}", position_listen);
+
+        let position_state = state.clone();
+
+        tasks.push(actix_rt::spawn(async move {
+            create_position_server(position_listen, position_state).await;
+        }));
+    }
 
     let clean_state = state.clone();
 
@@ -132,28 +395,118 @@ async fn main() {
         clean_loop(clean_state).await;
     });
 
-    let tcp_listener = TcpListener::bind(args.listen.clone()).await.unwrap();
-
     let mut waiting_list = Vec::new();
 
     // Create tcp server
     let server = create_tcp_server(tcp_listener, acceptor, server_version, state.clone());
-    waiting_list.push(server);
+    let tcp_handle = server.handle();
+    let tcp_state = state.clone();
+
+    state.health.set_tcp_running(true);
+
+    waiting_list.push(
+        async move {
+            let result = server.await;
+            tcp_state.health.set_tcp_running(false);
+
+            result
+        }
+        .boxed_local(),
+    );
 
     let http_server = create_http_server(
         args.http_listen,
-        config,
+        http_tls_config,
         args.https,
         state.clone(),
-        args.http_user,
-        args.http_password,
+        ApiAuth {
+            realm: "Restricted area",
+            user: args.http_user,
+            password: args.http_password,
+            tokens: args.api_tokens,
+            public_metrics: args.public_metrics,
+        },
         args.http_log,
     );
 
+    let mut server_handles = Vec::new();
+
     if let Some(http_server) = http_server {
-        waiting_list.push(http_server);
+        server_handles.push(http_server.handle());
+        waiting_list.push(http_server.boxed_local());
+    }
+
+    if let Some(metrics_listen) = args.metrics_listen {
+        tracing::info!("metrics server start listening on {}", metrics_listen);
+
+        let public = args.metrics_password.is_none() && args.metrics_token.is_none();
+        let tokens = args
+            .metrics_token
+            .into_iter()
+            .map(|token| ApiToken {
+                name: "metrics".to_string(),
+                scopes: vec![ApiScope::Metrics],
+                token,
+            })
+            .collect();
+
+        let metrics_server = create_metrics_server(
+            metrics_listen,
+            state.clone(),
+            ApiAuth {
+                realm: "Metrics",
+                user: args.metrics_user,
+                password: args.metrics_password,
+                tokens,
+                public_metrics: public,
+            },
+        );
+
+        if let Some(metrics_server) = metrics_server {
+            server_handles.push(metrics_server.handle());
+            waiting_list.push(metrics_server.boxed_local());
+        }
     }
 
+    state.health.set_ready(true);
+
+    let shutdown_state = state.clone();
+    let drain_timeout = Duration::from_secs(args.drain_timeout);
+
+    actix_rt::spawn(async move {
+        #[cfg(unix)]
+        let drain_timeout = match args.upgrade_socket {
+            Some(path) => {
+                tracing::info!("listening for upgrades on {}", path.display());
+
+                tokio::select! {
+                    _ = shutdown_signal() => drain_timeout,
+                    // voice doesn't reach this process anymore, its clients are closed right away
+                    // so they reconnect to the new one
+                    _ = serve_handover(path, &shutdown_state, handover_fds, &tcp_handle, &server_handles, &tasks) => Duration::ZERO,
+                }
+            }
+            None => {
+                shutdown_signal().await;
+
+                drain_timeout
+            }
+        };
+
+        #[cfg(not(unix))]
+        shutdown_signal().await;
+
+        graceful_shutdown(
+            &shutdown_state,
+            tcp_handle,
+            server_handles,
+            drain_timeout,
+            args.shutdown_message,
+            args.state_file,
+        )
+        .await;
+    });
+
     match futures::future::try_join_all(waiting_list).await {
         Ok(_) => (),
         Err(e) => {
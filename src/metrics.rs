@@ -1,6 +1,11 @@
 use lazy_static::lazy_static;
-use prometheus::{opts, register_int_counter_vec, register_int_gauge};
-use prometheus::{IntCounterVec, IntGauge};
+use parking_lot::Mutex;
+use prometheus::{exponential_buckets, histogram_opts, opts};
+use prometheus::{
+    register_counter, register_histogram, register_int_counter, register_int_counter_vec, register_int_gauge, register_int_gauge_vec,
+};
+use prometheus::{Counter, Histogram, IntCounter, IntCounterVec, IntGauge, IntGaugeVec};
+use std::collections::HashSet;
 
 lazy_static! {
     pub static ref MESSAGES_TOTAL: IntCounterVec = register_int_counter_vec!(
@@ -13,6 +18,21 @@ lazy_static! {
             .expect("can't create a metric");
     pub static ref CLIENTS_TOTAL: IntGauge =
         register_int_gauge!(opts!("zumble_clients_total", "Total number of clients")).expect("can't create a metric");
+    pub static ref CLIENTS_BY_VERSION: IntGaugeVec = register_int_gauge_vec!(
+        opts!("zumble_clients_by_version", "Number of clients by the major.minor.patch release they reported"),
+        &["version"]
+    )
+    .expect("can't create a metric");
+    /// Serializes the changes to [CLIENTS_BY_VERSION] so a series isn't removed while a client of
+    /// that version connects
+    static ref CLIENTS_BY_VERSION_LOCK: Mutex<()> = Mutex::new(());
+    pub static ref CLIENTS_TALKING: IntGauge =
+        register_int_gauge!(opts!("zumble_clients_talking", "Number of clients currently talking")).expect("can't create a metric");
+    pub static ref TALK_SPURTS_TOTAL: IntCounter =
+        register_int_counter!(opts!("zumble_talk_spurts_total", "Number of times a client started talking"))
+            .expect("can't create a metric");
+    pub static ref TALK_TIME_SECONDS_TOTAL: Counter =
+        register_counter!(opts!("zumble_talk_time_seconds_total", "Time spent talking by all clients")).expect("can't create a metric");
     pub static ref UNKNOWN_MESSAGES_TOTAL: IntCounterVec = register_int_counter_vec!(
         opts!(
             "zumble_unknown_messages_total",
@@ -29,4 +49,107 @@ lazy_static! {
         &["protocol", "direction", "kind"]
     )
     .expect("can't create a metric");
+    pub static ref WEBHOOK_DELIVERIES_TOTAL: IntCounterVec = register_int_counter_vec!(
+        opts!("zumble_webhook_deliveries_total", "Number of webhook deliveries by result"),
+        &["result"]
+    )
+    .expect("can't create a metric");
+    pub static ref CHANNEL_CLIENTS: IntGaugeVec = register_int_gauge_vec!(
+        opts!("zumble_channel_clients", "Number of clients in a channel"),
+        &["channel_id", "channel"]
+    )
+    .expect("can't create a metric");
+    /// Labels of the [CHANNEL_CLIENTS] series that were set on the last update
+    static ref CHANNEL_CLIENTS_LABELS: Mutex<HashSet<(String, String)>> = Mutex::new(HashSet::new());
+    pub static ref CLIENTS_BY_TRANSPORT: IntGaugeVec = register_int_gauge_vec!(
+        opts!(
+            "zumble_clients_by_transport",
+            "Number of clients sending their voice over udp or the tcp tunnel"
+        ),
+        &["transport"]
+    )
+    .expect("can't create a metric");
+    pub static ref VOICE_PACKETS_TOTAL: IntCounterVec = register_int_counter_vec!(
+        opts!(
+            "zumble_voice_packets_total",
+            "Number of good, late and lost voice packets decrypted from the clients"
+        ),
+        &["state"]
+    )
+    .expect("can't create a metric");
+    pub static ref VOICE_FANOUT: Histogram = register_histogram!(histogram_opts!(
+        "zumble_voice_fanout",
+        "Number of listeners a voice packet was queued for",
+        vec![0.0, 1.0, 2.0, 4.0, 8.0, 16.0, 32.0, 64.0, 128.0, 256.0, 512.0]
+    ))
+    .expect("can't create a metric");
+    pub static ref VOICE_ROUTING_SECONDS: Histogram = register_histogram!(histogram_opts!(
+        "zumble_voice_routing_seconds",
+        "Time spent finding the listeners of a voice packet and queuing it for them",
+        exponential_buckets(0.00001, 2.0, 14).expect("can't create buckets")
+    ))
+    .expect("can't create a metric");
+    pub static ref VOICE_DROPPED_TOTAL: IntCounterVec = register_int_counter_vec!(
+        opts!("zumble_voice_dropped_total", "Number of voice packets dropped by reason"),
+        &["reason"]
+    )
+    .expect("can't create a metric");
+    pub static ref CRYPT_RESETS_TOTAL: IntCounter =
+        register_int_counter!(opts!("zumble_crypt_resets_total", "Number of crypt setups resent to clients"))
+            .expect("can't create a metric");
+    pub static ref TLS_HANDSHAKE_FAILURES_TOTAL: IntCounter =
+        register_int_counter!(opts!("zumble_tls_handshake_failures_total", "Number of failed TLS handshakes"))
+            .expect("can't create a metric");
+}
+
+pub fn client_version_connected(version: &str) {
+    let _lock = CLIENTS_BY_VERSION_LOCK.lock();
+
+    CLIENTS_BY_VERSION.with_label_values(&[version]).inc();
+}
+
+/// The series of a version goes away with its last client
+pub fn client_version_disconnected(version: &str) {
+    let _lock = CLIENTS_BY_VERSION_LOCK.lock();
+
+    let gauge = CLIENTS_BY_VERSION.with_label_values(&[version]);
+    gauge.dec();
+
+    if gauge.get() <= 0 {
+        let _ = CLIENTS_BY_VERSION.remove_label_values(&[version]);
+    }
+}
+
+/// Sets the clients of every channel, the series of channels that are gone or got renamed are
+/// removed while the others keep being exported
+pub fn set_channel_clients(channels: impl IntoIterator<Item = (u32, String, i64)>) {
+    let mut labels = CHANNEL_CLIENTS_LABELS.lock();
+    let mut current = HashSet::with_capacity(labels.len());
+
+    for (id, name, clients) in channels {
+        let id = id.to_string();
+
+        CHANNEL_CLIENTS.with_label_values(&[&id, &name]).set(clients);
+        current.insert((id, name));
+    }
+
+    for (id, name) in labels.difference(&current) {
+        let _ = CHANNEL_CLIENTS.remove_label_values(&[id, name]);
+    }
+
+    *labels = current;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_removed_channels_lose_their_series() {
+        set_channel_clients([(9001, "kept".to_string(), 2), (9002, "removed".to_string(), 1)]);
+        set_channel_clients([(9001, "kept".to_string(), 3)]);
+
+        assert_eq!(CHANNEL_CLIENTS.with_label_values(&["9001", "kept"]).get(), 3);
+        assert!(CHANNEL_CLIENTS.remove_label_values(&["9002", "removed"]).is_err());
+    }
 }
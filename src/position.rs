@@ -0,0 +1,118 @@
+use crate::user_ref::UserRef;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PositionSource {
+    /// Sent by the mumble client in its voice packets, clients can spoof these
+    Client,
+    /// Pushed by the game server, these are trusted over the client ones
+    Server,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Position {
+    pub coordinates: [f32; 3],
+    pub routing_bucket: Option<i32>,
+    pub source: PositionSource,
+    pub updated_at: Instant,
+}
+
+impl Position {
+    pub fn new(coordinates: [f32; 3], routing_bucket: Option<i32>, source: PositionSource) -> Self {
+        Self {
+            coordinates,
+            routing_bucket,
+            source,
+            updated_at: Instant::now(),
+        }
+    }
+
+    pub fn is_expired(&self, ttl: Duration) -> bool {
+        self.updated_at.elapsed() > ttl
+    }
+
+    pub fn distance(&self, other: &Position) -> f32 {
+        self.coordinates
+            .iter()
+            .zip(other.coordinates.iter())
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum::<f32>()
+            .sqrt()
+    }
+}
+
+/// Position of a client as returned by the http api
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct PositionInfo {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub routing_bucket: Option<i32>,
+    pub source: PositionSource,
+    /// Milliseconds since the position was updated
+    pub age: u128,
+}
+
+impl From<&Position> for PositionInfo {
+    fn from(position: &Position) -> Self {
+        Self {
+            x: position.coordinates[0],
+            y: position.coordinates[1],
+            z: position.coordinates[2],
+            routing_bucket: position.routing_bucket,
+            source: position.source,
+            age: position.updated_at.elapsed().as_millis(),
+        }
+    }
+}
+
+/// Position pushed by the game server, the client is found by its session, its FiveM server id or
+/// its name
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PositionUpdate {
+    pub session: Option<u32>,
+    pub server_id: Option<u32>,
+    pub name: Option<String>,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    #[serde(default)]
+    pub routing_bucket: Option<i32>,
+}
+
+impl PositionUpdate {
+    pub fn user(&self) -> Option<UserRef> {
+        match (self.session, self.server_id, &self.name) {
+            (Some(session), _, _) => Some(UserRef::Session(session)),
+            (_, Some(server_id), _) => Some(UserRef::ServerId(server_id)),
+            (_, _, Some(name)) => Some(UserRef::Name(name.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn coordinates(&self) -> Option<[f32; 3]> {
+        let coordinates = [self.x, self.y, self.z];
+
+        coordinates.iter().all(|v| v.is_finite()).then_some(coordinates)
+    }
+}
+
+/// The game server can either send a single update or a batch of them
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum PositionUpdates {
+    Batch(Vec<PositionUpdate>),
+    Single(PositionUpdate),
+}
+
+impl PositionUpdates {
+    pub fn into_vec(self) -> Vec<PositionUpdate> {
+        match self {
+            PositionUpdates::Batch(updates) => updates,
+            PositionUpdates::Single(update) => vec![update],
+        }
+    }
+}
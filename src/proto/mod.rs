@@ -7,7 +7,11 @@ use std::future::Future;
 use std::pin::Pin;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
+// generated code, rust-protobuf 2 predates these lints
+#[allow(unused_parens, mismatched_lifetime_syntaxes)]
 pub mod mumble;
+#[allow(unused_parens, mismatched_lifetime_syntaxes)]
+pub mod mumble_udp;
 
 #[derive(Debug, Clone, Copy)]
 pub enum MessageKind {
@@ -37,6 +41,7 @@ pub enum MessageKind {
     RequestBlob = 23,
     ServerConfig = 24,
     SuggestConfig = 25,
+    PluginDataTransmission = 26,
 }
 
 impl fmt::Display for MessageKind {
@@ -68,6 +73,7 @@ impl fmt::Display for MessageKind {
             MessageKind::RequestBlob => write!(f, "RequestBlob"),
             MessageKind::ServerConfig => write!(f, "ServerConfig"),
             MessageKind::SuggestConfig => write!(f, "SuggestConfig"),
+            MessageKind::PluginDataTransmission => write!(f, "PluginDataTransmission"),
         }
     }
 }
@@ -103,6 +109,7 @@ impl TryFrom<u16> for MessageKind {
             23 => Ok(MessageKind::RequestBlob),
             24 => Ok(MessageKind::ServerConfig),
             25 => Ok(MessageKind::SuggestConfig),
+            26 => Ok(MessageKind::PluginDataTransmission),
             _ => Err(MumbleError::UnexpectedMessageKind(value)),
         }
     }
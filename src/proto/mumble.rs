@@ -1,4 +1,4 @@
-// This file is generated by rust-protobuf 2.27.1. Do not edit
+// This file is generated by rust-protobuf 2.28.0. Do not edit
 // @generated
 
 // https://github.com/rust-lang/rust-clippy/issues/702
@@ -20,12 +20,13 @@
 
 /// Generated files are compatible only with the same version
 /// of protobuf runtime.
-// const _PROTOBUF_VERSION_CHECK: () = ::protobuf::VERSION_2_27_1;
+// const _PROTOBUF_VERSION_CHECK: () = ::protobuf::VERSION_2_28_0;
 
 #[derive(PartialEq,Clone,Default)]
 pub struct Version {
     // message fields
-    version: ::std::option::Option<u32>,
+    version_v1: ::std::option::Option<u32>,
+    version_v2: ::std::option::Option<u64>,
     release: ::protobuf::SingularField<::std::string::String>,
     os: ::protobuf::SingularField<::std::string::String>,
     os_version: ::protobuf::SingularField<::std::string::String>,
@@ -45,23 +46,42 @@ impl Version {
         ::std::default::Default::default()
     }
 
-    // optional uint32 version = 1;
+    // optional uint32 version_v1 = 1;
 
 
-    pub fn get_version(&self) -> u32 {
-        self.version.unwrap_or(0)
+    pub fn get_version_v1(&self) -> u32 {
+        self.version_v1.unwrap_or(0)
     }
-    pub fn clear_version(&mut self) {
-        self.version = ::std::option::Option::None;
+    pub fn clear_version_v1(&mut self) {
+        self.version_v1 = ::std::option::Option::None;
     }
 
-    pub fn has_version(&self) -> bool {
-        self.version.is_some()
+    pub fn has_version_v1(&self) -> bool {
+        self.version_v1.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_version_v1(&mut self, v: u32) {
+        self.version_v1 = ::std::option::Option::Some(v);
+    }
+
+    // optional uint64 version_v2 = 5;
+
+
+    pub fn get_version_v2(&self) -> u64 {
+        self.version_v2.unwrap_or(0)
+    }
+    pub fn clear_version_v2(&mut self) {
+        self.version_v2 = ::std::option::Option::None;
+    }
+
+    pub fn has_version_v2(&self) -> bool {
+        self.version_v2.is_some()
     }
 
     // Param is passed by value, moved
-    pub fn set_version(&mut self, v: u32) {
-        self.version = ::std::option::Option::Some(v);
+    pub fn set_version_v2(&mut self, v: u64) {
+        self.version_v2 = ::std::option::Option::Some(v);
     }
 
     // optional string release = 2;
@@ -187,7 +207,14 @@ impl ::protobuf::Message for Version {
                         return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
                     }
                     let tmp = is.read_uint32()?;
-                    self.version = ::std::option::Option::Some(tmp);
+                    self.version_v1 = ::std::option::Option::Some(tmp);
+                },
+                5 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    }
+                    let tmp = is.read_uint64()?;
+                    self.version_v2 = ::std::option::Option::Some(tmp);
                 },
                 2 => {
                     ::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.release)?;
@@ -210,9 +237,12 @@ impl ::protobuf::Message for Version {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u32 {
         let mut my_size = 0;
-        if let Some(v) = self.version {
+        if let Some(v) = self.version_v1 {
             my_size += ::protobuf::rt::value_size(1, v, ::protobuf::wire_format::WireTypeVarint);
         }
+        if let Some(v) = self.version_v2 {
+            my_size += ::protobuf::rt::value_size(5, v, ::protobuf::wire_format::WireTypeVarint);
+        }
         if let Some(ref v) = self.release.as_ref() {
             my_size += ::protobuf::rt::string_size(2, &v);
         }
@@ -228,9 +258,12 @@ impl ::protobuf::Message for Version {
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::ProtobufResult<()> {
-        if let Some(v) = self.version {
+        if let Some(v) = self.version_v1 {
             os.write_uint32(1, v)?;
         }
+        if let Some(v) = self.version_v2 {
+            os.write_uint64(5, v)?;
+        }
         if let Some(ref v) = self.release.as_ref() {
             os.write_string(2, &v)?;
         }
@@ -279,9 +312,14 @@ impl ::protobuf::Message for Version {
         descriptor.get(|| {
             let mut fields = ::std::vec::Vec::new();
             fields.push(::protobuf::reflect::accessor::make_option_accessor::<_, ::protobuf::types::ProtobufTypeUint32>(
-                "version",
-                |m: &Version| { &m.version },
-                |m: &mut Version| { &mut m.version },
+                "version_v1",
+                |m: &Version| { &m.version_v1 },
+                |m: &mut Version| { &mut m.version_v1 },
+            ));
+            fields.push(::protobuf::reflect::accessor::make_option_accessor::<_, ::protobuf::types::ProtobufTypeUint64>(
+                "version_v2",
+                |m: &Version| { &m.version_v2 },
+                |m: &mut Version| { &mut m.version_v2 },
             ));
             fields.push(::protobuf::reflect::accessor::make_singular_field_accessor::<_, ::protobuf::types::ProtobufTypeString>(
                 "release",
@@ -314,7 +352,8 @@ impl ::protobuf::Message for Version {
 
 impl ::protobuf::Clear for Version {
     fn clear(&mut self) {
-        self.version = ::std::option::Option::None;
+        self.version_v1 = ::std::option::Option::None;
+        self.version_v2 = ::std::option::Option::None;
         self.release.clear();
         self.os.clear();
         self.os_version.clear();
@@ -11215,7 +11254,8 @@ impl ::protobuf::reflect::ProtobufValue for ServerConfig {
 #[derive(PartialEq,Clone,Default)]
 pub struct SuggestConfig {
     // message fields
-    version: ::std::option::Option<u32>,
+    version_v1: ::std::option::Option<u32>,
+    version_v2: ::std::option::Option<u64>,
     positional: ::std::option::Option<bool>,
     push_to_talk: ::std::option::Option<bool>,
     // special fields
@@ -11234,23 +11274,42 @@ impl SuggestConfig {
         ::std::default::Default::default()
     }
 
-    // optional uint32 version = 1;
+    // optional uint32 version_v1 = 1;
 
 
-    pub fn get_version(&self) -> u32 {
-        self.version.unwrap_or(0)
+    pub fn get_version_v1(&self) -> u32 {
+        self.version_v1.unwrap_or(0)
     }
-    pub fn clear_version(&mut self) {
-        self.version = ::std::option::Option::None;
+    pub fn clear_version_v1(&mut self) {
+        self.version_v1 = ::std::option::Option::None;
     }
 
-    pub fn has_version(&self) -> bool {
-        self.version.is_some()
+    pub fn has_version_v1(&self) -> bool {
+        self.version_v1.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_version_v1(&mut self, v: u32) {
+        self.version_v1 = ::std::option::Option::Some(v);
+    }
+
+    // optional uint64 version_v2 = 4;
+
+
+    pub fn get_version_v2(&self) -> u64 {
+        self.version_v2.unwrap_or(0)
+    }
+    pub fn clear_version_v2(&mut self) {
+        self.version_v2 = ::std::option::Option::None;
+    }
+
+    pub fn has_version_v2(&self) -> bool {
+        self.version_v2.is_some()
     }
 
     // Param is passed by value, moved
-    pub fn set_version(&mut self, v: u32) {
-        self.version = ::std::option::Option::Some(v);
+    pub fn set_version_v2(&mut self, v: u64) {
+        self.version_v2 = ::std::option::Option::Some(v);
     }
 
     // optional bool positional = 2;
@@ -11306,7 +11365,14 @@ impl ::protobuf::Message for SuggestConfig {
                         return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
                     }
                     let tmp = is.read_uint32()?;
-                    self.version = ::std::option::Option::Some(tmp);
+                    self.version_v1 = ::std::option::Option::Some(tmp);
+                },
+                4 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    }
+                    let tmp = is.read_uint64()?;
+                    self.version_v2 = ::std::option::Option::Some(tmp);
                 },
                 2 => {
                     if wire_type != ::protobuf::wire_format::WireTypeVarint {
@@ -11334,9 +11400,12 @@ impl ::protobuf::Message for SuggestConfig {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u32 {
         let mut my_size = 0;
-        if let Some(v) = self.version {
+        if let Some(v) = self.version_v1 {
             my_size += ::protobuf::rt::value_size(1, v, ::protobuf::wire_format::WireTypeVarint);
         }
+        if let Some(v) = self.version_v2 {
+            my_size += ::protobuf::rt::value_size(4, v, ::protobuf::wire_format::WireTypeVarint);
+        }
         if let Some(v) = self.positional {
             my_size += 2;
         }
@@ -11349,9 +11418,12 @@ impl ::protobuf::Message for SuggestConfig {
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::ProtobufResult<()> {
-        if let Some(v) = self.version {
+        if let Some(v) = self.version_v1 {
             os.write_uint32(1, v)?;
         }
+        if let Some(v) = self.version_v2 {
+            os.write_uint64(4, v)?;
+        }
         if let Some(v) = self.positional {
             os.write_bool(2, v)?;
         }
@@ -11397,9 +11469,14 @@ impl ::protobuf::Message for SuggestConfig {
         descriptor.get(|| {
             let mut fields = ::std::vec::Vec::new();
             fields.push(::protobuf::reflect::accessor::make_option_accessor::<_, ::protobuf::types::ProtobufTypeUint32>(
-                "version",
-                |m: &SuggestConfig| { &m.version },
-                |m: &mut SuggestConfig| { &mut m.version },
+                "version_v1",
+                |m: &SuggestConfig| { &m.version_v1 },
+                |m: &mut SuggestConfig| { &mut m.version_v1 },
+            ));
+            fields.push(::protobuf::reflect::accessor::make_option_accessor::<_, ::protobuf::types::ProtobufTypeUint64>(
+                "version_v2",
+                |m: &SuggestConfig| { &m.version_v2 },
+                |m: &mut SuggestConfig| { &mut m.version_v2 },
             ));
             fields.push(::protobuf::reflect::accessor::make_option_accessor::<_, ::protobuf::types::ProtobufTypeBool>(
                 "positional",
@@ -11427,7 +11504,8 @@ impl ::protobuf::Message for SuggestConfig {
 
 impl ::protobuf::Clear for SuggestConfig {
     fn clear(&mut self) {
-        self.version = ::std::option::Option::None;
+        self.version_v1 = ::std::option::Option::None;
+        self.version_v2 = ::std::option::Option::None;
         self.positional = ::std::option::Option::None;
         self.push_to_talk = ::std::option::Option::None;
         self.unknown_fields.clear();
@@ -11446,1236 +11524,490 @@ impl ::protobuf::reflect::ProtobufValue for SuggestConfig {
     }
 }
 
+#[derive(PartialEq,Clone,Default)]
+pub struct PluginDataTransmission {
+    // message fields
+    senderSession: ::std::option::Option<u32>,
+    pub receiverSessions: ::std::vec::Vec<u32>,
+    data: ::protobuf::SingularField<::std::vec::Vec<u8>>,
+    dataID: ::protobuf::SingularField<::std::string::String>,
+    // special fields
+    pub unknown_fields: ::protobuf::UnknownFields,
+    pub cached_size: ::protobuf::CachedSize,
+}
+
+impl<'a> ::std::default::Default for &'a PluginDataTransmission {
+    fn default() -> &'a PluginDataTransmission {
+        <PluginDataTransmission as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl PluginDataTransmission {
+    pub fn new() -> PluginDataTransmission {
+        ::std::default::Default::default()
+    }
+
+    // optional uint32 senderSession = 1;
+
+
+    pub fn get_senderSession(&self) -> u32 {
+        self.senderSession.unwrap_or(0)
+    }
+    pub fn clear_senderSession(&mut self) {
+        self.senderSession = ::std::option::Option::None;
+    }
+
+    pub fn has_senderSession(&self) -> bool {
+        self.senderSession.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_senderSession(&mut self, v: u32) {
+        self.senderSession = ::std::option::Option::Some(v);
+    }
+
+    // repeated uint32 receiverSessions = 2;
+
+
+    pub fn get_receiverSessions(&self) -> &[u32] {
+        &self.receiverSessions
+    }
+    pub fn clear_receiverSessions(&mut self) {
+        self.receiverSessions.clear();
+    }
+
+    // Param is passed by value, moved
+    pub fn set_receiverSessions(&mut self, v: ::std::vec::Vec<u32>) {
+        self.receiverSessions = v;
+    }
+
+    // Mutable pointer to the field.
+    pub fn mut_receiverSessions(&mut self) -> &mut ::std::vec::Vec<u32> {
+        &mut self.receiverSessions
+    }
+
+    // Take field
+    pub fn take_receiverSessions(&mut self) -> ::std::vec::Vec<u32> {
+        ::std::mem::replace(&mut self.receiverSessions, ::std::vec::Vec::new())
+    }
+
+    // optional bytes data = 3;
+
+
+    pub fn get_data(&self) -> &[u8] {
+        match self.data.as_ref() {
+            Some(v) => &v,
+            None => &[],
+        }
+    }
+    pub fn clear_data(&mut self) {
+        self.data.clear();
+    }
+
+    pub fn has_data(&self) -> bool {
+        self.data.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_data(&mut self, v: ::std::vec::Vec<u8>) {
+        self.data = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_data(&mut self) -> &mut ::std::vec::Vec<u8> {
+        if self.data.is_none() {
+            self.data.set_default();
+        }
+        self.data.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_data(&mut self) -> ::std::vec::Vec<u8> {
+        self.data.take().unwrap_or_else(|| ::std::vec::Vec::new())
+    }
+
+    // optional string dataID = 4;
+
+
+    pub fn get_dataID(&self) -> &str {
+        match self.dataID.as_ref() {
+            Some(v) => &v,
+            None => "",
+        }
+    }
+    pub fn clear_dataID(&mut self) {
+        self.dataID.clear();
+    }
+
+    pub fn has_dataID(&self) -> bool {
+        self.dataID.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_dataID(&mut self, v: ::std::string::String) {
+        self.dataID = ::protobuf::SingularField::some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_dataID(&mut self) -> &mut ::std::string::String {
+        if self.dataID.is_none() {
+            self.dataID.set_default();
+        }
+        self.dataID.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_dataID(&mut self) -> ::std::string::String {
+        self.dataID.take().unwrap_or_else(|| ::std::string::String::new())
+    }
+}
+
+impl ::protobuf::Message for PluginDataTransmission {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::ProtobufResult<()> {
+        while !is.eof()? {
+            let (field_number, wire_type) = is.read_tag_unpack()?;
+            match field_number {
+                1 => {
+                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
+                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
+                    }
+                    let tmp = is.read_uint32()?;
+                    self.senderSession = ::std::option::Option::Some(tmp);
+                },
+                2 => {
+                    ::protobuf::rt::read_repeated_uint32_into(wire_type, is, &mut self.receiverSessions)?;
+                },
+                3 => {
+                    ::protobuf::rt::read_singular_bytes_into(wire_type, is, &mut self.data)?;
+                },
+                4 => {
+                    ::protobuf::rt::read_singular_string_into(wire_type, is, &mut self.dataID)?;
+                },
+                _ => {
+                    ::protobuf::rt::read_unknown_or_skip_group(field_number, wire_type, is, self.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u32 {
+        let mut my_size = 0;
+        if let Some(v) = self.senderSession {
+            my_size += ::protobuf::rt::value_size(1, v, ::protobuf::wire_format::WireTypeVarint);
+        }
+        if !self.receiverSessions.is_empty() {
+            my_size += ::protobuf::rt::vec_packed_varint_size(2, &self.receiverSessions);
+        }
+        if let Some(ref v) = self.data.as_ref() {
+            my_size += ::protobuf::rt::bytes_size(3, &v);
+        }
+        if let Some(ref v) = self.dataID.as_ref() {
+            my_size += ::protobuf::rt::string_size(4, &v);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.get_unknown_fields());
+        self.cached_size.set(my_size);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::ProtobufResult<()> {
+        if let Some(v) = self.senderSession {
+            os.write_uint32(1, v)?;
+        }
+        if !self.receiverSessions.is_empty() {
+            os.write_tag(2, ::protobuf::wire_format::WireTypeLengthDelimited)?;
+            // TODO: Data size is computed again, it should be cached
+            os.write_raw_varint32(::protobuf::rt::vec_packed_varint_data_size(&self.receiverSessions))?;
+            for v in &self.receiverSessions {
+                os.write_uint32_no_tag(*v)?;
+            };
+        }
+        if let Some(ref v) = self.data.as_ref() {
+            os.write_bytes(3, &v)?;
+        }
+        if let Some(ref v) = self.dataID.as_ref() {
+            os.write_string(4, &v)?;
+        }
+        os.write_unknown_fields(self.get_unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn get_cached_size(&self) -> u32 {
+        self.cached_size.get()
+    }
+
+    fn get_unknown_fields(&self) -> &::protobuf::UnknownFields {
+        &self.unknown_fields
+    }
+
+    fn mut_unknown_fields(&mut self) -> &mut ::protobuf::UnknownFields {
+        &mut self.unknown_fields
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self as &dyn (::std::any::Any)
+    }
+    fn as_any_mut(&mut self) -> &mut dyn (::std::any::Any) {
+        self as &mut dyn (::std::any::Any)
+    }
+    fn into_any(self: ::std::boxed::Box<Self>) -> ::std::boxed::Box<dyn (::std::any::Any)> {
+        self
+    }
+
+    fn descriptor(&self) -> &'static ::protobuf::reflect::MessageDescriptor {
+        Self::descriptor_static()
+    }
+
+    fn new() -> PluginDataTransmission {
+        PluginDataTransmission::new()
+    }
+
+    fn descriptor_static() -> &'static ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::LazyV2<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::LazyV2::INIT;
+        descriptor.get(|| {
+            let mut fields = ::std::vec::Vec::new();
+            fields.push(::protobuf::reflect::accessor::make_option_accessor::<_, ::protobuf::types::ProtobufTypeUint32>(
+                "senderSession",
+                |m: &PluginDataTransmission| { &m.senderSession },
+                |m: &mut PluginDataTransmission| { &mut m.senderSession },
+            ));
+            fields.push(::protobuf::reflect::accessor::make_vec_accessor::<_, ::protobuf::types::ProtobufTypeUint32>(
+                "receiverSessions",
+                |m: &PluginDataTransmission| { &m.receiverSessions },
+                |m: &mut PluginDataTransmission| { &mut m.receiverSessions },
+            ));
+            fields.push(::protobuf::reflect::accessor::make_singular_field_accessor::<_, ::protobuf::types::ProtobufTypeBytes>(
+                "data",
+                |m: &PluginDataTransmission| { &m.data },
+                |m: &mut PluginDataTransmission| { &mut m.data },
+            ));
+            fields.push(::protobuf::reflect::accessor::make_singular_field_accessor::<_, ::protobuf::types::ProtobufTypeString>(
+                "dataID",
+                |m: &PluginDataTransmission| { &m.dataID },
+                |m: &mut PluginDataTransmission| { &mut m.dataID },
+            ));
+            ::protobuf::reflect::MessageDescriptor::new_pb_name::<PluginDataTransmission>(
+                "PluginDataTransmission",
+                fields,
+                file_descriptor_proto()
+            )
+        })
+    }
+
+    fn default_instance() -> &'static PluginDataTransmission {
+        static instance: ::protobuf::rt::LazyV2<PluginDataTransmission> = ::protobuf::rt::LazyV2::INIT;
+        instance.get(PluginDataTransmission::new)
+    }
+}
+
+impl ::protobuf::Clear for PluginDataTransmission {
+    fn clear(&mut self) {
+        self.senderSession = ::std::option::Option::None;
+        self.receiverSessions.clear();
+        self.data.clear();
+        self.dataID.clear();
+        self.unknown_fields.clear();
+    }
+}
+
+impl ::std::fmt::Debug for PluginDataTransmission {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for PluginDataTransmission {
+    fn as_ref(&self) -> ::protobuf::reflect::ReflectValueRef {
+        ::protobuf::reflect::ReflectValueRef::Message(self)
+    }
+}
+
 static file_descriptor_proto_data: &'static [u8] = b"\
-    \n\x16src/proto/mumble.proto\x12\x0bMumbleProto\"l\n\x07Version\x12\x18\
-    \n\x07version\x18\x01\x20\x01(\rR\x07version\x12\x18\n\x07release\x18\
-    \x02\x20\x01(\tR\x07release\x12\x0e\n\x02os\x18\x03\x20\x01(\tR\x02os\
-    \x12\x1d\n\nos_version\x18\x04\x20\x01(\tR\tosVersion\"#\n\tUDPTunnel\
-    \x12\x16\n\x06packet\x18\x01\x20\x02(\x0cR\x06packet\"\x9e\x01\n\x0cAuth\
-    enticate\x12\x1a\n\x08username\x18\x01\x20\x01(\tR\x08username\x12\x1a\n\
-    \x08password\x18\x02\x20\x01(\tR\x08password\x12\x16\n\x06tokens\x18\x03\
-    \x20\x03(\tR\x06tokens\x12#\n\rcelt_versions\x18\x04\x20\x03(\x05R\x0cce\
-    ltVersions\x12\x19\n\x04opus\x18\x05\x20\x01(\x08:\x05falseR\x04opus\"\
-    \xc2\x02\n\x04Ping\x12\x1c\n\ttimestamp\x18\x01\x20\x01(\x04R\ttimestamp\
-    \x12\x12\n\x04good\x18\x02\x20\x01(\rR\x04good\x12\x12\n\x04late\x18\x03\
-    \x20\x01(\rR\x04late\x12\x12\n\x04lost\x18\x04\x20\x01(\rR\x04lost\x12\
-    \x16\n\x06resync\x18\x05\x20\x01(\rR\x06resync\x12\x1f\n\x0budp_packets\
-    \x18\x06\x20\x01(\rR\nudpPackets\x12\x1f\n\x0btcp_packets\x18\x07\x20\
-    \x01(\rR\ntcpPackets\x12\x20\n\x0cudp_ping_avg\x18\x08\x20\x01(\x02R\nud\
-    pPingAvg\x12\x20\n\x0cudp_ping_var\x18\t\x20\x01(\x02R\nudpPingVar\x12\
-    \x20\n\x0ctcp_ping_avg\x18\n\x20\x01(\x02R\ntcpPingAvg\x12\x20\n\x0ctcp_\
-    ping_var\x18\x0b\x20\x01(\x02R\ntcpPingVar\"\x85\x02\n\x06Reject\x122\n\
-    \x04type\x18\x01\x20\x01(\x0e2\x1e.MumbleProto.Reject.RejectTypeR\x04typ\
-    e\x12\x16\n\x06reason\x18\x02\x20\x01(\tR\x06reason\"\xae\x01\n\nRejectT\
-    ype\x12\x08\n\x04None\x10\0\x12\x10\n\x0cWrongVersion\x10\x01\x12\x13\n\
-    \x0fInvalidUsername\x10\x02\x12\x0f\n\x0bWrongUserPW\x10\x03\x12\x11\n\r\
-    WrongServerPW\x10\x04\x12\x11\n\rUsernameInUse\x10\x05\x12\x0e\n\nServer\
-    Full\x10\x06\x12\x11\n\rNoCertificate\x10\x07\x12\x15\n\x11Authenticator\
-    Fail\x10\x08\"\x90\x01\n\nServerSync\x12\x18\n\x07session\x18\x01\x20\
-    \x01(\rR\x07session\x12#\n\rmax_bandwidth\x18\x02\x20\x01(\rR\x0cmaxBand\
-    width\x12!\n\x0cwelcome_text\x18\x03\x20\x01(\tR\x0bwelcomeText\x12\x20\
-    \n\x0bpermissions\x18\x04\x20\x01(\x04R\x0bpermissions\".\n\rChannelRemo\
-    ve\x12\x1d\n\nchannel_id\x18\x01\x20\x02(\rR\tchannelId\"\xaa\x03\n\x0cC\
-    hannelState\x12\x1d\n\nchannel_id\x18\x01\x20\x01(\rR\tchannelId\x12\x16\
-    \n\x06parent\x18\x02\x20\x01(\rR\x06parent\x12\x12\n\x04name\x18\x03\x20\
-    \x01(\tR\x04name\x12\x14\n\x05links\x18\x04\x20\x03(\rR\x05links\x12\x20\
-    \n\x0bdescription\x18\x05\x20\x01(\tR\x0bdescription\x12\x1b\n\tlinks_ad\
-    d\x18\x06\x20\x03(\rR\x08linksAdd\x12!\n\x0clinks_remove\x18\x07\x20\x03\
-    (\rR\x0blinksRemove\x12#\n\ttemporary\x18\x08\x20\x01(\x08:\x05falseR\tt\
-    emporary\x12\x1d\n\x08position\x18\t\x20\x01(\x05:\x010R\x08position\x12\
-    )\n\x10description_hash\x18\n\x20\x01(\x0cR\x0fdescriptionHash\x12\x1b\n\
-    \tmax_users\x18\x0b\x20\x01(\rR\x08maxUsers\x12.\n\x13is_enter_restricte\
-    d\x18\x0c\x20\x01(\x08R\x11isEnterRestricted\x12\x1b\n\tcan_enter\x18\r\
-    \x20\x01(\x08R\x08canEnter\"f\n\nUserRemove\x12\x18\n\x07session\x18\x01\
-    \x20\x02(\rR\x07session\x12\x14\n\x05actor\x18\x02\x20\x01(\rR\x05actor\
-    \x12\x16\n\x06reason\x18\x03\x20\x01(\tR\x06reason\x12\x10\n\x03ban\x18\
-    \x04\x20\x01(\x08R\x03ban\"\xd2\x05\n\tUserState\x12\x18\n\x07session\
-    \x18\x01\x20\x01(\rR\x07session\x12\x14\n\x05actor\x18\x02\x20\x01(\rR\
-    \x05actor\x12\x12\n\x04name\x18\x03\x20\x01(\tR\x04name\x12\x17\n\x07use\
-    r_id\x18\x04\x20\x01(\rR\x06userId\x12\x1d\n\nchannel_id\x18\x05\x20\x01\
-    (\rR\tchannelId\x12\x12\n\x04mute\x18\x06\x20\x01(\x08R\x04mute\x12\x12\
-    \n\x04deaf\x18\x07\x20\x01(\x08R\x04deaf\x12\x1a\n\x08suppress\x18\x08\
-    \x20\x01(\x08R\x08suppress\x12\x1b\n\tself_mute\x18\t\x20\x01(\x08R\x08s\
-    elfMute\x12\x1b\n\tself_deaf\x18\n\x20\x01(\x08R\x08selfDeaf\x12\x18\n\
-    \x07texture\x18\x0b\x20\x01(\x0cR\x07texture\x12%\n\x0eplugin_context\
-    \x18\x0c\x20\x01(\x0cR\rpluginContext\x12'\n\x0fplugin_identity\x18\r\
-    \x20\x01(\tR\x0epluginIdentity\x12\x18\n\x07comment\x18\x0e\x20\x01(\tR\
-    \x07comment\x12\x12\n\x04hash\x18\x0f\x20\x01(\tR\x04hash\x12!\n\x0ccomm\
-    ent_hash\x18\x10\x20\x01(\x0cR\x0bcommentHash\x12!\n\x0ctexture_hash\x18\
-    \x11\x20\x01(\x0cR\x0btextureHash\x12)\n\x10priority_speaker\x18\x12\x20\
-    \x01(\x08R\x0fprioritySpeaker\x12\x1c\n\trecording\x18\x13\x20\x01(\x08R\
-    \trecording\x126\n\x17temporary_access_tokens\x18\x14\x20\x03(\tR\x15tem\
-    poraryAccessTokens\x122\n\x15listening_channel_add\x18\x15\x20\x03(\rR\
-    \x13listeningChannelAdd\x128\n\x18listening_channel_remove\x18\x16\x20\
-    \x03(\rR\x16listeningChannelRemove\"\x86\x02\n\x07BanList\x121\n\x04bans\
-    \x18\x01\x20\x03(\x0b2\x1d.MumbleProto.BanList.BanEntryR\x04bans\x12\x1b\
-    \n\x05query\x18\x02\x20\x01(\x08:\x05falseR\x05query\x1a\xaa\x01\n\x08Ba\
-    nEntry\x12\x18\n\x07address\x18\x01\x20\x02(\x0cR\x07address\x12\x12\n\
-    \x04mask\x18\x02\x20\x02(\rR\x04mask\x12\x12\n\x04name\x18\x03\x20\x01(\
-    \tR\x04name\x12\x12\n\x04hash\x18\x04\x20\x01(\tR\x04hash\x12\x16\n\x06r\
-    eason\x18\x05\x20\x01(\tR\x06reason\x12\x14\n\x05start\x18\x06\x20\x01(\
-    \tR\x05start\x12\x1a\n\x08duration\x18\x07\x20\x01(\rR\x08duration\"\x8f\
-    \x01\n\x0bTextMessage\x12\x14\n\x05actor\x18\x01\x20\x01(\rR\x05actor\
-    \x12\x18\n\x07session\x18\x02\x20\x03(\rR\x07session\x12\x1d\n\nchannel_\
-    id\x18\x03\x20\x03(\rR\tchannelId\x12\x17\n\x07tree_id\x18\x04\x20\x03(\
-    \rR\x06treeId\x12\x18\n\x07message\x18\x05\x20\x02(\tR\x07message\"\xdb\
-    \x03\n\x10PermissionDenied\x12\x1e\n\npermission\x18\x01\x20\x01(\rR\npe\
-    rmission\x12\x1d\n\nchannel_id\x18\x02\x20\x01(\rR\tchannelId\x12\x18\n\
-    \x07session\x18\x03\x20\x01(\rR\x07session\x12\x16\n\x06reason\x18\x04\
-    \x20\x01(\tR\x06reason\x12:\n\x04type\x18\x05\x20\x01(\x0e2&.MumbleProto\
-    .PermissionDenied.DenyTypeR\x04type\x12\x12\n\x04name\x18\x06\x20\x01(\t\
-    R\x04name\"\x85\x02\n\x08DenyType\x12\x08\n\x04Text\x10\0\x12\x0e\n\nPer\
-    mission\x10\x01\x12\r\n\tSuperUser\x10\x02\x12\x0f\n\x0bChannelName\x10\
-    \x03\x12\x0f\n\x0bTextTooLong\x10\x04\x12\x07\n\x03H9K\x10\x05\x12\x14\n\
-    \x10TemporaryChannel\x10\x06\x12\x16\n\x12MissingCertificate\x10\x07\x12\
-    \x0c\n\x08UserName\x10\x08\x12\x0f\n\x0bChannelFull\x10\t\x12\x10\n\x0cN\
-    estingLimit\x10\n\x12\x15\n\x11ChannelCountLimit\x10\x0b\x12\x18\n\x14Ch\
-    annelListenerLimit\x10\x0c\x12\x15\n\x11UserListenerLimit\x10\r\"\x84\
-    \x05\n\x03ACL\x12\x1d\n\nchannel_id\x18\x01\x20\x02(\rR\tchannelId\x12'\
-    \n\x0cinherit_acls\x18\x02\x20\x01(\x08:\x04trueR\x0binheritAcls\x122\n\
-    \x06groups\x18\x03\x20\x03(\x0b2\x1a.MumbleProto.ACL.ChanGroupR\x06group\
-    s\x12,\n\x04acls\x18\x04\x20\x03(\x0b2\x18.MumbleProto.ACL.ChanACLR\x04a\
-    cls\x12\x1b\n\x05query\x18\x05\x20\x01(\x08:\x05falseR\x05query\x1a\xe2\
-    \x01\n\tChanGroup\x12\x12\n\x04name\x18\x01\x20\x02(\tR\x04name\x12\"\n\
-    \tinherited\x18\x02\x20\x01(\x08:\x04trueR\tinherited\x12\x1e\n\x07inher\
-    it\x18\x03\x20\x01(\x08:\x04trueR\x07inherit\x12&\n\x0binheritable\x18\
-    \x04\x20\x01(\x08:\x04trueR\x0binheritable\x12\x10\n\x03add\x18\x05\x20\
-    \x03(\rR\x03add\x12\x16\n\x06remove\x18\x06\x20\x03(\rR\x06remove\x12+\n\
-    \x11inherited_members\x18\x07\x20\x03(\rR\x10inheritedMembers\x1a\xd0\
-    \x01\n\x07ChanACL\x12#\n\napply_here\x18\x01\x20\x01(\x08:\x04trueR\tapp\
-    lyHere\x12#\n\napply_subs\x18\x02\x20\x01(\x08:\x04trueR\tapplySubs\x12\
-    \"\n\tinherited\x18\x03\x20\x01(\x08:\x04trueR\tinherited\x12\x17\n\x07u\
-    ser_id\x18\x04\x20\x01(\rR\x06userId\x12\x14\n\x05group\x18\x05\x20\x01(\
-    \tR\x05group\x12\x14\n\x05grant\x18\x06\x20\x01(\rR\x05grant\x12\x12\n\
-    \x04deny\x18\x07\x20\x01(\rR\x04deny\"4\n\nQueryUsers\x12\x10\n\x03ids\
-    \x18\x01\x20\x03(\rR\x03ids\x12\x14\n\x05names\x18\x02\x20\x03(\tR\x05na\
-    mes\"d\n\nCryptSetup\x12\x10\n\x03key\x18\x01\x20\x01(\x0cR\x03key\x12!\
-    \n\x0cclient_nonce\x18\x02\x20\x01(\x0cR\x0bclientNonce\x12!\n\x0cserver\
-    _nonce\x18\x03\x20\x01(\x0cR\x0bserverNonce\"\xf5\x01\n\x13ContextAction\
-    Modify\x12\x16\n\x06action\x18\x01\x20\x02(\tR\x06action\x12\x12\n\x04te\
-    xt\x18\x02\x20\x01(\tR\x04text\x12\x18\n\x07context\x18\x03\x20\x01(\rR\
-    \x07context\x12H\n\toperation\x18\x04\x20\x01(\x0e2*.MumbleProto.Context\
-    ActionModify.OperationR\toperation\",\n\x07Context\x12\n\n\x06Server\x10\
-    \x01\x12\x0b\n\x07Channel\x10\x02\x12\x08\n\x04User\x10\x04\"\x20\n\tOpe\
-    ration\x12\x07\n\x03Add\x10\0\x12\n\n\x06Remove\x10\x01\"`\n\rContextAct\
-    ion\x12\x18\n\x07session\x18\x01\x20\x01(\rR\x07session\x12\x1d\n\nchann\
-    el_id\x18\x02\x20\x01(\rR\tchannelId\x12\x16\n\x06action\x18\x03\x20\x02\
-    (\tR\x06action\"\xb1\x01\n\x08UserList\x120\n\x05users\x18\x01\x20\x03(\
-    \x0b2\x1a.MumbleProto.UserList.UserR\x05users\x1as\n\x04User\x12\x17\n\
-    \x07user_id\x18\x01\x20\x02(\rR\x06userId\x12\x12\n\x04name\x18\x02\x20\
-    \x01(\tR\x04name\x12\x1b\n\tlast_seen\x18\x03\x20\x01(\tR\x08lastSeen\
-    \x12!\n\x0clast_channel\x18\x04\x20\x01(\rR\x0blastChannel\"\xf2\x01\n\
-    \x0bVoiceTarget\x12\x0e\n\x02id\x18\x01\x20\x01(\rR\x02id\x129\n\x07targ\
-    ets\x18\x02\x20\x03(\x0b2\x1f.MumbleProto.VoiceTarget.TargetR\x07targets\
-    \x1a\x97\x01\n\x06Target\x12\x18\n\x07session\x18\x01\x20\x03(\rR\x07ses\
-    sion\x12\x1d\n\nchannel_id\x18\x02\x20\x01(\rR\tchannelId\x12\x14\n\x05g\
-    roup\x18\x03\x20\x01(\tR\x05group\x12\x1b\n\x05links\x18\x04\x20\x01(\
-    \x08:\x05falseR\x05links\x12!\n\x08children\x18\x05\x20\x01(\x08:\x05fal\
-    seR\x08children\"o\n\x0fPermissionQuery\x12\x1d\n\nchannel_id\x18\x01\
-    \x20\x01(\rR\tchannelId\x12\x20\n\x0bpermissions\x18\x02\x20\x01(\rR\x0b\
-    permissions\x12\x1b\n\x05flush\x18\x03\x20\x01(\x08:\x05falseR\x05flush\
-    \"|\n\x0cCodecVersion\x12\x14\n\x05alpha\x18\x01\x20\x02(\x05R\x05alpha\
-    \x12\x12\n\x04beta\x18\x02\x20\x02(\x05R\x04beta\x12'\n\x0cprefer_alpha\
-    \x18\x03\x20\x02(\x08:\x04trueR\x0bpreferAlpha\x12\x19\n\x04opus\x18\x04\
-    \x20\x01(\x08:\x05falseR\x04opus\"\xae\x06\n\tUserStats\x12\x18\n\x07ses\
-    sion\x18\x01\x20\x01(\rR\x07session\x12$\n\nstats_only\x18\x02\x20\x01(\
-    \x08:\x05falseR\tstatsOnly\x12\"\n\x0ccertificates\x18\x03\x20\x03(\x0cR\
-    \x0ccertificates\x12=\n\x0bfrom_client\x18\x04\x20\x01(\x0b2\x1c.MumbleP\
-    roto.UserStats.StatsR\nfromClient\x12=\n\x0bfrom_server\x18\x05\x20\x01(\
-    \x0b2\x1c.MumbleProto.UserStats.StatsR\nfromServer\x12\x1f\n\x0budp_pack\
-    ets\x18\x06\x20\x01(\rR\nudpPackets\x12\x1f\n\x0btcp_packets\x18\x07\x20\
-    \x01(\rR\ntcpPackets\x12\x20\n\x0cudp_ping_avg\x18\x08\x20\x01(\x02R\nud\
-    pPingAvg\x12\x20\n\x0cudp_ping_var\x18\t\x20\x01(\x02R\nudpPingVar\x12\
-    \x20\n\x0ctcp_ping_avg\x18\n\x20\x01(\x02R\ntcpPingAvg\x12\x20\n\x0ctcp_\
-    ping_var\x18\x0b\x20\x01(\x02R\ntcpPingVar\x12.\n\x07version\x18\x0c\x20\
-    \x01(\x0b2\x14.MumbleProto.VersionR\x07version\x12#\n\rcelt_versions\x18\
-    \r\x20\x03(\x05R\x0cceltVersions\x12\x18\n\x07address\x18\x0e\x20\x01(\
-    \x0cR\x07address\x12\x1c\n\tbandwidth\x18\x0f\x20\x01(\rR\tbandwidth\x12\
-    \x1e\n\nonlinesecs\x18\x10\x20\x01(\rR\nonlinesecs\x12\x1a\n\x08idlesecs\
-    \x18\x11\x20\x01(\rR\x08idlesecs\x124\n\x12strong_certificate\x18\x12\
-    \x20\x01(\x08:\x05falseR\x11strongCertificate\x12\x19\n\x04opus\x18\x13\
-    \x20\x01(\x08:\x05falseR\x04opus\x1a[\n\x05Stats\x12\x12\n\x04good\x18\
-    \x01\x20\x01(\rR\x04good\x12\x12\n\x04late\x18\x02\x20\x01(\rR\x04late\
-    \x12\x12\n\x04lost\x18\x03\x20\x01(\rR\x04lost\x12\x16\n\x06resync\x18\
-    \x04\x20\x01(\rR\x06resync\"\x90\x01\n\x0bRequestBlob\x12'\n\x0fsession_\
-    texture\x18\x01\x20\x03(\rR\x0esessionTexture\x12'\n\x0fsession_comment\
-    \x18\x02\x20\x03(\rR\x0esessionComment\x12/\n\x13channel_description\x18\
-    \x03\x20\x03(\rR\x12channelDescription\"\xeb\x01\n\x0cServerConfig\x12#\
-    \n\rmax_bandwidth\x18\x01\x20\x01(\rR\x0cmaxBandwidth\x12!\n\x0cwelcome_\
-    text\x18\x02\x20\x01(\tR\x0bwelcomeText\x12\x1d\n\nallow_html\x18\x03\
-    \x20\x01(\x08R\tallowHtml\x12%\n\x0emessage_length\x18\x04\x20\x01(\rR\r\
-    messageLength\x120\n\x14image_message_length\x18\x05\x20\x01(\rR\x12imag\
-    eMessageLength\x12\x1b\n\tmax_users\x18\x06\x20\x01(\rR\x08maxUsers\"k\n\
-    \rSuggestConfig\x12\x18\n\x07version\x18\x01\x20\x01(\rR\x07version\x12\
-    \x1e\n\npositional\x18\x02\x20\x01(\x08R\npositional\x12\x20\n\x0cpush_t\
-    o_talk\x18\x03\x20\x01(\x08R\npushToTalkB\x02H\x01J\x91\xe5\x01\n\x07\
-    \x12\x05\x05\0\xc9\x04\x01\n\xff\x01\n\x01\x0c\x12\x03\x05\0\x122\xf4\
-    \x01\x20Copyright\x202005-2020\x20The\x20Mumble\x20Developers.\x20All\
-    \x20rights\x20reserved.\n\x20Use\x20of\x20this\x20source\x20code\x20is\
-    \x20governed\x20by\x20a\x20BSD-style\x20license\n\x20that\x20can\x20be\
-    \x20found\x20in\x20the\x20LICENSE\x20file\x20at\x20the\x20root\x20of\x20\
-    the\n\x20Mumble\x20source\x20tree\x20or\x20at\x20<https://www.mumble.inf\
-    o/LICENSE>.\n\n\x08\n\x01\x02\x12\x03\x07\0\x14\n\x08\n\x01\x08\x12\x03\
-    \t\0\x1c\n\t\n\x02\x08\t\x12\x03\t\0\x1c\n\n\n\x02\x04\0\x12\x04\x0b\0\
-    \x14\x01\n\n\n\x03\x04\0\x01\x12\x03\x0b\x08\x0f\nJ\n\x04\x04\0\x02\0\
-    \x12\x03\r\x04\x20\x1a=\x202-byte\x20Major,\x201-byte\x20Minor\x20and\
-    \x201-byte\x20Patch\x20version\x20number.\n\n\x0c\n\x05\x04\0\x02\0\x04\
-    \x12\x03\r\x04\x0c\n\x0c\n\x05\x04\0\x02\0\x05\x12\x03\r\r\x13\n\x0c\n\
-    \x05\x04\0\x02\0\x01\x12\x03\r\x14\x1b\n\x0c\n\x05\x04\0\x02\0\x03\x12\
-    \x03\r\x1e\x1f\n#\n\x04\x04\0\x02\x01\x12\x03\x0f\x04\x20\x1a\x16\x20Cli\
-    ent\x20release\x20name.\n\n\x0c\n\x05\x04\0\x02\x01\x04\x12\x03\x0f\x04\
-    \x0c\n\x0c\n\x05\x04\0\x02\x01\x05\x12\x03\x0f\r\x13\n\x0c\n\x05\x04\0\
-    \x02\x01\x01\x12\x03\x0f\x14\x1b\n\x0c\n\x05\x04\0\x02\x01\x03\x12\x03\
-    \x0f\x1e\x1f\n\x1e\n\x04\x04\0\x02\x02\x12\x03\x11\x04\x1b\x1a\x11\x20Cl\
-    ient\x20OS\x20name.\n\n\x0c\n\x05\x04\0\x02\x02\x04\x12\x03\x11\x04\x0c\
-    \n\x0c\n\x05\x04\0\x02\x02\x05\x12\x03\x11\r\x13\n\x0c\n\x05\x04\0\x02\
-    \x02\x01\x12\x03\x11\x14\x16\n\x0c\n\x05\x04\0\x02\x02\x03\x12\x03\x11\
-    \x19\x1a\n!\n\x04\x04\0\x02\x03\x12\x03\x13\x04#\x1a\x14\x20Client\x20OS\
-    \x20version.\n\n\x0c\n\x05\x04\0\x02\x03\x04\x12\x03\x13\x04\x0c\n\x0c\n\
-    \x05\x04\0\x02\x03\x05\x12\x03\x13\r\x13\n\x0c\n\x05\x04\0\x02\x03\x01\
-    \x12\x03\x13\x14\x1e\n\x0c\n\x05\x04\0\x02\x03\x03\x12\x03\x13!\"\n?\n\
-    \x02\x04\x01\x12\x04\x17\0\x1a\x01\x1a3\x20Not\x20used.\x20Not\x20even\
-    \x20for\x20tunneling\x20UDP\x20through\x20TCP.\n\n\n\n\x03\x04\x01\x01\
-    \x12\x03\x17\x08\x11\n\x18\n\x04\x04\x01\x02\0\x12\x03\x19\x04\x1e\x1a\
-    \x0b\x20Not\x20used.\n\n\x0c\n\x05\x04\x01\x02\0\x04\x12\x03\x19\x04\x0c\
-    \n\x0c\n\x05\x04\x01\x02\0\x05\x12\x03\x19\r\x12\n\x0c\n\x05\x04\x01\x02\
-    \0\x01\x12\x03\x19\x13\x19\n\x0c\n\x05\x04\x01\x02\0\x03\x12\x03\x19\x1c\
-    \x1d\nV\n\x02\x04\x02\x12\x04\x1d\0'\x01\x1aJ\x20Used\x20by\x20the\x20cl\
-    ient\x20to\x20send\x20the\x20authentication\x20credentials\x20to\x20the\
-    \x20server.\n\n\n\n\x03\x04\x02\x01\x12\x03\x1d\x08\x14\n&\n\x04\x04\x02\
-    \x02\0\x12\x03\x1f\x04!\x1a\x19\x20UTF-8\x20encoded\x20username.\n\n\x0c\
-    \n\x05\x04\x02\x02\0\x04\x12\x03\x1f\x04\x0c\n\x0c\n\x05\x04\x02\x02\0\
-    \x05\x12\x03\x1f\r\x13\n\x0c\n\x05\x04\x02\x02\0\x01\x12\x03\x1f\x14\x1c\
-    \n\x0c\n\x05\x04\x02\x02\0\x03\x12\x03\x1f\x1f\x20\n'\n\x04\x04\x02\x02\
-    \x01\x12\x03!\x04!\x1a\x1a\x20Server\x20or\x20user\x20password.\n\n\x0c\
-    \n\x05\x04\x02\x02\x01\x04\x12\x03!\x04\x0c\n\x0c\n\x05\x04\x02\x02\x01\
-    \x05\x12\x03!\r\x13\n\x0c\n\x05\x04\x02\x02\x01\x01\x12\x03!\x14\x1c\n\
-    \x0c\n\x05\x04\x02\x02\x01\x03\x12\x03!\x1f\x20\n>\n\x04\x04\x02\x02\x02\
-    \x12\x03#\x04\x1f\x1a1\x20Additional\x20access\x20tokens\x20for\x20serve\
-    r\x20ACL\x20groups.\n\n\x0c\n\x05\x04\x02\x02\x02\x04\x12\x03#\x04\x0c\n\
-    \x0c\n\x05\x04\x02\x02\x02\x05\x12\x03#\r\x13\n\x0c\n\x05\x04\x02\x02\
-    \x02\x01\x12\x03#\x14\x1a\n\x0c\n\x05\x04\x02\x02\x02\x03\x12\x03#\x1d\
-    \x1e\nR\n\x04\x04\x02\x02\x03\x12\x03%\x04%\x1aE\x20A\x20list\x20of\x20C\
-    ELT\x20bitstream\x20version\x20constants\x20supported\x20by\x20the\x20cl\
-    ient.\n\n\x0c\n\x05\x04\x02\x02\x03\x04\x12\x03%\x04\x0c\n\x0c\n\x05\x04\
-    \x02\x02\x03\x05\x12\x03%\r\x12\n\x0c\n\x05\x04\x02\x02\x03\x01\x12\x03%\
-    \x13\x20\n\x0c\n\x05\x04\x02\x02\x03\x03\x12\x03%#$\n\x0b\n\x04\x04\x02\
-    \x02\x04\x12\x03&\x04-\n\x0c\n\x05\x04\x02\x02\x04\x04\x12\x03&\x04\x0c\
-    \n\x0c\n\x05\x04\x02\x02\x04\x05\x12\x03&\r\x11\n\x0c\n\x05\x04\x02\x02\
-    \x04\x01\x12\x03&\x12\x16\n\x0c\n\x05\x04\x02\x02\x04\x03\x12\x03&\x19\
-    \x1a\n\x0c\n\x05\x04\x02\x02\x04\x08\x12\x03&\x1b,\n\x0c\n\x05\x04\x02\
-    \x02\x04\x07\x12\x03&&+\n\xe4\x01\n\x02\x04\x03\x12\x04,\0C\x01\x1a\xd7\
-    \x01\x20Sent\x20by\x20the\x20client\x20to\x20notify\x20the\x20server\x20\
-    that\x20the\x20client\x20is\x20still\x20alive.\n\x20Server\x20must\x20re\
-    ply\x20to\x20the\x20packet\x20with\x20the\x20same\x20timestamp\x20and\
-    \x20its\x20own\n\x20good/late/lost/resync\x20numbers.\x20None\x20of\x20t\
-    he\x20fields\x20is\x20strictly\x20required.\n\n\n\n\x03\x04\x03\x01\x12\
-    \x03,\x08\x0c\nE\n\x04\x04\x03\x02\0\x12\x03.\x04\"\x1a8\x20Client\x20ti\
-    mestamp.\x20Server\x20should\x20not\x20attempt\x20to\x20decode.\n\n\x0c\
-    \n\x05\x04\x03\x02\0\x04\x12\x03.\x04\x0c\n\x0c\n\x05\x04\x03\x02\0\x05\
-    \x12\x03.\r\x13\n\x0c\n\x05\x04\x03\x02\0\x01\x12\x03.\x14\x1d\n\x0c\n\
-    \x05\x04\x03\x02\0\x03\x12\x03.\x20!\n3\n\x04\x04\x03\x02\x01\x12\x030\
-    \x04\x1d\x1a&\x20The\x20amount\x20of\x20good\x20packets\x20received.\n\n\
-    \x0c\n\x05\x04\x03\x02\x01\x04\x12\x030\x04\x0c\n\x0c\n\x05\x04\x03\x02\
-    \x01\x05\x12\x030\r\x13\n\x0c\n\x05\x04\x03\x02\x01\x01\x12\x030\x14\x18\
-    \n\x0c\n\x05\x04\x03\x02\x01\x03\x12\x030\x1b\x1c\n3\n\x04\x04\x03\x02\
-    \x02\x12\x032\x04\x1d\x1a&\x20The\x20amount\x20of\x20late\x20packets\x20\
-    received.\n\n\x0c\n\x05\x04\x03\x02\x02\x04\x12\x032\x04\x0c\n\x0c\n\x05\
-    \x04\x03\x02\x02\x05\x12\x032\r\x13\n\x0c\n\x05\x04\x03\x02\x02\x01\x12\
-    \x032\x14\x18\n\x0c\n\x05\x04\x03\x02\x02\x03\x12\x032\x1b\x1c\n4\n\x04\
-    \x04\x03\x02\x03\x12\x034\x04\x1d\x1a'\x20The\x20amount\x20of\x20packets\
-    \x20never\x20received.\n\n\x0c\n\x05\x04\x03\x02\x03\x04\x12\x034\x04\
-    \x0c\n\x0c\n\x05\x04\x03\x02\x03\x05\x12\x034\r\x13\n\x0c\n\x05\x04\x03\
-    \x02\x03\x01\x12\x034\x14\x18\n\x0c\n\x05\x04\x03\x02\x03\x03\x12\x034\
-    \x1b\x1c\n+\n\x04\x04\x03\x02\x04\x12\x036\x04\x1f\x1a\x1e\x20The\x20amo\
-    unt\x20of\x20nonce\x20resyncs.\n\n\x0c\n\x05\x04\x03\x02\x04\x04\x12\x03\
-    6\x04\x0c\n\x0c\n\x05\x04\x03\x02\x04\x05\x12\x036\r\x13\n\x0c\n\x05\x04\
-    \x03\x02\x04\x01\x12\x036\x14\x1a\n\x0c\n\x05\x04\x03\x02\x04\x03\x12\
-    \x036\x1d\x1e\n8\n\x04\x04\x03\x02\x05\x12\x038\x04$\x1a+\x20The\x20tota\
-    l\x20amount\x20of\x20UDP\x20packets\x20received.\n\n\x0c\n\x05\x04\x03\
-    \x02\x05\x04\x12\x038\x04\x0c\n\x0c\n\x05\x04\x03\x02\x05\x05\x12\x038\r\
-    \x13\n\x0c\n\x05\x04\x03\x02\x05\x01\x12\x038\x14\x1f\n\x0c\n\x05\x04\
-    \x03\x02\x05\x03\x12\x038\"#\n8\n\x04\x04\x03\x02\x06\x12\x03:\x04$\x1a+\
-    \x20The\x20total\x20amount\x20of\x20TCP\x20packets\x20received.\n\n\x0c\
-    \n\x05\x04\x03\x02\x06\x04\x12\x03:\x04\x0c\n\x0c\n\x05\x04\x03\x02\x06\
-    \x05\x12\x03:\r\x13\n\x0c\n\x05\x04\x03\x02\x06\x01\x12\x03:\x14\x1f\n\
-    \x0c\n\x05\x04\x03\x02\x06\x03\x12\x03:\"#\n\x20\n\x04\x04\x03\x02\x07\
-    \x12\x03<\x04$\x1a\x13\x20UDP\x20ping\x20average.\n\n\x0c\n\x05\x04\x03\
-    \x02\x07\x04\x12\x03<\x04\x0c\n\x0c\n\x05\x04\x03\x02\x07\x05\x12\x03<\r\
-    \x12\n\x0c\n\x05\x04\x03\x02\x07\x01\x12\x03<\x13\x1f\n\x0c\n\x05\x04\
-    \x03\x02\x07\x03\x12\x03<\"#\n!\n\x04\x04\x03\x02\x08\x12\x03>\x04$\x1a\
-    \x14\x20UDP\x20ping\x20variance.\n\n\x0c\n\x05\x04\x03\x02\x08\x04\x12\
-    \x03>\x04\x0c\n\x0c\n\x05\x04\x03\x02\x08\x05\x12\x03>\r\x12\n\x0c\n\x05\
-    \x04\x03\x02\x08\x01\x12\x03>\x13\x1f\n\x0c\n\x05\x04\x03\x02\x08\x03\
-    \x12\x03>\"#\n\x20\n\x04\x04\x03\x02\t\x12\x03@\x04%\x1a\x13\x20TCP\x20p\
-    ing\x20average.\n\n\x0c\n\x05\x04\x03\x02\t\x04\x12\x03@\x04\x0c\n\x0c\n\
-    \x05\x04\x03\x02\t\x05\x12\x03@\r\x12\n\x0c\n\x05\x04\x03\x02\t\x01\x12\
-    \x03@\x13\x1f\n\x0c\n\x05\x04\x03\x02\t\x03\x12\x03@\"$\n!\n\x04\x04\x03\
-    \x02\n\x12\x03B\x04%\x1a\x14\x20TCP\x20ping\x20variance.\n\n\x0c\n\x05\
-    \x04\x03\x02\n\x04\x12\x03B\x04\x0c\n\x0c\n\x05\x04\x03\x02\n\x05\x12\
-    \x03B\r\x12\n\x0c\n\x05\x04\x03\x02\n\x01\x12\x03B\x13\x1f\n\x0c\n\x05\
-    \x04\x03\x02\n\x03\x12\x03B\"$\nE\n\x02\x04\x04\x12\x04F\0a\x01\x1a9\x20\
-    Sent\x20by\x20the\x20server\x20when\x20it\x20rejects\x20the\x20user\x20c\
-    onnection.\n\n\n\n\x03\x04\x04\x01\x12\x03F\x08\x0e\n\x0c\n\x04\x04\x04\
-    \x04\0\x12\x04G\x04\\\x05\n\x0c\n\x05\x04\x04\x04\0\x01\x12\x03G\t\x13\n\
-    a\n\x06\x04\x04\x04\0\x02\0\x12\x03J\x08\x11\x1aR\x20The\x20rejection\
-    \x20reason\x20is\x20unknown\x20(details\x20should\x20be\x20available\n\
-    \x20in\x20Reject.reason).\n\n\x0e\n\x07\x04\x04\x04\0\x02\0\x01\x12\x03J\
-    \x08\x0c\n\x0e\n\x07\x04\x04\x04\0\x02\0\x02\x12\x03J\x0f\x10\nN\n\x06\
-    \x04\x04\x04\0\x02\x01\x12\x03L\x08\x19\x1a?\x20The\x20client\x20attempt\
-    ed\x20to\x20connect\x20with\x20an\x20incompatible\x20version.\n\n\x0e\n\
-    \x07\x04\x04\x04\0\x02\x01\x01\x12\x03L\x08\x14\n\x0e\n\x07\x04\x04\x04\
-    \0\x02\x01\x02\x12\x03L\x17\x18\nB\n\x06\x04\x04\x04\0\x02\x02\x12\x03N\
-    \x08\x1c\x1a3\x20The\x20user\x20name\x20supplied\x20by\x20the\x20client\
-    \x20was\x20invalid.\n\n\x0e\n\x07\x04\x04\x04\0\x02\x02\x01\x12\x03N\x08\
-    \x17\n\x0e\n\x07\x04\x04\x04\0\x02\x02\x02\x12\x03N\x1a\x1b\nb\n\x06\x04\
-    \x04\x04\0\x02\x03\x12\x03Q\x08\x18\x1aS\x20The\x20client\x20attempted\
-    \x20to\x20authenticate\x20as\x20a\x20user\x20with\x20a\x20password\x20bu\
-    t\x20it\n\x20was\x20wrong.\n\n\x0e\n\x07\x04\x04\x04\0\x02\x03\x01\x12\
-    \x03Q\x08\x13\n\x0e\n\x07\x04\x04\x04\0\x02\x03\x02\x12\x03Q\x16\x17\nd\
-    \n\x06\x04\x04\x04\0\x02\x04\x12\x03T\x08\x1a\x1aU\x20The\x20client\x20a\
-    ttempted\x20to\x20connect\x20to\x20a\x20passworded\x20server\x20but\x20t\
-    he\x20password\n\x20was\x20wrong.\n\n\x0e\n\x07\x04\x04\x04\0\x02\x04\
-    \x01\x12\x03T\x08\x15\n\x0e\n\x07\x04\x04\x04\0\x02\x04\x02\x12\x03T\x18\
-    \x19\n5\n\x06\x04\x04\x04\0\x02\x05\x12\x03V\x08\x1a\x1a&\x20Supplied\
-    \x20username\x20is\x20already\x20in\x20use.\n\n\x0e\n\x07\x04\x04\x04\0\
-    \x02\x05\x01\x12\x03V\x08\x15\n\x0e\n\x07\x04\x04\x04\0\x02\x05\x02\x12\
-    \x03V\x18\x19\nG\n\x06\x04\x04\x04\0\x02\x06\x12\x03X\x08\x17\x1a8\x20Se\
-    rver\x20is\x20currently\x20full\x20and\x20cannot\x20accept\x20more\x20us\
-    ers.\n\n\x0e\n\x07\x04\x04\x04\0\x02\x06\x01\x12\x03X\x08\x12\n\x0e\n\
-    \x07\x04\x04\x04\0\x02\x06\x02\x12\x03X\x15\x16\nL\n\x06\x04\x04\x04\0\
-    \x02\x07\x12\x03Z\x08\x1a\x1a=\x20The\x20user\x20did\x20not\x20provide\
-    \x20a\x20certificate\x20but\x20one\x20is\x20required.\n\n\x0e\n\x07\x04\
-    \x04\x04\0\x02\x07\x01\x12\x03Z\x08\x15\n\x0e\n\x07\x04\x04\x04\0\x02\
-    \x07\x02\x12\x03Z\x18\x19\n\r\n\x06\x04\x04\x04\0\x02\x08\x12\x03[\x08\
-    \x1e\n\x0e\n\x07\x04\x04\x04\0\x02\x08\x01\x12\x03[\x08\x19\n\x0e\n\x07\
-    \x04\x04\x04\0\x02\x08\x02\x12\x03[\x1c\x1d\n\x1e\n\x04\x04\x04\x02\0\
-    \x12\x03^\x04!\x1a\x11\x20Rejection\x20type.\n\n\x0c\n\x05\x04\x04\x02\0\
-    \x04\x12\x03^\x04\x0c\n\x0c\n\x05\x04\x04\x02\0\x06\x12\x03^\r\x17\n\x0c\
-    \n\x05\x04\x04\x02\0\x01\x12\x03^\x18\x1c\n\x0c\n\x05\x04\x04\x02\0\x03\
-    \x12\x03^\x1f\x20\n/\n\x04\x04\x04\x02\x01\x12\x03`\x04\x1f\x1a\"\x20Hum\
-    an\x20readable\x20rejection\x20reason.\n\n\x0c\n\x05\x04\x04\x02\x01\x04\
-    \x12\x03`\x04\x0c\n\x0c\n\x05\x04\x04\x02\x01\x05\x12\x03`\r\x13\n\x0c\n\
-    \x05\x04\x04\x02\x01\x01\x12\x03`\x14\x1a\n\x0c\n\x05\x04\x04\x02\x01\
-    \x03\x12\x03`\x1d\x1e\n\x87\x01\n\x02\x04\x05\x12\x04e\0n\x01\x1a{\x20Se\
-    rverSync\x20message\x20is\x20sent\x20by\x20the\x20server\x20when\x20it\
-    \x20has\x20authenticated\x20the\x20user\n\x20and\x20finished\x20synchron\
-    izing\x20the\x20server\x20state.\n\n\n\n\x03\x04\x05\x01\x12\x03e\x08\
-    \x12\n/\n\x04\x04\x05\x02\0\x12\x03g\x04\x20\x1a\"\x20The\x20session\x20\
-    of\x20the\x20current\x20user.\n\n\x0c\n\x05\x04\x05\x02\0\x04\x12\x03g\
-    \x04\x0c\n\x0c\n\x05\x04\x05\x02\0\x05\x12\x03g\r\x13\n\x0c\n\x05\x04\
-    \x05\x02\0\x01\x12\x03g\x14\x1b\n\x0c\n\x05\x04\x05\x02\0\x03\x12\x03g\
-    \x1e\x1f\n:\n\x04\x04\x05\x02\x01\x12\x03i\x04&\x1a-\x20Maximum\x20bandw\
-    idth\x20that\x20the\x20user\x20should\x20use.\n\n\x0c\n\x05\x04\x05\x02\
-    \x01\x04\x12\x03i\x04\x0c\n\x0c\n\x05\x04\x05\x02\x01\x05\x12\x03i\r\x13\
-    \n\x0c\n\x05\x04\x05\x02\x01\x01\x12\x03i\x14!\n\x0c\n\x05\x04\x05\x02\
-    \x01\x03\x12\x03i$%\n#\n\x04\x04\x05\x02\x02\x12\x03k\x04%\x1a\x16\x20Se\
-    rver\x20welcome\x20text.\n\n\x0c\n\x05\x04\x05\x02\x02\x04\x12\x03k\x04\
-    \x0c\n\x0c\n\x05\x04\x05\x02\x02\x05\x12\x03k\r\x13\n\x0c\n\x05\x04\x05\
-    \x02\x02\x01\x12\x03k\x14\x20\n\x0c\n\x05\x04\x05\x02\x02\x03\x12\x03k#$\
-    \n<\n\x04\x04\x05\x02\x03\x12\x03m\x04$\x1a/\x20Current\x20user\x20permi\
-    ssions\x20in\x20the\x20root\x20channel.\n\n\x0c\n\x05\x04\x05\x02\x03\
-    \x04\x12\x03m\x04\x0c\n\x0c\n\x05\x04\x05\x02\x03\x05\x12\x03m\r\x13\n\
-    \x0c\n\x05\x04\x05\x02\x03\x01\x12\x03m\x14\x1f\n\x0c\n\x05\x04\x05\x02\
-    \x03\x03\x12\x03m\"#\n\x96\x01\n\x02\x04\x06\x12\x04r\0t\x01\x1a\x89\x01\
-    \x20Sent\x20by\x20the\x20client\x20when\x20it\x20wants\x20a\x20channel\
-    \x20removed.\x20Sent\x20by\x20the\x20server\x20when\n\x20a\x20channel\
-    \x20has\x20been\x20removed\x20and\x20clients\x20should\x20be\x20notified\
-    .\n\n\n\n\x03\x04\x06\x01\x12\x03r\x08\x15\n\x0b\n\x04\x04\x06\x02\0\x12\
-    \x03s\x04#\n\x0c\n\x05\x04\x06\x02\0\x04\x12\x03s\x04\x0c\n\x0c\n\x05\
-    \x04\x06\x02\0\x05\x12\x03s\r\x13\n\x0c\n\x05\x04\x06\x02\0\x01\x12\x03s\
-    \x14\x1e\n\x0c\n\x05\x04\x06\x02\0\x03\x12\x03s!\"\n\xee\x01\n\x02\x04\
-    \x07\x12\x05y\0\x98\x01\x01\x1a\xe0\x01\x20Used\x20to\x20communicate\x20\
-    channel\x20properties\x20between\x20the\x20client\x20and\x20the\x20serve\
-    r.\n\x20Sent\x20by\x20the\x20server\x20during\x20the\x20login\x20process\
-    \x20or\x20when\x20channel\x20properties\x20are\n\x20updated.\x20Client\
-    \x20may\x20use\x20this\x20message\x20to\x20update\x20said\x20channel\x20\
-    properties.\n\n\n\n\x03\x04\x07\x01\x12\x03y\x08\x14\n;\n\x04\x04\x07\
-    \x02\0\x12\x03{\x04#\x1a.\x20Unique\x20ID\x20for\x20the\x20channel\x20wi\
-    thin\x20the\x20server.\n\n\x0c\n\x05\x04\x07\x02\0\x04\x12\x03{\x04\x0c\
-    \n\x0c\n\x05\x04\x07\x02\0\x05\x12\x03{\r\x13\n\x0c\n\x05\x04\x07\x02\0\
-    \x01\x12\x03{\x14\x1e\n\x0c\n\x05\x04\x07\x02\0\x03\x12\x03{!\"\n0\n\x04\
-    \x04\x07\x02\x01\x12\x03}\x04\x1f\x1a#\x20channel_id\x20of\x20the\x20par\
-    ent\x20channel.\n\n\x0c\n\x05\x04\x07\x02\x01\x04\x12\x03}\x04\x0c\n\x0c\
-    \n\x05\x04\x07\x02\x01\x05\x12\x03}\r\x13\n\x0c\n\x05\x04\x07\x02\x01\
-    \x01\x12\x03}\x14\x1a\n\x0c\n\x05\x04\x07\x02\x01\x03\x12\x03}\x1d\x1e\n\
-    *\n\x04\x04\x07\x02\x02\x12\x03\x7f\x04\x1d\x1a\x1d\x20UTF-8\x20encoded\
-    \x20channel\x20name.\n\n\x0c\n\x05\x04\x07\x02\x02\x04\x12\x03\x7f\x04\
-    \x0c\n\x0c\n\x05\x04\x07\x02\x02\x05\x12\x03\x7f\r\x13\n\x0c\n\x05\x04\
-    \x07\x02\x02\x01\x12\x03\x7f\x14\x18\n\x0c\n\x05\x04\x07\x02\x02\x03\x12\
-    \x03\x7f\x1b\x1c\ns\n\x04\x04\x07\x02\x03\x12\x04\x82\x01\x04\x1e\x1ae\
-    \x20A\x20collection\x20of\x20channel\x20id\x20values\x20of\x20the\x20lin\
-    ked\x20channels.\x20Absent\x20during\n\x20the\x20first\x20channel\x20lis\
-    ting.\n\n\r\n\x05\x04\x07\x02\x03\x04\x12\x04\x82\x01\x04\x0c\n\r\n\x05\
-    \x04\x07\x02\x03\x05\x12\x04\x82\x01\r\x13\n\r\n\x05\x04\x07\x02\x03\x01\
-    \x12\x04\x82\x01\x14\x19\n\r\n\x05\x04\x07\x02\x03\x03\x12\x04\x82\x01\
-    \x1c\x1d\nb\n\x04\x04\x07\x02\x04\x12\x04\x85\x01\x04$\x1aT\x20UTF-8\x20\
-    encoded\x20channel\x20description.\x20Only\x20if\x20the\x20description\
-    \x20is\x20less\x20than\n\x20128\x20bytes\n\n\r\n\x05\x04\x07\x02\x04\x04\
-    \x12\x04\x85\x01\x04\x0c\n\r\n\x05\x04\x07\x02\x04\x05\x12\x04\x85\x01\r\
-    \x13\n\r\n\x05\x04\x07\x02\x04\x01\x12\x04\x85\x01\x14\x1f\n\r\n\x05\x04\
-    \x07\x02\x04\x03\x12\x04\x85\x01\"#\nP\n\x04\x04\x07\x02\x05\x12\x04\x87\
-    \x01\x04\"\x1aB\x20A\x20collection\x20of\x20channel_id\x20values\x20that\
-    \x20should\x20be\x20added\x20to\x20links.\n\n\r\n\x05\x04\x07\x02\x05\
-    \x04\x12\x04\x87\x01\x04\x0c\n\r\n\x05\x04\x07\x02\x05\x05\x12\x04\x87\
-    \x01\r\x13\n\r\n\x05\x04\x07\x02\x05\x01\x12\x04\x87\x01\x14\x1d\n\r\n\
-    \x05\x04\x07\x02\x05\x03\x12\x04\x87\x01\x20!\nT\n\x04\x04\x07\x02\x06\
-    \x12\x04\x89\x01\x04%\x1aF\x20A\x20collection\x20of\x20channel_id\x20val\
-    ues\x20that\x20should\x20be\x20removed\x20from\x20links.\n\n\r\n\x05\x04\
-    \x07\x02\x06\x04\x12\x04\x89\x01\x04\x0c\n\r\n\x05\x04\x07\x02\x06\x05\
-    \x12\x04\x89\x01\r\x13\n\r\n\x05\x04\x07\x02\x06\x01\x12\x04\x89\x01\x14\
-    \x20\n\r\n\x05\x04\x07\x02\x06\x03\x12\x04\x89\x01#$\n1\n\x04\x04\x07\
-    \x02\x07\x12\x04\x8b\x01\x042\x1a#\x20True\x20if\x20the\x20channel\x20is\
-    \x20temporary.\n\n\r\n\x05\x04\x07\x02\x07\x04\x12\x04\x8b\x01\x04\x0c\n\
-    \r\n\x05\x04\x07\x02\x07\x05\x12\x04\x8b\x01\r\x11\n\r\n\x05\x04\x07\x02\
-    \x07\x01\x12\x04\x8b\x01\x12\x1b\n\r\n\x05\x04\x07\x02\x07\x03\x12\x04\
-    \x8b\x01\x1e\x1f\n\r\n\x05\x04\x07\x02\x07\x08\x12\x04\x8b\x01\x201\n\r\
-    \n\x05\x04\x07\x02\x07\x07\x12\x04\x8b\x01+0\nR\n\x04\x04\x07\x02\x08\
-    \x12\x04\x8d\x01\x04.\x1aD\x20Position\x20weight\x20to\x20tweak\x20the\
-    \x20channel\x20position\x20in\x20the\x20channel\x20list.\n\n\r\n\x05\x04\
-    \x07\x02\x08\x04\x12\x04\x8d\x01\x04\x0c\n\r\n\x05\x04\x07\x02\x08\x05\
-    \x12\x04\x8d\x01\r\x12\n\r\n\x05\x04\x07\x02\x08\x01\x12\x04\x8d\x01\x13\
-    \x1b\n\r\n\x05\x04\x07\x02\x08\x03\x12\x04\x8d\x01\x1e\x1f\n\r\n\x05\x04\
-    \x07\x02\x08\x08\x12\x04\x8d\x01\x20-\n\r\n\x05\x04\x07\x02\x08\x07\x12\
-    \x04\x8d\x01+,\nU\n\x04\x04\x07\x02\t\x12\x04\x8f\x01\x04)\x1aG\x20SHA1\
-    \x20hash\x20of\x20the\x20description\x20if\x20the\x20description\x20is\
-    \x20128\x20bytes\x20or\x20more.\n\n\r\n\x05\x04\x07\x02\t\x04\x12\x04\
-    \x8f\x01\x04\x0c\n\r\n\x05\x04\x07\x02\t\x05\x12\x04\x8f\x01\r\x12\n\r\n\
-    \x05\x04\x07\x02\t\x01\x12\x04\x8f\x01\x13#\n\r\n\x05\x04\x07\x02\t\x03\
-    \x12\x04\x8f\x01&(\n\xc0\x01\n\x04\x04\x07\x02\n\x12\x04\x93\x01\x04#\
-    \x1a\xb1\x01\x20Maximum\x20number\x20of\x20users\x20allowed\x20in\x20the\
-    \x20channel.\x20If\x20this\x20value\x20is\x20zero,\n\x20the\x20maximum\
-    \x20number\x20of\x20users\x20allowed\x20in\x20the\x20channel\x20is\x20gi\
-    ven\x20by\x20the\n\x20server's\x20\"usersperchannel\"\x20setting.\n\n\r\
-    \n\x05\x04\x07\x02\n\x04\x12\x04\x93\x01\x04\x0c\n\r\n\x05\x04\x07\x02\n\
-    \x05\x12\x04\x93\x01\r\x13\n\r\n\x05\x04\x07\x02\n\x01\x12\x04\x93\x01\
-    \x14\x1d\n\r\n\x05\x04\x07\x02\n\x03\x12\x04\x93\x01\x20\"\nS\n\x04\x04\
-    \x07\x02\x0b\x12\x04\x95\x01\x04+\x1aE\x20Whether\x20this\x20channel\x20\
-    has\x20enter\x20restrictions\x20(ACL\x20denying\x20ENTER)\x20set\n\n\r\n\
-    \x05\x04\x07\x02\x0b\x04\x12\x04\x95\x01\x04\x0c\n\r\n\x05\x04\x07\x02\
-    \x0b\x05\x12\x04\x95\x01\r\x11\n\r\n\x05\x04\x07\x02\x0b\x01\x12\x04\x95\
-    \x01\x12%\n\r\n\x05\x04\x07\x02\x0b\x03\x12\x04\x95\x01(*\n_\n\x04\x04\
-    \x07\x02\x0c\x12\x04\x97\x01\x04!\x1aQ\x20Whether\x20the\x20receiver\x20\
-    of\x20this\x20msg\x20is\x20considered\x20to\x20be\x20able\x20to\x20enter\
-    \x20this\x20channel\n\n\r\n\x05\x04\x07\x02\x0c\x04\x12\x04\x97\x01\x04\
-    \x0c\n\r\n\x05\x04\x07\x02\x0c\x05\x12\x04\x97\x01\r\x11\n\r\n\x05\x04\
-    \x07\x02\x0c\x01\x12\x04\x97\x01\x12\x1b\n\r\n\x05\x04\x07\x02\x0c\x03\
-    \x12\x04\x97\x01\x1e\x20\n\xd2\x01\n\x02\x04\x08\x12\x06\x9d\x01\0\xa8\
-    \x01\x01\x1a\xc3\x01\x20Used\x20to\x20communicate\x20user\x20leaving\x20\
-    or\x20being\x20kicked.\x20May\x20be\x20sent\x20by\x20the\x20client\n\x20\
-    when\x20it\x20attempts\x20to\x20kick\x20a\x20user.\x20Sent\x20by\x20the\
-    \x20server\x20when\x20it\x20informs\x20the\n\x20clients\x20that\x20a\x20\
-    user\x20is\x20not\x20present\x20anymore.\n\n\x0b\n\x03\x04\x08\x01\x12\
-    \x04\x9d\x01\x08\x12\nt\n\x04\x04\x08\x02\0\x12\x04\xa0\x01\x04\x20\x1af\
-    \x20The\x20user\x20who\x20is\x20being\x20kicked,\x20identified\x20by\x20\
-    their\x20session,\x20not\x20present\n\x20when\x20no\x20one\x20is\x20bein\
-    g\x20kicked.\n\n\r\n\x05\x04\x08\x02\0\x04\x12\x04\xa0\x01\x04\x0c\n\r\n\
-    \x05\x04\x08\x02\0\x05\x12\x04\xa0\x01\r\x13\n\r\n\x05\x04\x08\x02\0\x01\
-    \x12\x04\xa0\x01\x14\x1b\n\r\n\x05\x04\x08\x02\0\x03\x12\x04\xa0\x01\x1e\
-    \x1f\n\x80\x01\n\x04\x04\x08\x02\x01\x12\x04\xa3\x01\x04\x1e\x1ar\x20The\
-    \x20user\x20who\x20initiated\x20the\x20removal.\x20Either\x20the\x20user\
-    \x20who\x20performs\x20the\x20kick\n\x20or\x20the\x20user\x20who\x20is\
-    \x20currently\x20leaving.\n\n\r\n\x05\x04\x08\x02\x01\x04\x12\x04\xa3\
-    \x01\x04\x0c\n\r\n\x05\x04\x08\x02\x01\x05\x12\x04\xa3\x01\r\x13\n\r\n\
-    \x05\x04\x08\x02\x01\x01\x12\x04\xa3\x01\x14\x19\n\r\n\x05\x04\x08\x02\
-    \x01\x03\x12\x04\xa3\x01\x1c\x1d\nT\n\x04\x04\x08\x02\x02\x12\x04\xa5\
-    \x01\x04\x1f\x1aF\x20Reason\x20for\x20the\x20kick,\x20stored\x20as\x20th\
-    e\x20ban\x20reason\x20if\x20the\x20user\x20is\x20banned.\n\n\r\n\x05\x04\
-    \x08\x02\x02\x04\x12\x04\xa5\x01\x04\x0c\n\r\n\x05\x04\x08\x02\x02\x05\
-    \x12\x04\xa5\x01\r\x13\n\r\n\x05\x04\x08\x02\x02\x01\x12\x04\xa5\x01\x14\
-    \x1a\n\r\n\x05\x04\x08\x02\x02\x03\x12\x04\xa5\x01\x1d\x1e\n8\n\x04\x04\
-    \x08\x02\x03\x12\x04\xa7\x01\x04\x1a\x1a*\x20True\x20if\x20the\x20kick\
-    \x20should\x20result\x20in\x20a\x20ban.\n\n\r\n\x05\x04\x08\x02\x03\x04\
-    \x12\x04\xa7\x01\x04\x0c\n\r\n\x05\x04\x08\x02\x03\x05\x12\x04\xa7\x01\r\
-    \x11\n\r\n\x05\x04\x08\x02\x03\x01\x12\x04\xa7\x01\x12\x15\n\r\n\x05\x04\
-    \x08\x02\x03\x03\x12\x04\xa7\x01\x18\x19\n\xbb\x01\n\x02\x04\t\x12\x06\
-    \xad\x01\0\xe1\x01\x01\x1a\xac\x01\x20Sent\x20by\x20the\x20server\x20whe\
-    n\x20it\x20communicates\x20new\x20and\x20changed\x20users\x20to\x20clien\
-    t.\n\x20First\x20seen\x20during\x20login\x20procedure.\x20May\x20be\x20s\
-    ent\x20by\x20the\x20client\x20when\x20it\x20wishes\n\x20to\x20alter\x20i\
-    ts\x20state.\n\n\x0b\n\x03\x04\t\x01\x12\x04\xad\x01\x08\x11\na\n\x04\
-    \x04\t\x02\0\x12\x04\xb0\x01\x04\x20\x1aS\x20Unique\x20user\x20session\
-    \x20ID\x20of\x20the\x20user\x20whose\x20state\x20this\x20is,\x20may\x20c\
-    hange\x20on\n\x20reconnect.\n\n\r\n\x05\x04\t\x02\0\x04\x12\x04\xb0\x01\
-    \x04\x0c\n\r\n\x05\x04\t\x02\0\x05\x12\x04\xb0\x01\r\x13\n\r\n\x05\x04\t\
-    \x02\0\x01\x12\x04\xb0\x01\x14\x1b\n\r\n\x05\x04\t\x02\0\x03\x12\x04\xb0\
-    \x01\x1e\x1f\nB\n\x04\x04\t\x02\x01\x12\x04\xb2\x01\x04\x1e\x1a4\x20The\
-    \x20session\x20of\x20the\x20user\x20who\x20is\x20updating\x20this\x20use\
-    r.\n\n\r\n\x05\x04\t\x02\x01\x04\x12\x04\xb2\x01\x04\x0c\n\r\n\x05\x04\t\
-    \x02\x01\x05\x12\x04\xb2\x01\r\x13\n\r\n\x05\x04\t\x02\x01\x01\x12\x04\
-    \xb2\x01\x14\x19\n\r\n\x05\x04\t\x02\x01\x03\x12\x04\xb2\x01\x1c\x1d\n)\
-    \n\x04\x04\t\x02\x02\x12\x04\xb4\x01\x04\x1d\x1a\x1b\x20User\x20name,\
-    \x20UTF-8\x20encoded.\n\n\r\n\x05\x04\t\x02\x02\x04\x12\x04\xb4\x01\x04\
-    \x0c\n\r\n\x05\x04\t\x02\x02\x05\x12\x04\xb4\x01\r\x13\n\r\n\x05\x04\t\
-    \x02\x02\x01\x12\x04\xb4\x01\x14\x18\n\r\n\x05\x04\t\x02\x02\x03\x12\x04\
-    \xb4\x01\x1b\x1c\n=\n\x04\x04\t\x02\x03\x12\x04\xb6\x01\x04\x20\x1a/\x20\
-    Registered\x20user\x20ID\x20if\x20the\x20user\x20is\x20registered.\n\n\r\
-    \n\x05\x04\t\x02\x03\x04\x12\x04\xb6\x01\x04\x0c\n\r\n\x05\x04\t\x02\x03\
-    \x05\x12\x04\xb6\x01\r\x13\n\r\n\x05\x04\t\x02\x03\x01\x12\x04\xb6\x01\
-    \x14\x1b\n\r\n\x05\x04\t\x02\x03\x03\x12\x04\xb6\x01\x1e\x1f\n-\n\x04\
-    \x04\t\x02\x04\x12\x04\xb8\x01\x04#\x1a\x1f\x20Channel\x20on\x20which\
-    \x20the\x20user\x20is.\n\n\r\n\x05\x04\t\x02\x04\x04\x12\x04\xb8\x01\x04\
-    \x0c\n\r\n\x05\x04\t\x02\x04\x05\x12\x04\xb8\x01\r\x13\n\r\n\x05\x04\t\
-    \x02\x04\x01\x12\x04\xb8\x01\x14\x1e\n\r\n\x05\x04\t\x02\x04\x03\x12\x04\
-    \xb8\x01!\"\n3\n\x04\x04\t\x02\x05\x12\x04\xba\x01\x04\x1b\x1a%\x20True\
-    \x20if\x20the\x20user\x20is\x20muted\x20by\x20admin.\n\n\r\n\x05\x04\t\
-    \x02\x05\x04\x12\x04\xba\x01\x04\x0c\n\r\n\x05\x04\t\x02\x05\x05\x12\x04\
-    \xba\x01\r\x11\n\r\n\x05\x04\t\x02\x05\x01\x12\x04\xba\x01\x12\x16\n\r\n\
-    \x05\x04\t\x02\x05\x03\x12\x04\xba\x01\x19\x1a\n6\n\x04\x04\t\x02\x06\
-    \x12\x04\xbc\x01\x04\x1b\x1a(\x20True\x20if\x20the\x20user\x20is\x20deaf\
-    ened\x20by\x20admin.\n\n\r\n\x05\x04\t\x02\x06\x04\x12\x04\xbc\x01\x04\
-    \x0c\n\r\n\x05\x04\t\x02\x06\x05\x12\x04\xbc\x01\r\x11\n\r\n\x05\x04\t\
-    \x02\x06\x01\x12\x04\xbc\x01\x12\x16\n\r\n\x05\x04\t\x02\x06\x03\x12\x04\
-    \xbc\x01\x19\x1a\nf\n\x04\x04\t\x02\x07\x12\x04\xbf\x01\x04\x1f\x1aX\x20\
-    True\x20if\x20the\x20user\x20has\x20been\x20suppressed\x20from\x20talkin\
-    g\x20by\x20a\x20reason\x20other\x20than\n\x20being\x20muted.\n\n\r\n\x05\
-    \x04\t\x02\x07\x04\x12\x04\xbf\x01\x04\x0c\n\r\n\x05\x04\t\x02\x07\x05\
-    \x12\x04\xbf\x01\r\x11\n\r\n\x05\x04\t\x02\x07\x01\x12\x04\xbf\x01\x12\
-    \x1a\n\r\n\x05\x04\t\x02\x07\x03\x12\x04\xbf\x01\x1d\x1e\n0\n\x04\x04\t\
-    \x02\x08\x12\x04\xc1\x01\x04\x20\x1a\"\x20True\x20if\x20the\x20user\x20h\
-    as\x20muted\x20self.\n\n\r\n\x05\x04\t\x02\x08\x04\x12\x04\xc1\x01\x04\
-    \x0c\n\r\n\x05\x04\t\x02\x08\x05\x12\x04\xc1\x01\r\x11\n\r\n\x05\x04\t\
-    \x02\x08\x01\x12\x04\xc1\x01\x12\x1b\n\r\n\x05\x04\t\x02\x08\x03\x12\x04\
-    \xc1\x01\x1e\x1f\n3\n\x04\x04\t\x02\t\x12\x04\xc3\x01\x04!\x1a%\x20True\
-    \x20if\x20the\x20user\x20has\x20deafened\x20self.\n\n\r\n\x05\x04\t\x02\
-    \t\x04\x12\x04\xc3\x01\x04\x0c\n\r\n\x05\x04\t\x02\t\x05\x12\x04\xc3\x01\
-    \r\x11\n\r\n\x05\x04\t\x02\t\x01\x12\x04\xc3\x01\x12\x1b\n\r\n\x05\x04\t\
-    \x02\t\x03\x12\x04\xc3\x01\x1e\x20\n8\n\x04\x04\t\x02\n\x12\x04\xc5\x01\
-    \x04\x20\x1a*\x20User\x20image\x20if\x20it\x20is\x20less\x20than\x20128\
-    \x20bytes.\n\n\r\n\x05\x04\t\x02\n\x04\x12\x04\xc5\x01\x04\x0c\n\r\n\x05\
-    \x04\t\x02\n\x05\x12\x04\xc5\x01\r\x12\n\r\n\x05\x04\t\x02\n\x01\x12\x04\
-    \xc5\x01\x13\x1a\n\r\n\x05\x04\t\x02\n\x03\x12\x04\xc5\x01\x1d\x1f\n\xbe\
-    \x01\n\x04\x04\t\x02\x0b\x12\x04\xcb\x01\x04'\x1a\xaf\x01\x20The\x20posi\
-    tional\x20audio\x20plugin\x20identifier.\n\x20Positional\x20audio\x20inf\
-    ormation\x20is\x20only\x20sent\x20to\x20users\x20who\x20share\n\x20ident\
-    ical\x20plugin\x20contexts.\n\n\x20This\x20value\x20is\x20not\x20transmi\
-    tted\x20to\x20clients.\n\n\r\n\x05\x04\t\x02\x0b\x04\x12\x04\xcb\x01\x04\
-    \x0c\n\r\n\x05\x04\t\x02\x0b\x05\x12\x04\xcb\x01\r\x12\n\r\n\x05\x04\t\
-    \x02\x0b\x01\x12\x04\xcb\x01\x13!\n\r\n\x05\x04\t\x02\x0b\x03\x12\x04\
-    \xcb\x01$&\n_\n\x04\x04\t\x02\x0c\x12\x04\xce\x01\x04)\x1aQ\x20The\x20us\
-    er's\x20plugin-specific\x20identity.\n\x20This\x20value\x20is\x20not\x20\
-    transmitted\x20to\x20clients.\n\n\r\n\x05\x04\t\x02\x0c\x04\x12\x04\xce\
-    \x01\x04\x0c\n\r\n\x05\x04\t\x02\x0c\x05\x12\x04\xce\x01\r\x13\n\r\n\x05\
-    \x04\t\x02\x0c\x01\x12\x04\xce\x01\x14#\n\r\n\x05\x04\t\x02\x0c\x03\x12\
-    \x04\xce\x01&(\n:\n\x04\x04\t\x02\r\x12\x04\xd0\x01\x04!\x1a,\x20User\
-    \x20comment\x20if\x20it\x20is\x20less\x20than\x20128\x20bytes.\n\n\r\n\
-    \x05\x04\t\x02\r\x04\x12\x04\xd0\x01\x04\x0c\n\r\n\x05\x04\t\x02\r\x05\
-    \x12\x04\xd0\x01\r\x13\n\r\n\x05\x04\t\x02\r\x01\x12\x04\xd0\x01\x14\x1b\
-    \n\r\n\x05\x04\t\x02\r\x03\x12\x04\xd0\x01\x1e\x20\n1\n\x04\x04\t\x02\
-    \x0e\x12\x04\xd2\x01\x04\x1e\x1a#\x20The\x20hash\x20of\x20the\x20user\
-    \x20certificate.\n\n\r\n\x05\x04\t\x02\x0e\x04\x12\x04\xd2\x01\x04\x0c\n\
-    \r\n\x05\x04\t\x02\x0e\x05\x12\x04\xd2\x01\r\x13\n\r\n\x05\x04\t\x02\x0e\
-    \x01\x12\x04\xd2\x01\x14\x18\n\r\n\x05\x04\t\x02\x0e\x03\x12\x04\xd2\x01\
-    \x1b\x1d\nF\n\x04\x04\t\x02\x0f\x12\x04\xd4\x01\x04%\x1a8\x20SHA1\x20has\
-    h\x20of\x20the\x20user\x20comment\x20if\x20it\x20128\x20bytes\x20or\x20m\
-    ore.\n\n\r\n\x05\x04\t\x02\x0f\x04\x12\x04\xd4\x01\x04\x0c\n\r\n\x05\x04\
-    \t\x02\x0f\x05\x12\x04\xd4\x01\r\x12\n\r\n\x05\x04\t\x02\x0f\x01\x12\x04\
-    \xd4\x01\x13\x1f\n\r\n\x05\x04\t\x02\x0f\x03\x12\x04\xd4\x01\"$\nF\n\x04\
-    \x04\t\x02\x10\x12\x04\xd6\x01\x04%\x1a8\x20SHA1\x20hash\x20of\x20the\
-    \x20user\x20picture\x20if\x20it\x20128\x20bytes\x20or\x20more.\n\n\r\n\
-    \x05\x04\t\x02\x10\x04\x12\x04\xd6\x01\x04\x0c\n\r\n\x05\x04\t\x02\x10\
-    \x05\x12\x04\xd6\x01\r\x12\n\r\n\x05\x04\t\x02\x10\x01\x12\x04\xd6\x01\
-    \x13\x1f\n\r\n\x05\x04\t\x02\x10\x03\x12\x04\xd6\x01\"$\n7\n\x04\x04\t\
-    \x02\x11\x12\x04\xd8\x01\x04(\x1a)\x20True\x20if\x20the\x20user\x20is\
-    \x20a\x20priority\x20speaker.\n\n\r\n\x05\x04\t\x02\x11\x04\x12\x04\xd8\
-    \x01\x04\x0c\n\r\n\x05\x04\t\x02\x11\x05\x12\x04\xd8\x01\r\x11\n\r\n\x05\
-    \x04\t\x02\x11\x01\x12\x04\xd8\x01\x12\"\n\r\n\x05\x04\t\x02\x11\x03\x12\
-    \x04\xd8\x01%'\n8\n\x04\x04\t\x02\x12\x12\x04\xda\x01\x04!\x1a*\x20True\
-    \x20if\x20the\x20user\x20is\x20currently\x20recording.\n\n\r\n\x05\x04\t\
-    \x02\x12\x04\x12\x04\xda\x01\x04\x0c\n\r\n\x05\x04\t\x02\x12\x05\x12\x04\
-    \xda\x01\r\x11\n\r\n\x05\x04\t\x02\x12\x01\x12\x04\xda\x01\x12\x1b\n\r\n\
-    \x05\x04\t\x02\x12\x03\x12\x04\xda\x01\x1e\x20\n_\n\x04\x04\t\x02\x13\
-    \x12\x04\xdc\x01\x041\x1aQ\x20A\x20list\x20of\x20temporary\x20access\x20\
-    tokens\x20to\x20be\x20respected\x20when\x20processing\x20this\x20request\
-    .\n\n\r\n\x05\x04\t\x02\x13\x04\x12\x04\xdc\x01\x04\x0c\n\r\n\x05\x04\t\
-    \x02\x13\x05\x12\x04\xdc\x01\r\x13\n\r\n\x05\x04\t\x02\x13\x01\x12\x04\
-    \xdc\x01\x14+\n\r\n\x05\x04\t\x02\x13\x03\x12\x04\xdc\x01.0\nH\n\x04\x04\
-    \t\x02\x14\x12\x04\xde\x01\x04/\x1a:\x20A\x20list\x20of\x20channels\x20t\
-    he\x20user\x20wants\x20to\x20start\x20listening\x20to.\n\n\r\n\x05\x04\t\
-    \x02\x14\x04\x12\x04\xde\x01\x04\x0c\n\r\n\x05\x04\t\x02\x14\x05\x12\x04\
-    \xde\x01\r\x13\n\r\n\x05\x04\t\x02\x14\x01\x12\x04\xde\x01\x14)\n\r\n\
-    \x05\x04\t\x02\x14\x03\x12\x04\xde\x01,.\nM\n\x04\x04\t\x02\x15\x12\x04\
-    \xe0\x01\x042\x1a?\x20a\x20list\x20of\x20channels\x20the\x20user\x20does\
-    \x20no\x20longer\x20want\x20to\x20listen\x20to.\n\n\r\n\x05\x04\t\x02\
-    \x15\x04\x12\x04\xe0\x01\x04\x0c\n\r\n\x05\x04\t\x02\x15\x05\x12\x04\xe0\
-    \x01\r\x13\n\r\n\x05\x04\t\x02\x15\x01\x12\x04\xe0\x01\x14,\n\r\n\x05\
-    \x04\t\x02\x15\x03\x12\x04\xe0\x01/1\n\xda\x01\n\x02\x04\n\x12\x06\xe6\
-    \x01\0\xfc\x01\x01\x1a\xcb\x01\x20Relays\x20information\x20on\x20the\x20\
-    bans.\x20The\x20client\x20may\x20send\x20the\x20BanList\x20message\x20to\
-    \n\x20either\x20modify\x20the\x20list\x20of\x20bans\x20or\x20query\x20th\
-    em\x20from\x20the\x20server.\x20The\x20server\n\x20sends\x20this\x20list\
-    \x20only\x20after\x20a\x20client\x20queries\x20for\x20it.\n\n\x0b\n\x03\
-    \x04\n\x01\x12\x04\xe6\x01\x08\x0f\n\x0e\n\x04\x04\n\x03\0\x12\x06\xe7\
-    \x01\x04\xf6\x01\x05\n\r\n\x05\x04\n\x03\0\x01\x12\x04\xe7\x01\x0c\x14\n\
-    $\n\x06\x04\n\x03\0\x02\0\x12\x04\xe9\x01\x08#\x1a\x14\x20Banned\x20IP\
-    \x20address.\n\n\x0f\n\x07\x04\n\x03\0\x02\0\x04\x12\x04\xe9\x01\x08\x10\
-    \n\x0f\n\x07\x04\n\x03\0\x02\0\x05\x12\x04\xe9\x01\x11\x16\n\x0f\n\x07\
-    \x04\n\x03\0\x02\0\x01\x12\x04\xe9\x01\x17\x1e\n\x0f\n\x07\x04\n\x03\0\
-    \x02\0\x03\x12\x04\xe9\x01!\"\n<\n\x06\x04\n\x03\0\x02\x01\x12\x04\xeb\
-    \x01\x08!\x1a,\x20The\x20length\x20of\x20the\x20subnet\x20mask\x20for\
-    \x20the\x20ban.\n\n\x0f\n\x07\x04\n\x03\0\x02\x01\x04\x12\x04\xeb\x01\
-    \x08\x10\n\x0f\n\x07\x04\n\x03\0\x02\x01\x05\x12\x04\xeb\x01\x11\x17\n\
-    \x0f\n\x07\x04\n\x03\0\x02\x01\x01\x12\x04\xeb\x01\x18\x1c\n\x0f\n\x07\
-    \x04\n\x03\0\x02\x01\x03\x12\x04\xeb\x01\x1f\x20\nR\n\x06\x04\n\x03\0\
-    \x02\x02\x12\x04\xed\x01\x08!\x1aB\x20User\x20name\x20for\x20identificat\
-    ion\x20purposes\x20(does\x20not\x20affect\x20the\x20ban).\n\n\x0f\n\x07\
-    \x04\n\x03\0\x02\x02\x04\x12\x04\xed\x01\x08\x10\n\x0f\n\x07\x04\n\x03\0\
-    \x02\x02\x05\x12\x04\xed\x01\x11\x17\n\x0f\n\x07\x04\n\x03\0\x02\x02\x01\
-    \x12\x04\xed\x01\x18\x1c\n\x0f\n\x07\x04\n\x03\0\x02\x02\x03\x12\x04\xed\
-    \x01\x1f\x20\n:\n\x06\x04\n\x03\0\x02\x03\x12\x04\xef\x01\x08!\x1a*\x20T\
-    he\x20certificate\x20hash\x20of\x20the\x20banned\x20user.\n\n\x0f\n\x07\
-    \x04\n\x03\0\x02\x03\x04\x12\x04\xef\x01\x08\x10\n\x0f\n\x07\x04\n\x03\0\
-    \x02\x03\x05\x12\x04\xef\x01\x11\x17\n\x0f\n\x07\x04\n\x03\0\x02\x03\x01\
-    \x12\x04\xef\x01\x18\x1c\n\x0f\n\x07\x04\n\x03\0\x02\x03\x03\x12\x04\xef\
-    \x01\x1f\x20\n?\n\x06\x04\n\x03\0\x02\x04\x12\x04\xf1\x01\x08#\x1a/\x20R\
-    eason\x20for\x20the\x20ban\x20(does\x20not\x20affect\x20the\x20ban).\n\n\
-    \x0f\n\x07\x04\n\x03\0\x02\x04\x04\x12\x04\xf1\x01\x08\x10\n\x0f\n\x07\
-    \x04\n\x03\0\x02\x04\x05\x12\x04\xf1\x01\x11\x17\n\x0f\n\x07\x04\n\x03\0\
-    \x02\x04\x01\x12\x04\xf1\x01\x18\x1e\n\x0f\n\x07\x04\n\x03\0\x02\x04\x03\
-    \x12\x04\xf1\x01!\"\n!\n\x06\x04\n\x03\0\x02\x05\x12\x04\xf3\x01\x08\"\
-    \x1a\x11\x20Ban\x20start\x20time.\n\n\x0f\n\x07\x04\n\x03\0\x02\x05\x04\
-    \x12\x04\xf3\x01\x08\x10\n\x0f\n\x07\x04\n\x03\0\x02\x05\x05\x12\x04\xf3\
-    \x01\x11\x17\n\x0f\n\x07\x04\n\x03\0\x02\x05\x01\x12\x04\xf3\x01\x18\x1d\
-    \n\x0f\n\x07\x04\n\x03\0\x02\x05\x03\x12\x04\xf3\x01\x20!\n*\n\x06\x04\n\
-    \x03\0\x02\x06\x12\x04\xf5\x01\x08%\x1a\x1a\x20Ban\x20duration\x20in\x20\
-    seconds.\n\n\x0f\n\x07\x04\n\x03\0\x02\x06\x04\x12\x04\xf5\x01\x08\x10\n\
-    \x0f\n\x07\x04\n\x03\0\x02\x06\x05\x12\x04\xf5\x01\x11\x17\n\x0f\n\x07\
-    \x04\n\x03\0\x02\x06\x01\x12\x04\xf5\x01\x18\x20\n\x0f\n\x07\x04\n\x03\0\
-    \x02\x06\x03\x12\x04\xf5\x01#$\n7\n\x04\x04\n\x02\0\x12\x04\xf8\x01\x04\
-    \x1f\x1a)\x20List\x20of\x20ban\x20entries\x20currently\x20in\x20place.\n\
-    \n\r\n\x05\x04\n\x02\0\x04\x12\x04\xf8\x01\x04\x0c\n\r\n\x05\x04\n\x02\0\
-    \x06\x12\x04\xf8\x01\r\x15\n\r\n\x05\x04\n\x02\0\x01\x12\x04\xf8\x01\x16\
-    \x1a\n\r\n\x05\x04\n\x02\0\x03\x12\x04\xf8\x01\x1d\x1e\nz\n\x04\x04\n\
-    \x02\x01\x12\x04\xfb\x01\x04.\x1al\x20True\x20if\x20the\x20server\x20sho\
-    uld\x20return\x20the\x20list,\x20false\x20if\x20it\x20should\x20replace\
-    \x20old\n\x20ban\x20list\x20with\x20the\x20one\x20provided.\n\n\r\n\x05\
-    \x04\n\x02\x01\x04\x12\x04\xfb\x01\x04\x0c\n\r\n\x05\x04\n\x02\x01\x05\
-    \x12\x04\xfb\x01\r\x11\n\r\n\x05\x04\n\x02\x01\x01\x12\x04\xfb\x01\x12\
-    \x17\n\r\n\x05\x04\n\x02\x01\x03\x12\x04\xfb\x01\x1a\x1b\n\r\n\x05\x04\n\
-    \x02\x01\x08\x12\x04\xfb\x01\x1c-\n\r\n\x05\x04\n\x02\x01\x07\x12\x04\
-    \xfb\x01',\n9\n\x02\x04\x0b\x12\x06\xff\x01\0\x8c\x02\x01\x1a+\x20Used\
-    \x20to\x20send\x20and\x20broadcast\x20text\x20messages.\n\n\x0b\n\x03\
-    \x04\x0b\x01\x12\x04\xff\x01\x08\x13\n>\n\x04\x04\x0b\x02\0\x12\x04\x81\
-    \x02\x04\x1e\x1a0\x20The\x20message\x20sender,\x20identified\x20by\x20it\
-    s\x20session.\n\n\r\n\x05\x04\x0b\x02\0\x04\x12\x04\x81\x02\x04\x0c\n\r\
-    \n\x05\x04\x0b\x02\0\x05\x12\x04\x81\x02\r\x13\n\r\n\x05\x04\x0b\x02\0\
-    \x01\x12\x04\x81\x02\x14\x19\n\r\n\x05\x04\x0b\x02\0\x03\x12\x04\x81\x02\
-    \x1c\x1d\nJ\n\x04\x04\x0b\x02\x01\x12\x04\x83\x02\x04\x20\x1a<\x20Target\
-    \x20users\x20for\x20the\x20message,\x20identified\x20by\x20their\x20sess\
-    ion.\n\n\r\n\x05\x04\x0b\x02\x01\x04\x12\x04\x83\x02\x04\x0c\n\r\n\x05\
-    \x04\x0b\x02\x01\x05\x12\x04\x83\x02\r\x13\n\r\n\x05\x04\x0b\x02\x01\x01\
-    \x12\x04\x83\x02\x14\x1b\n\r\n\x05\x04\x0b\x02\x01\x03\x12\x04\x83\x02\
-    \x1e\x1f\n\\\n\x04\x04\x0b\x02\x02\x12\x04\x86\x02\x04#\x1aN\x20The\x20c\
-    hannels\x20to\x20which\x20the\x20message\x20is\x20sent,\x20identified\
-    \x20by\x20their\n\x20channel_ids.\n\n\r\n\x05\x04\x0b\x02\x02\x04\x12\
-    \x04\x86\x02\x04\x0c\n\r\n\x05\x04\x0b\x02\x02\x05\x12\x04\x86\x02\r\x13\
-    \n\r\n\x05\x04\x0b\x02\x02\x01\x12\x04\x86\x02\x14\x1e\n\r\n\x05\x04\x0b\
-    \x02\x02\x03\x12\x04\x86\x02!\"\ny\n\x04\x04\x0b\x02\x03\x12\x04\x89\x02\
-    \x04\x20\x1ak\x20The\x20root\x20channels\x20when\x20sending\x20message\
-    \x20recursively\x20to\x20several\x20channels,\n\x20identified\x20by\x20t\
-    heir\x20channel_ids.\n\n\r\n\x05\x04\x0b\x02\x03\x04\x12\x04\x89\x02\x04\
-    \x0c\n\r\n\x05\x04\x0b\x02\x03\x05\x12\x04\x89\x02\r\x13\n\r\n\x05\x04\
-    \x0b\x02\x03\x01\x12\x04\x89\x02\x14\x1b\n\r\n\x05\x04\x0b\x02\x03\x03\
-    \x12\x04\x89\x02\x1e\x1f\nL\n\x04\x04\x0b\x02\x04\x12\x04\x8b\x02\x04\
-    \x20\x1a>\x20The\x20UTF-8\x20encoded\x20message.\x20May\x20be\x20HTML\
-    \x20if\x20the\x20server\x20allows.\n\n\r\n\x05\x04\x0b\x02\x04\x04\x12\
-    \x04\x8b\x02\x04\x0c\n\r\n\x05\x04\x0b\x02\x04\x05\x12\x04\x8b\x02\r\x13\
-    \n\r\n\x05\x04\x0b\x02\x04\x01\x12\x04\x8b\x02\x14\x1b\n\r\n\x05\x04\x0b\
-    \x02\x04\x03\x12\x04\x8b\x02\x1e\x1f\n\x0c\n\x02\x04\x0c\x12\x06\x8e\x02\
-    \0\xba\x02\x01\n\x0b\n\x03\x04\x0c\x01\x12\x04\x8e\x02\x08\x18\n\x0e\n\
-    \x04\x04\x0c\x04\0\x12\x06\x8f\x02\x04\xac\x02\x05\n\r\n\x05\x04\x0c\x04\
-    \0\x01\x12\x04\x8f\x02\t\x11\nF\n\x06\x04\x0c\x04\0\x02\0\x12\x04\x91\
-    \x02\x08\x11\x1a6\x20Operation\x20denied\x20for\x20other\x20reason,\x20s\
-    ee\x20reason\x20field.\n\n\x0f\n\x07\x04\x0c\x04\0\x02\0\x01\x12\x04\x91\
-    \x02\x08\x0c\n\x0f\n\x07\x04\x0c\x04\0\x02\0\x02\x12\x04\x91\x02\x0f\x10\
-    \n*\n\x06\x04\x0c\x04\0\x02\x01\x12\x04\x93\x02\x08\x17\x1a\x1a\x20Permi\
-    ssions\x20were\x20denied.\n\n\x0f\n\x07\x04\x0c\x04\0\x02\x01\x01\x12\
-    \x04\x93\x02\x08\x12\n\x0f\n\x07\x04\x0c\x04\0\x02\x01\x02\x12\x04\x93\
-    \x02\x15\x16\n*\n\x06\x04\x0c\x04\0\x02\x02\x12\x04\x95\x02\x08\x16\x1a\
-    \x1a\x20Cannot\x20modify\x20SuperUser.\n\n\x0f\n\x07\x04\x0c\x04\0\x02\
-    \x02\x01\x12\x04\x95\x02\x08\x11\n\x0f\n\x07\x04\x0c\x04\0\x02\x02\x02\
-    \x12\x04\x95\x02\x14\x15\n'\n\x06\x04\x0c\x04\0\x02\x03\x12\x04\x97\x02\
-    \x08\x18\x1a\x17\x20Invalid\x20channel\x20name.\n\n\x0f\n\x07\x04\x0c\
-    \x04\0\x02\x03\x01\x12\x04\x97\x02\x08\x13\n\x0f\n\x07\x04\x0c\x04\0\x02\
-    \x03\x02\x12\x04\x97\x02\x16\x17\n(\n\x06\x04\x0c\x04\0\x02\x04\x12\x04\
-    \x99\x02\x08\x18\x1a\x18\x20Text\x20message\x20too\x20long.\n\n\x0f\n\
-    \x07\x04\x0c\x04\0\x02\x04\x01\x12\x04\x99\x02\x08\x13\n\x0f\n\x07\x04\
-    \x0c\x04\0\x02\x04\x02\x12\x04\x99\x02\x16\x17\n7\n\x06\x04\x0c\x04\0\
-    \x02\x05\x12\x04\x9b\x02\x08\x10\x1a'\x20The\x20flux\x20capacitor\x20was\
-    \x20spelled\x20wrong.\n\n\x0f\n\x07\x04\x0c\x04\0\x02\x05\x01\x12\x04\
-    \x9b\x02\x08\x0b\n\x0f\n\x07\x04\x0c\x04\0\x02\x05\x02\x12\x04\x9b\x02\
-    \x0e\x0f\n?\n\x06\x04\x0c\x04\0\x02\x06\x12\x04\x9d\x02\x08\x1d\x1a/\x20\
-    Operation\x20not\x20permitted\x20in\x20temporary\x20channel.\n\n\x0f\n\
-    \x07\x04\x0c\x04\0\x02\x06\x01\x12\x04\x9d\x02\x08\x18\n\x0f\n\x07\x04\
-    \x0c\x04\0\x02\x06\x02\x12\x04\x9d\x02\x1b\x1c\n1\n\x06\x04\x0c\x04\0\
-    \x02\x07\x12\x04\x9f\x02\x08\x1f\x1a!\x20Operation\x20requires\x20certif\
-    icate.\n\n\x0f\n\x07\x04\x0c\x04\0\x02\x07\x01\x12\x04\x9f\x02\x08\x1a\n\
-    \x0f\n\x07\x04\x0c\x04\0\x02\x07\x02\x12\x04\x9f\x02\x1d\x1e\n#\n\x06\
-    \x04\x0c\x04\0\x02\x08\x12\x04\xa1\x02\x08\x15\x1a\x13\x20Invalid\x20use\
-    rname.\n\n\x0f\n\x07\x04\x0c\x04\0\x02\x08\x01\x12\x04\xa1\x02\x08\x10\n\
-    \x0f\n\x07\x04\x0c\x04\0\x02\x08\x02\x12\x04\xa1\x02\x13\x14\n\"\n\x06\
-    \x04\x0c\x04\0\x02\t\x12\x04\xa3\x02\x08\x18\x1a\x12\x20Channel\x20is\
-    \x20full.\n\n\x0f\n\x07\x04\x0c\x04\0\x02\t\x01\x12\x04\xa3\x02\x08\x13\
-    \n\x0f\n\x07\x04\x0c\x04\0\x02\t\x02\x12\x04\xa3\x02\x16\x17\n1\n\x06\
-    \x04\x0c\x04\0\x02\n\x12\x04\xa5\x02\x08\x1a\x1a!\x20Channels\x20are\x20\
-    nested\x20too\x20deeply.\n\n\x0f\n\x07\x04\x0c\x04\0\x02\n\x01\x12\x04\
-    \xa5\x02\x08\x14\n\x0f\n\x07\x04\x0c\x04\0\x02\n\x02\x12\x04\xa5\x02\x17\
-    \x19\n0\n\x06\x04\x0c\x04\0\x02\x0b\x12\x04\xa7\x02\x08\x1f\x1a\x20\x20M\
-    aximum\x20channel\x20count\x20reached.\n\n\x0f\n\x07\x04\x0c\x04\0\x02\
-    \x0b\x01\x12\x04\xa7\x02\x08\x19\n\x0f\n\x07\x04\x0c\x04\0\x02\x0b\x02\
-    \x12\x04\xa7\x02\x1c\x1e\nN\n\x06\x04\x0c\x04\0\x02\x0c\x12\x04\xa9\x02\
-    \x08\"\x1a>\x20Amount\x20of\x20listener\x20objects\x20for\x20this\x20cha\
-    nnel\x20has\x20been\x20reached\n\n\x0f\n\x07\x04\x0c\x04\0\x02\x0c\x01\
-    \x12\x04\xa9\x02\x08\x1c\n\x0f\n\x07\x04\x0c\x04\0\x02\x0c\x02\x12\x04\
-    \xa9\x02\x1f!\nJ\n\x06\x04\x0c\x04\0\x02\r\x12\x04\xab\x02\x08\x1f\x1a:\
-    \x20Amount\x20of\x20listener\x20proxies\x20for\x20the\x20user\x20has\x20\
-    been\x20reached\n\n\x0f\n\x07\x04\x0c\x04\0\x02\r\x01\x12\x04\xab\x02\
-    \x08\x19\n\x0f\n\x07\x04\x0c\x04\0\x02\r\x02\x12\x04\xab\x02\x1c\x1e\n>\
-    \n\x04\x04\x0c\x02\0\x12\x04\xae\x02\x04#\x1a0\x20The\x20denied\x20permi\
-    ssion\x20when\x20type\x20is\x20Permission.\n\n\r\n\x05\x04\x0c\x02\0\x04\
-    \x12\x04\xae\x02\x04\x0c\n\r\n\x05\x04\x0c\x02\0\x05\x12\x04\xae\x02\r\
-    \x13\n\r\n\x05\x04\x0c\x02\0\x01\x12\x04\xae\x02\x14\x1e\n\r\n\x05\x04\
-    \x0c\x02\0\x03\x12\x04\xae\x02!\"\nd\n\x04\x04\x0c\x02\x01\x12\x04\xb1\
-    \x02\x04#\x1aV\x20channel_id\x20for\x20the\x20channel\x20where\x20the\
-    \x20permission\x20was\x20denied\x20when\x20type\x20is\n\x20Permission.\n\
-    \n\r\n\x05\x04\x0c\x02\x01\x04\x12\x04\xb1\x02\x04\x0c\n\r\n\x05\x04\x0c\
-    \x02\x01\x05\x12\x04\xb1\x02\r\x13\n\r\n\x05\x04\x0c\x02\x01\x01\x12\x04\
-    \xb1\x02\x14\x1e\n\r\n\x05\x04\x0c\x02\x01\x03\x12\x04\xb1\x02!\"\nK\n\
-    \x04\x04\x0c\x02\x02\x12\x04\xb3\x02\x04\x20\x1a=\x20The\x20user\x20who\
-    \x20was\x20denied\x20permissions,\x20identified\x20by\x20session.\n\n\r\
-    \n\x05\x04\x0c\x02\x02\x04\x12\x04\xb3\x02\x04\x0c\n\r\n\x05\x04\x0c\x02\
-    \x02\x05\x12\x04\xb3\x02\r\x13\n\r\n\x05\x04\x0c\x02\x02\x01\x12\x04\xb3\
-    \x02\x14\x1b\n\r\n\x05\x04\x0c\x02\x02\x03\x12\x04\xb3\x02\x1e\x1f\n.\n\
-    \x04\x04\x0c\x02\x03\x12\x04\xb5\x02\x04\x1f\x1a\x20\x20Textual\x20reaso\
-    n\x20for\x20the\x20denial.\n\n\r\n\x05\x04\x0c\x02\x03\x04\x12\x04\xb5\
-    \x02\x04\x0c\n\r\n\x05\x04\x0c\x02\x03\x05\x12\x04\xb5\x02\r\x13\n\r\n\
-    \x05\x04\x0c\x02\x03\x01\x12\x04\xb5\x02\x14\x1a\n\r\n\x05\x04\x0c\x02\
-    \x03\x03\x12\x04\xb5\x02\x1d\x1e\n#\n\x04\x04\x0c\x02\x04\x12\x04\xb7\
-    \x02\x04\x1f\x1a\x15\x20Type\x20of\x20the\x20denial.\n\n\r\n\x05\x04\x0c\
-    \x02\x04\x04\x12\x04\xb7\x02\x04\x0c\n\r\n\x05\x04\x0c\x02\x04\x06\x12\
-    \x04\xb7\x02\r\x15\n\r\n\x05\x04\x0c\x02\x04\x01\x12\x04\xb7\x02\x16\x1a\
-    \n\r\n\x05\x04\x0c\x02\x04\x03\x12\x04\xb7\x02\x1d\x1e\n?\n\x04\x04\x0c\
-    \x02\x05\x12\x04\xb9\x02\x04\x1d\x1a1\x20The\x20name\x20that\x20is\x20in\
-    valid\x20when\x20type\x20is\x20UserName.\n\n\r\n\x05\x04\x0c\x02\x05\x04\
-    \x12\x04\xb9\x02\x04\x0c\n\r\n\x05\x04\x0c\x02\x05\x05\x12\x04\xb9\x02\r\
-    \x13\n\r\n\x05\x04\x0c\x02\x05\x01\x12\x04\xb9\x02\x14\x18\n\r\n\x05\x04\
-    \x0c\x02\x05\x03\x12\x04\xb9\x02\x1b\x1c\n\x0c\n\x02\x04\r\x12\x06\xbc\
-    \x02\0\xe8\x02\x01\n\x0b\n\x03\x04\r\x01\x12\x04\xbc\x02\x08\x0b\n\x0e\n\
-    \x04\x04\r\x03\0\x12\x06\xbd\x02\x04\xcd\x02\x05\n\r\n\x05\x04\r\x03\0\
-    \x01\x12\x04\xbd\x02\x0c\x15\n;\n\x06\x04\r\x03\0\x02\0\x12\x04\xbf\x02\
-    \x08!\x1a+\x20Name\x20of\x20the\x20channel\x20group,\x20UTF-8\x20encoded\
-    .\n\n\x0f\n\x07\x04\r\x03\0\x02\0\x04\x12\x04\xbf\x02\x08\x10\n\x0f\n\
-    \x07\x04\r\x03\0\x02\0\x05\x12\x04\xbf\x02\x11\x17\n\x0f\n\x07\x04\r\x03\
-    \0\x02\0\x01\x12\x04\xbf\x02\x18\x1c\n\x0f\n\x07\x04\r\x03\0\x02\0\x03\
-    \x12\x04\xbf\x02\x1f\x20\nS\n\x06\x04\r\x03\0\x02\x01\x12\x04\xc1\x02\
-    \x085\x1aC\x20True\x20if\x20the\x20group\x20has\x20been\x20inherited\x20\
-    from\x20the\x20parent\x20(Read\x20only).\n\n\x0f\n\x07\x04\r\x03\0\x02\
-    \x01\x04\x12\x04\xc1\x02\x08\x10\n\x0f\n\x07\x04\r\x03\0\x02\x01\x05\x12\
-    \x04\xc1\x02\x11\x15\n\x0f\n\x07\x04\r\x03\0\x02\x01\x01\x12\x04\xc1\x02\
-    \x16\x1f\n\x0f\n\x07\x04\r\x03\0\x02\x01\x03\x12\x04\xc1\x02\"#\n\x0f\n\
-    \x07\x04\r\x03\0\x02\x01\x08\x12\x04\xc1\x02$4\n\x0f\n\x07\x04\r\x03\0\
-    \x02\x01\x07\x12\x04\xc1\x02/3\n:\n\x06\x04\r\x03\0\x02\x02\x12\x04\xc3\
-    \x02\x083\x1a*\x20True\x20if\x20the\x20group\x20members\x20are\x20inheri\
-    ted.\n\n\x0f\n\x07\x04\r\x03\0\x02\x02\x04\x12\x04\xc3\x02\x08\x10\n\x0f\
-    \n\x07\x04\r\x03\0\x02\x02\x05\x12\x04\xc3\x02\x11\x15\n\x0f\n\x07\x04\r\
-    \x03\0\x02\x02\x01\x12\x04\xc3\x02\x16\x1d\n\x0f\n\x07\x04\r\x03\0\x02\
-    \x02\x03\x12\x04\xc3\x02\x20!\n\x0f\n\x07\x04\r\x03\0\x02\x02\x08\x12\
-    \x04\xc3\x02\"2\n\x0f\n\x07\x04\r\x03\0\x02\x02\x07\x12\x04\xc3\x02-1\nE\
-    \n\x06\x04\r\x03\0\x02\x03\x12\x04\xc5\x02\x087\x1a5\x20True\x20if\x20th\
-    e\x20group\x20can\x20be\x20inherited\x20by\x20sub\x20channels.\n\n\x0f\n\
-    \x07\x04\r\x03\0\x02\x03\x04\x12\x04\xc5\x02\x08\x10\n\x0f\n\x07\x04\r\
-    \x03\0\x02\x03\x05\x12\x04\xc5\x02\x11\x15\n\x0f\n\x07\x04\r\x03\0\x02\
-    \x03\x01\x12\x04\xc5\x02\x16!\n\x0f\n\x07\x04\r\x03\0\x02\x03\x03\x12\
-    \x04\xc5\x02$%\n\x0f\n\x07\x04\r\x03\0\x02\x03\x08\x12\x04\xc5\x02&6\n\
-    \x0f\n\x07\x04\r\x03\0\x02\x03\x07\x12\x04\xc5\x0215\nQ\n\x06\x04\r\x03\
-    \0\x02\x04\x12\x04\xc7\x02\x08\x20\x1aA\x20Users\x20explicitly\x20includ\
-    ed\x20in\x20this\x20group,\x20identified\x20by\x20user_id.\n\n\x0f\n\x07\
-    \x04\r\x03\0\x02\x04\x04\x12\x04\xc7\x02\x08\x10\n\x0f\n\x07\x04\r\x03\0\
-    \x02\x04\x05\x12\x04\xc7\x02\x11\x17\n\x0f\n\x07\x04\r\x03\0\x02\x04\x01\
-    \x12\x04\xc7\x02\x18\x1b\n\x0f\n\x07\x04\r\x03\0\x02\x04\x03\x12\x04\xc7\
-    \x02\x1e\x1f\n\x83\x01\n\x06\x04\r\x03\0\x02\x05\x12\x04\xca\x02\x08#\
-    \x1as\x20Users\x20explicitly\x20removed\x20from\x20this\x20group\x20in\
-    \x20this\x20channel\x20if\x20the\x20group\n\x20has\x20been\x20inherited,\
-    \x20identified\x20by\x20user_id.\n\n\x0f\n\x07\x04\r\x03\0\x02\x05\x04\
-    \x12\x04\xca\x02\x08\x10\n\x0f\n\x07\x04\r\x03\0\x02\x05\x05\x12\x04\xca\
-    \x02\x11\x17\n\x0f\n\x07\x04\r\x03\0\x02\x05\x01\x12\x04\xca\x02\x18\x1e\
-    \n\x0f\n\x07\x04\r\x03\0\x02\x05\x03\x12\x04\xca\x02!\"\n9\n\x06\x04\r\
-    \x03\0\x02\x06\x12\x04\xcc\x02\x08.\x1a)\x20Users\x20inherited,\x20ident\
-    ified\x20by\x20user_id.\n\n\x0f\n\x07\x04\r\x03\0\x02\x06\x04\x12\x04\
-    \xcc\x02\x08\x10\n\x0f\n\x07\x04\r\x03\0\x02\x06\x05\x12\x04\xcc\x02\x11\
-    \x17\n\x0f\n\x07\x04\r\x03\0\x02\x06\x01\x12\x04\xcc\x02\x18)\n\x0f\n\
-    \x07\x04\r\x03\0\x02\x06\x03\x12\x04\xcc\x02,-\n\x0e\n\x04\x04\r\x03\x01\
-    \x12\x06\xce\x02\x04\xdd\x02\x05\n\r\n\x05\x04\r\x03\x01\x01\x12\x04\xce\
-    \x02\x0c\x13\nB\n\x06\x04\r\x03\x01\x02\0\x12\x04\xd0\x02\x086\x1a2\x20T\
-    rue\x20if\x20this\x20ACL\x20applies\x20to\x20the\x20current\x20channel.\
-    \n\n\x0f\n\x07\x04\r\x03\x01\x02\0\x04\x12\x04\xd0\x02\x08\x10\n\x0f\n\
-    \x07\x04\r\x03\x01\x02\0\x05\x12\x04\xd0\x02\x11\x15\n\x0f\n\x07\x04\r\
-    \x03\x01\x02\0\x01\x12\x04\xd0\x02\x16\x20\n\x0f\n\x07\x04\r\x03\x01\x02\
-    \0\x03\x12\x04\xd0\x02#$\n\x0f\n\x07\x04\r\x03\x01\x02\0\x08\x12\x04\xd0\
-    \x02%5\n\x0f\n\x07\x04\r\x03\x01\x02\0\x07\x12\x04\xd0\x0204\n?\n\x06\
-    \x04\r\x03\x01\x02\x01\x12\x04\xd2\x02\x086\x1a/\x20True\x20if\x20this\
-    \x20ACL\x20applies\x20to\x20the\x20sub\x20channels.\n\n\x0f\n\x07\x04\r\
-    \x03\x01\x02\x01\x04\x12\x04\xd2\x02\x08\x10\n\x0f\n\x07\x04\r\x03\x01\
-    \x02\x01\x05\x12\x04\xd2\x02\x11\x15\n\x0f\n\x07\x04\r\x03\x01\x02\x01\
-    \x01\x12\x04\xd2\x02\x16\x20\n\x0f\n\x07\x04\r\x03\x01\x02\x01\x03\x12\
-    \x04\xd2\x02#$\n\x0f\n\x07\x04\r\x03\x01\x02\x01\x08\x12\x04\xd2\x02%5\n\
-    \x0f\n\x07\x04\r\x03\x01\x02\x01\x07\x12\x04\xd2\x0204\nE\n\x06\x04\r\
-    \x03\x01\x02\x02\x12\x04\xd4\x02\x085\x1a5\x20True\x20if\x20the\x20ACL\
-    \x20has\x20been\x20inherited\x20from\x20the\x20parent.\n\n\x0f\n\x07\x04\
-    \r\x03\x01\x02\x02\x04\x12\x04\xd4\x02\x08\x10\n\x0f\n\x07\x04\r\x03\x01\
-    \x02\x02\x05\x12\x04\xd4\x02\x11\x15\n\x0f\n\x07\x04\r\x03\x01\x02\x02\
-    \x01\x12\x04\xd4\x02\x16\x1f\n\x0f\n\x07\x04\r\x03\x01\x02\x02\x03\x12\
-    \x04\xd4\x02\"#\n\x0f\n\x07\x04\r\x03\x01\x02\x02\x08\x12\x04\xd4\x02$4\
-    \n\x0f\n\x07\x04\r\x03\x01\x02\x02\x07\x12\x04\xd4\x02/3\n>\n\x06\x04\r\
-    \x03\x01\x02\x03\x12\x04\xd6\x02\x08$\x1a.\x20ID\x20of\x20the\x20user\
-    \x20that\x20is\x20affected\x20by\x20this\x20ACL.\n\n\x0f\n\x07\x04\r\x03\
-    \x01\x02\x03\x04\x12\x04\xd6\x02\x08\x10\n\x0f\n\x07\x04\r\x03\x01\x02\
-    \x03\x05\x12\x04\xd6\x02\x11\x17\n\x0f\n\x07\x04\r\x03\x01\x02\x03\x01\
-    \x12\x04\xd6\x02\x18\x1f\n\x0f\n\x07\x04\r\x03\x01\x02\x03\x03\x12\x04\
-    \xd6\x02\"#\n?\n\x06\x04\r\x03\x01\x02\x04\x12\x04\xd8\x02\x08\"\x1a/\
-    \x20ID\x20of\x20the\x20group\x20that\x20is\x20affected\x20by\x20this\x20\
-    ACL.\n\n\x0f\n\x07\x04\r\x03\x01\x02\x04\x04\x12\x04\xd8\x02\x08\x10\n\
-    \x0f\n\x07\x04\r\x03\x01\x02\x04\x05\x12\x04\xd8\x02\x11\x17\n\x0f\n\x07\
-    \x04\r\x03\x01\x02\x04\x01\x12\x04\xd8\x02\x18\x1d\n\x0f\n\x07\x04\r\x03\
-    \x01\x02\x04\x03\x12\x04\xd8\x02\x20!\nH\n\x06\x04\r\x03\x01\x02\x05\x12\
-    \x04\xda\x02\x08\"\x1a8\x20Bit\x20flag\x20field\x20of\x20the\x20permissi\
-    ons\x20granted\x20by\x20this\x20ACL.\n\n\x0f\n\x07\x04\r\x03\x01\x02\x05\
-    \x04\x12\x04\xda\x02\x08\x10\n\x0f\n\x07\x04\r\x03\x01\x02\x05\x05\x12\
-    \x04\xda\x02\x11\x17\n\x0f\n\x07\x04\r\x03\x01\x02\x05\x01\x12\x04\xda\
-    \x02\x18\x1d\n\x0f\n\x07\x04\r\x03\x01\x02\x05\x03\x12\x04\xda\x02\x20!\
-    \nG\n\x06\x04\r\x03\x01\x02\x06\x12\x04\xdc\x02\x08!\x1a7\x20Bit\x20flag\
-    \x20field\x20of\x20the\x20permissions\x20denied\x20by\x20this\x20ACL.\n\
-    \n\x0f\n\x07\x04\r\x03\x01\x02\x06\x04\x12\x04\xdc\x02\x08\x10\n\x0f\n\
-    \x07\x04\r\x03\x01\x02\x06\x05\x12\x04\xdc\x02\x11\x17\n\x0f\n\x07\x04\r\
-    \x03\x01\x02\x06\x01\x12\x04\xdc\x02\x18\x1c\n\x0f\n\x07\x04\r\x03\x01\
-    \x02\x06\x03\x12\x04\xdc\x02\x1f\x20\n?\n\x04\x04\r\x02\0\x12\x04\xdf\
-    \x02\x04#\x1a1\x20Channel\x20ID\x20of\x20the\x20channel\x20this\x20messa\
-    ge\x20affects.\n\n\r\n\x05\x04\r\x02\0\x04\x12\x04\xdf\x02\x04\x0c\n\r\n\
-    \x05\x04\r\x02\0\x05\x12\x04\xdf\x02\r\x13\n\r\n\x05\x04\r\x02\0\x01\x12\
-    \x04\xdf\x02\x14\x1e\n\r\n\x05\x04\r\x02\0\x03\x12\x04\xdf\x02!\"\n?\n\
-    \x04\x04\r\x02\x01\x12\x04\xe1\x02\x044\x1a1\x20True\x20if\x20the\x20cha\
-    nnel\x20inherits\x20its\x20parent's\x20ACLs.\n\n\r\n\x05\x04\r\x02\x01\
-    \x04\x12\x04\xe1\x02\x04\x0c\n\r\n\x05\x04\r\x02\x01\x05\x12\x04\xe1\x02\
-    \r\x11\n\r\n\x05\x04\r\x02\x01\x01\x12\x04\xe1\x02\x12\x1e\n\r\n\x05\x04\
-    \r\x02\x01\x03\x12\x04\xe1\x02!\"\n\r\n\x05\x04\r\x02\x01\x08\x12\x04\
-    \xe1\x02#3\n\r\n\x05\x04\r\x02\x01\x07\x12\x04\xe1\x02.2\n*\n\x04\x04\r\
-    \x02\x02\x12\x04\xe3\x02\x04\"\x1a\x1c\x20User\x20group\x20specification\
-    s.\n\n\r\n\x05\x04\r\x02\x02\x04\x12\x04\xe3\x02\x04\x0c\n\r\n\x05\x04\r\
-    \x02\x02\x06\x12\x04\xe3\x02\r\x16\n\r\n\x05\x04\r\x02\x02\x01\x12\x04\
-    \xe3\x02\x17\x1d\n\r\n\x05\x04\r\x02\x02\x03\x12\x04\xe3\x02\x20!\n#\n\
-    \x04\x04\r\x02\x03\x12\x04\xe5\x02\x04\x1e\x1a\x15\x20ACL\x20specificati\
-    ons.\n\n\r\n\x05\x04\r\x02\x03\x04\x12\x04\xe5\x02\x04\x0c\n\r\n\x05\x04\
-    \r\x02\x03\x06\x12\x04\xe5\x02\r\x14\n\r\n\x05\x04\r\x02\x03\x01\x12\x04\
-    \xe5\x02\x15\x19\n\r\n\x05\x04\r\x02\x03\x03\x12\x04\xe5\x02\x1c\x1d\nP\
-    \n\x04\x04\r\x02\x04\x12\x04\xe7\x02\x04.\x1aB\x20True\x20if\x20the\x20m\
-    essage\x20is\x20a\x20query\x20for\x20ACLs\x20instead\x20of\x20setting\
-    \x20them.\n\n\r\n\x05\x04\r\x02\x04\x04\x12\x04\xe7\x02\x04\x0c\n\r\n\
-    \x05\x04\r\x02\x04\x05\x12\x04\xe7\x02\r\x11\n\r\n\x05\x04\r\x02\x04\x01\
-    \x12\x04\xe7\x02\x12\x17\n\r\n\x05\x04\r\x02\x04\x03\x12\x04\xe7\x02\x1a\
-    \x1b\n\r\n\x05\x04\r\x02\x04\x08\x12\x04\xe7\x02\x1c-\n\r\n\x05\x04\r\
-    \x02\x04\x07\x12\x04\xe7\x02',\n\xe3\x01\n\x02\x04\x0e\x12\x06\xed\x02\0\
-    \xf2\x02\x01\x1a\xd4\x01\x20Client\x20may\x20use\x20this\x20message\x20t\
-    o\x20refresh\x20its\x20registered\x20user\x20information.\x20The\n\x20cl\
-    ient\x20should\x20fill\x20the\x20IDs\x20or\x20Names\x20of\x20the\x20user\
-    s\x20it\x20wants\x20to\x20refresh.\x20The\n\x20server\x20fills\x20the\
-    \x20missing\x20parts\x20and\x20sends\x20the\x20message\x20back.\n\n\x0b\
-    \n\x03\x04\x0e\x01\x12\x04\xed\x02\x08\x12\n\x19\n\x04\x04\x0e\x02\0\x12\
-    \x04\xef\x02\x04\x1c\x1a\x0b\x20user_ids.\n\n\r\n\x05\x04\x0e\x02\0\x04\
-    \x12\x04\xef\x02\x04\x0c\n\r\n\x05\x04\x0e\x02\0\x05\x12\x04\xef\x02\r\
-    \x13\n\r\n\x05\x04\x0e\x02\0\x01\x12\x04\xef\x02\x14\x17\n\r\n\x05\x04\
-    \x0e\x02\0\x03\x12\x04\xef\x02\x1a\x1b\n4\n\x04\x04\x0e\x02\x01\x12\x04\
-    \xf1\x02\x04\x1e\x1a&\x20User\x20names\x20in\x20the\x20same\x20order\x20\
-    as\x20ids.\n\n\r\n\x05\x04\x0e\x02\x01\x04\x12\x04\xf1\x02\x04\x0c\n\r\n\
-    \x05\x04\x0e\x02\x01\x05\x12\x04\xf1\x02\r\x13\n\r\n\x05\x04\x0e\x02\x01\
-    \x01\x12\x04\xf1\x02\x14\x19\n\r\n\x05\x04\x0e\x02\x01\x03\x12\x04\xf1\
-    \x02\x1c\x1d\n\xf4\x01\n\x02\x04\x0f\x12\x06\xf8\x02\0\xff\x02\x01\x1a\
-    \xe5\x01\x20Used\x20to\x20initialize\x20and\x20resync\x20the\x20UDP\x20e\
-    ncryption.\x20Either\x20side\x20may\x20request\x20a\n\x20resync\x20by\
-    \x20sending\x20the\x20message\x20without\x20any\x20values\x20filled.\x20\
-    The\x20resync\x20is\n\x20performed\x20by\x20sending\x20the\x20message\
-    \x20with\x20only\x20the\x20client\x20or\x20server\x20nonce\n\x20filled.\
-    \n\n\x0b\n\x03\x04\x0f\x01\x12\x04\xf8\x02\x08\x12\n\x1f\n\x04\x04\x0f\
-    \x02\0\x12\x04\xfa\x02\x04\x1b\x1a\x11\x20Encryption\x20key.\n\n\r\n\x05\
-    \x04\x0f\x02\0\x04\x12\x04\xfa\x02\x04\x0c\n\r\n\x05\x04\x0f\x02\0\x05\
-    \x12\x04\xfa\x02\r\x12\n\r\n\x05\x04\x0f\x02\0\x01\x12\x04\xfa\x02\x13\
-    \x16\n\r\n\x05\x04\x0f\x02\0\x03\x12\x04\xfa\x02\x19\x1a\n\x1d\n\x04\x04\
-    \x0f\x02\x01\x12\x04\xfc\x02\x04$\x1a\x0f\x20Client\x20nonce.\n\n\r\n\
-    \x05\x04\x0f\x02\x01\x04\x12\x04\xfc\x02\x04\x0c\n\r\n\x05\x04\x0f\x02\
-    \x01\x05\x12\x04\xfc\x02\r\x12\n\r\n\x05\x04\x0f\x02\x01\x01\x12\x04\xfc\
-    \x02\x13\x1f\n\r\n\x05\x04\x0f\x02\x01\x03\x12\x04\xfc\x02\"#\n\x1d\n\
-    \x04\x04\x0f\x02\x02\x12\x04\xfe\x02\x04$\x1a\x0f\x20Server\x20nonce.\n\
-    \n\r\n\x05\x04\x0f\x02\x02\x04\x12\x04\xfe\x02\x04\x0c\n\r\n\x05\x04\x0f\
-    \x02\x02\x05\x12\x04\xfe\x02\r\x12\n\r\n\x05\x04\x0f\x02\x02\x01\x12\x04\
-    \xfe\x02\x13\x1f\n\r\n\x05\x04\x0f\x02\x02\x03\x12\x04\xfe\x02\"#\nN\n\
-    \x02\x04\x10\x12\x06\x82\x03\0\x9b\x03\x01\x1a@\x20Used\x20to\x20add\x20\
-    or\x20remove\x20custom\x20context\x20menu\x20item\x20on\x20client-side.\
-    \n\n\x0b\n\x03\x04\x10\x01\x12\x04\x82\x03\x08\x1b\n\x0e\n\x04\x04\x10\
-    \x04\0\x12\x06\x83\x03\x04\x8a\x03\x05\n\r\n\x05\x04\x10\x04\0\x01\x12\
-    \x04\x83\x03\t\x10\n5\n\x06\x04\x10\x04\0\x02\0\x12\x04\x85\x03\x08\x16\
-    \x1a%\x20Action\x20is\x20applicable\x20to\x20the\x20server.\n\n\x0f\n\
-    \x07\x04\x10\x04\0\x02\0\x01\x12\x04\x85\x03\x08\x0e\n\x0f\n\x07\x04\x10\
-    \x04\0\x02\0\x02\x12\x04\x85\x03\x11\x15\n.\n\x06\x04\x10\x04\0\x02\x01\
-    \x12\x04\x87\x03\x08\x17\x1a\x1e\x20Action\x20can\x20target\x20a\x20Chan\
-    nel.\n\n\x0f\n\x07\x04\x10\x04\0\x02\x01\x01\x12\x04\x87\x03\x08\x0f\n\
-    \x0f\n\x07\x04\x10\x04\0\x02\x01\x02\x12\x04\x87\x03\x12\x16\n+\n\x06\
-    \x04\x10\x04\0\x02\x02\x12\x04\x89\x03\x08\x14\x1a\x1b\x20Action\x20can\
-    \x20target\x20a\x20User.\n\n\x0f\n\x07\x04\x10\x04\0\x02\x02\x01\x12\x04\
-    \x89\x03\x08\x0c\n\x0f\n\x07\x04\x10\x04\0\x02\x02\x02\x12\x04\x89\x03\
-    \x0f\x13\n\x0e\n\x04\x04\x10\x04\x01\x12\x06\x8b\x03\x04\x8e\x03\x05\n\r\
-    \n\x05\x04\x10\x04\x01\x01\x12\x04\x8b\x03\t\x12\n\x0e\n\x06\x04\x10\x04\
-    \x01\x02\0\x12\x04\x8c\x03\x08\x10\n\x0f\n\x07\x04\x10\x04\x01\x02\0\x01\
-    \x12\x04\x8c\x03\x08\x0b\n\x0f\n\x07\x04\x10\x04\x01\x02\0\x02\x12\x04\
-    \x8c\x03\x0e\x0f\n\x0e\n\x06\x04\x10\x04\x01\x02\x01\x12\x04\x8d\x03\x08\
-    \x13\n\x0f\n\x07\x04\x10\x04\x01\x02\x01\x01\x12\x04\x8d\x03\x08\x0e\n\
-    \x0f\n\x07\x04\x10\x04\x01\x02\x01\x02\x12\x04\x8d\x03\x11\x12\nH\n\x04\
-    \x04\x10\x02\0\x12\x04\x90\x03\x04\x1f\x1a:\x20The\x20action\x20identifi\
-    er.\x20Used\x20later\x20to\x20initiate\x20an\x20action.\n\n\r\n\x05\x04\
-    \x10\x02\0\x04\x12\x04\x90\x03\x04\x0c\n\r\n\x05\x04\x10\x02\0\x05\x12\
-    \x04\x90\x03\r\x13\n\r\n\x05\x04\x10\x02\0\x01\x12\x04\x90\x03\x14\x1a\n\
-    \r\n\x05\x04\x10\x02\0\x03\x12\x04\x90\x03\x1d\x1e\n/\n\x04\x04\x10\x02\
-    \x01\x12\x04\x92\x03\x04\x1d\x1a!\x20The\x20display\x20name\x20of\x20the\
-    \x20action.\n\n\r\n\x05\x04\x10\x02\x01\x04\x12\x04\x92\x03\x04\x0c\n\r\
-    \n\x05\x04\x10\x02\x01\x05\x12\x04\x92\x03\r\x13\n\r\n\x05\x04\x10\x02\
-    \x01\x01\x12\x04\x92\x03\x14\x18\n\r\n\x05\x04\x10\x02\x01\x03\x12\x04\
-    \x92\x03\x1b\x1c\n\x80\x01\n\x04\x04\x10\x02\x02\x12\x04\x95\x03\x04\x20\
-    \x1ar\x20Context\x20bit\x20flags\x20defining\x20where\x20the\x20action\
-    \x20should\x20be\x20displayed.\n\x20Flags\x20can\x20be\x20OR-ed\x20to\
-    \x20combine\x20different\x20types.\n\n\r\n\x05\x04\x10\x02\x02\x04\x12\
-    \x04\x95\x03\x04\x0c\n\r\n\x05\x04\x10\x02\x02\x05\x12\x04\x95\x03\r\x13\
-    \n\r\n\x05\x04\x10\x02\x02\x01\x12\x04\x95\x03\x14\x1b\n\r\n\x05\x04\x10\
-    \x02\x02\x03\x12\x04\x95\x03\x1e\x1f\n\xe8\x01\n\x04\x04\x10\x02\x03\x12\
-    \x04\x9a\x03\x04%\x1a\xd9\x01\x20Choose\x20either\x20to\x20add\x20or\x20\
-    to\x20remove\x20the\x20context\x20action.\n\x20Note:\x20This\x20field\
-    \x20only\x20exists\x20after\x20Mumble\x201.2.4-beta1\x20release.\n\x20\
-    \x20\x20\x20\x20\x20\x20The\x20message\x20will\x20be\x20recognized\x20as\
-    \x20Add\x20regardless\x20of\x20this\x20field\n\x20\x20\x20\x20\x20\x20\
-    \x20before\x20said\x20release.\n\n\r\n\x05\x04\x10\x02\x03\x04\x12\x04\
-    \x9a\x03\x04\x0c\n\r\n\x05\x04\x10\x02\x03\x06\x12\x04\x9a\x03\r\x16\n\r\
-    \n\x05\x04\x10\x02\x03\x01\x12\x04\x9a\x03\x17\x20\n\r\n\x05\x04\x10\x02\
-    \x03\x03\x12\x04\x9a\x03#$\nN\n\x02\x04\x11\x12\x06\x9e\x03\0\xa5\x03\
-    \x01\x1a@\x20Sent\x20by\x20the\x20client\x20when\x20it\x20wants\x20to\
-    \x20initiate\x20a\x20Context\x20action.\n\n\x0b\n\x03\x04\x11\x01\x12\
-    \x04\x9e\x03\x08\x15\nF\n\x04\x04\x11\x02\0\x12\x04\xa0\x03\x04\x20\x1a8\
-    \x20The\x20target\x20User\x20for\x20the\x20action,\x20identified\x20by\
-    \x20session.\n\n\r\n\x05\x04\x11\x02\0\x04\x12\x04\xa0\x03\x04\x0c\n\r\n\
-    \x05\x04\x11\x02\0\x05\x12\x04\xa0\x03\r\x13\n\r\n\x05\x04\x11\x02\0\x01\
-    \x12\x04\xa0\x03\x14\x1b\n\r\n\x05\x04\x11\x02\0\x03\x12\x04\xa0\x03\x1e\
-    \x1f\nL\n\x04\x04\x11\x02\x01\x12\x04\xa2\x03\x04#\x1a>\x20The\x20target\
-    \x20Channel\x20for\x20the\x20action,\x20identified\x20by\x20channel_id.\
-    \n\n\r\n\x05\x04\x11\x02\x01\x04\x12\x04\xa2\x03\x04\x0c\n\r\n\x05\x04\
-    \x11\x02\x01\x05\x12\x04\xa2\x03\r\x13\n\r\n\x05\x04\x11\x02\x01\x01\x12\
-    \x04\xa2\x03\x14\x1e\n\r\n\x05\x04\x11\x02\x01\x03\x12\x04\xa2\x03!\"\n3\
-    \n\x04\x04\x11\x02\x02\x12\x04\xa4\x03\x04\x1f\x1a%\x20The\x20action\x20\
-    that\x20should\x20be\x20executed.\n\n\r\n\x05\x04\x11\x02\x02\x04\x12\
-    \x04\xa4\x03\x04\x0c\n\r\n\x05\x04\x11\x02\x02\x05\x12\x04\xa4\x03\r\x13\
-    \n\r\n\x05\x04\x11\x02\x02\x01\x12\x04\xa4\x03\x14\x1a\n\r\n\x05\x04\x11\
-    \x02\x02\x03\x12\x04\xa4\x03\x1d\x1e\n+\n\x02\x04\x12\x12\x06\xa8\x03\0\
-    \xb3\x03\x01\x1a\x1d\x20Lists\x20the\x20registered\x20users.\n\n\x0b\n\
-    \x03\x04\x12\x01\x12\x04\xa8\x03\x08\x10\n\x0e\n\x04\x04\x12\x03\0\x12\
-    \x06\xa9\x03\x04\xb0\x03\x05\n\r\n\x05\x04\x12\x03\0\x01\x12\x04\xa9\x03\
-    \x0c\x10\n%\n\x06\x04\x12\x03\0\x02\0\x12\x04\xab\x03\x08$\x1a\x15\x20Re\
-    gistered\x20user\x20ID.\n\n\x0f\n\x07\x04\x12\x03\0\x02\0\x04\x12\x04\
-    \xab\x03\x08\x10\n\x0f\n\x07\x04\x12\x03\0\x02\0\x05\x12\x04\xab\x03\x11\
-    \x17\n\x0f\n\x07\x04\x12\x03\0\x02\0\x01\x12\x04\xab\x03\x18\x1f\n\x0f\n\
-    \x07\x04\x12\x03\0\x02\0\x03\x12\x04\xab\x03\"#\n'\n\x06\x04\x12\x03\0\
-    \x02\x01\x12\x04\xad\x03\x08!\x1a\x17\x20Registered\x20user\x20name.\n\n\
-    \x0f\n\x07\x04\x12\x03\0\x02\x01\x04\x12\x04\xad\x03\x08\x10\n\x0f\n\x07\
-    \x04\x12\x03\0\x02\x01\x05\x12\x04\xad\x03\x11\x17\n\x0f\n\x07\x04\x12\
-    \x03\0\x02\x01\x01\x12\x04\xad\x03\x18\x1c\n\x0f\n\x07\x04\x12\x03\0\x02\
-    \x01\x03\x12\x04\xad\x03\x1f\x20\n\x0e\n\x06\x04\x12\x03\0\x02\x02\x12\
-    \x04\xae\x03\x08&\n\x0f\n\x07\x04\x12\x03\0\x02\x02\x04\x12\x04\xae\x03\
-    \x08\x10\n\x0f\n\x07\x04\x12\x03\0\x02\x02\x05\x12\x04\xae\x03\x11\x17\n\
-    \x0f\n\x07\x04\x12\x03\0\x02\x02\x01\x12\x04\xae\x03\x18!\n\x0f\n\x07\
-    \x04\x12\x03\0\x02\x02\x03\x12\x04\xae\x03$%\n\x0e\n\x06\x04\x12\x03\0\
-    \x02\x03\x12\x04\xaf\x03\x08)\n\x0f\n\x07\x04\x12\x03\0\x02\x03\x04\x12\
-    \x04\xaf\x03\x08\x10\n\x0f\n\x07\x04\x12\x03\0\x02\x03\x05\x12\x04\xaf\
-    \x03\x11\x17\n\x0f\n\x07\x04\x12\x03\0\x02\x03\x01\x12\x04\xaf\x03\x18$\
-    \n\x0f\n\x07\x04\x12\x03\0\x02\x03\x03\x12\x04\xaf\x03'(\n+\n\x04\x04\
-    \x12\x02\0\x12\x04\xb2\x03\x04\x1c\x1a\x1d\x20A\x20list\x20of\x20registe\
-    red\x20users.\n\n\r\n\x05\x04\x12\x02\0\x04\x12\x04\xb2\x03\x04\x0c\n\r\
-    \n\x05\x04\x12\x02\0\x06\x12\x04\xb2\x03\r\x11\n\r\n\x05\x04\x12\x02\0\
-    \x01\x12\x04\xb2\x03\x12\x17\n\r\n\x05\x04\x12\x02\0\x03\x12\x04\xb2\x03\
-    \x1a\x1b\n\xc1\x01\n\x02\x04\x13\x12\x06\xb9\x03\0\xcb\x03\x01\x1a\xb2\
-    \x01\x20Sent\x20by\x20the\x20client\x20when\x20it\x20wants\x20to\x20regi\
-    ster\x20or\x20clear\x20whisper\x20targets.\n\n\x20Note:\x20The\x20first\
-    \x20available\x20target\x20ID\x20is\x201\x20as\x200\x20is\x20reserved\
-    \x20for\x20normal\n\x20talking.\x20Maximum\x20target\x20ID\x20is\x2030.\
-    \n\n\x0b\n\x03\x04\x13\x01\x12\x04\xb9\x03\x08\x13\n\x0e\n\x04\x04\x13\
-    \x03\0\x12\x06\xba\x03\x04\xc6\x03\x05\n\r\n\x05\x04\x13\x03\0\x01\x12\
-    \x04\xba\x03\x0c\x12\n5\n\x06\x04\x13\x03\0\x02\0\x12\x04\xbc\x03\x08$\
-    \x1a%\x20Users\x20that\x20are\x20included\x20as\x20targets.\n\n\x0f\n\
-    \x07\x04\x13\x03\0\x02\0\x04\x12\x04\xbc\x03\x08\x10\n\x0f\n\x07\x04\x13\
-    \x03\0\x02\0\x05\x12\x04\xbc\x03\x11\x17\n\x0f\n\x07\x04\x13\x03\0\x02\0\
-    \x01\x12\x04\xbc\x03\x18\x1f\n\x0f\n\x07\x04\x13\x03\0\x02\0\x03\x12\x04\
-    \xbc\x03\"#\n7\n\x06\x04\x13\x03\0\x02\x01\x12\x04\xbe\x03\x08'\x1a'\x20\
-    Channel\x20that\x20is\x20included\x20as\x20a\x20target.\n\n\x0f\n\x07\
-    \x04\x13\x03\0\x02\x01\x04\x12\x04\xbe\x03\x08\x10\n\x0f\n\x07\x04\x13\
-    \x03\0\x02\x01\x05\x12\x04\xbe\x03\x11\x17\n\x0f\n\x07\x04\x13\x03\0\x02\
-    \x01\x01\x12\x04\xbe\x03\x18\"\n\x0f\n\x07\x04\x13\x03\0\x02\x01\x03\x12\
-    \x04\xbe\x03%&\n9\n\x06\x04\x13\x03\0\x02\x02\x12\x04\xc0\x03\x08\"\x1a)\
-    \x20ACL\x20group\x20that\x20is\x20included\x20as\x20a\x20target.\n\n\x0f\
-    \n\x07\x04\x13\x03\0\x02\x02\x04\x12\x04\xc0\x03\x08\x10\n\x0f\n\x07\x04\
-    \x13\x03\0\x02\x02\x05\x12\x04\xc0\x03\x11\x17\n\x0f\n\x07\x04\x13\x03\0\
-    \x02\x02\x01\x12\x04\xc0\x03\x18\x1d\n\x0f\n\x07\x04\x13\x03\0\x02\x02\
-    \x03\x12\x04\xc0\x03\x20!\nS\n\x06\x04\x13\x03\0\x02\x03\x12\x04\xc2\x03\
-    \x082\x1aC\x20True\x20if\x20the\x20voice\x20should\x20follow\x20links\
-    \x20from\x20the\x20specified\x20channel.\n\n\x0f\n\x07\x04\x13\x03\0\x02\
-    \x03\x04\x12\x04\xc2\x03\x08\x10\n\x0f\n\x07\x04\x13\x03\0\x02\x03\x05\
-    \x12\x04\xc2\x03\x11\x15\n\x0f\n\x07\x04\x13\x03\0\x02\x03\x01\x12\x04\
-    \xc2\x03\x16\x1b\n\x0f\n\x07\x04\x13\x03\0\x02\x03\x03\x12\x04\xc2\x03\
-    \x1e\x1f\n\x0f\n\x07\x04\x13\x03\0\x02\x03\x08\x12\x04\xc2\x03\x201\n\
-    \x0f\n\x07\x04\x13\x03\0\x02\x03\x07\x12\x04\xc2\x03+0\n]\n\x06\x04\x13\
-    \x03\0\x02\x04\x12\x04\xc5\x03\x085\x1aM\x20True\x20if\x20the\x20voice\
-    \x20should\x20also\x20be\x20sent\x20to\x20children\x20of\x20the\x20speci\
-    fic\n\x20channel.\n\n\x0f\n\x07\x04\x13\x03\0\x02\x04\x04\x12\x04\xc5\
-    \x03\x08\x10\n\x0f\n\x07\x04\x13\x03\0\x02\x04\x05\x12\x04\xc5\x03\x11\
-    \x15\n\x0f\n\x07\x04\x13\x03\0\x02\x04\x01\x12\x04\xc5\x03\x16\x1e\n\x0f\
-    \n\x07\x04\x13\x03\0\x02\x04\x03\x12\x04\xc5\x03!\"\n\x0f\n\x07\x04\x13\
-    \x03\0\x02\x04\x08\x12\x04\xc5\x03#4\n\x0f\n\x07\x04\x13\x03\0\x02\x04\
-    \x07\x12\x04\xc5\x03.3\n\x20\n\x04\x04\x13\x02\0\x12\x04\xc8\x03\x04\x1b\
-    \x1a\x12\x20Voice\x20target\x20ID.\n\n\r\n\x05\x04\x13\x02\0\x04\x12\x04\
-    \xc8\x03\x04\x0c\n\r\n\x05\x04\x13\x02\0\x05\x12\x04\xc8\x03\r\x13\n\r\n\
-    \x05\x04\x13\x02\0\x01\x12\x04\xc8\x03\x14\x16\n\r\n\x05\x04\x13\x02\0\
-    \x03\x12\x04\xc8\x03\x19\x1a\n>\n\x04\x04\x13\x02\x01\x12\x04\xca\x03\
-    \x04\x20\x1a0\x20The\x20receivers\x20that\x20this\x20voice\x20target\x20\
-    includes.\n\n\r\n\x05\x04\x13\x02\x01\x04\x12\x04\xca\x03\x04\x0c\n\r\n\
-    \x05\x04\x13\x02\x01\x06\x12\x04\xca\x03\r\x13\n\r\n\x05\x04\x13\x02\x01\
-    \x01\x12\x04\xca\x03\x14\x1b\n\r\n\x05\x04\x13\x02\x01\x03\x12\x04\xca\
-    \x03\x1e\x1f\n\xbb\x01\n\x02\x04\x14\x12\x06\xd0\x03\0\xd8\x03\x01\x1a\
-    \xac\x01\x20Sent\x20by\x20the\x20client\x20when\x20it\x20wants\x20permis\
-    sions\x20for\x20a\x20certain\x20channel.\x20Sent\x20by\n\x20the\x20serve\
-    r\x20when\x20it\x20replies\x20to\x20the\x20query\x20or\x20wants\x20the\
-    \x20user\x20to\x20resync\x20all\n\x20channel\x20permissions.\n\n\x0b\n\
-    \x03\x04\x14\x01\x12\x04\xd0\x03\x08\x17\nP\n\x04\x04\x14\x02\0\x12\x04\
-    \xd2\x03\x04#\x1aB\x20channel_id\x20of\x20the\x20channel\x20for\x20which\
-    \x20the\x20permissions\x20are\x20queried.\n\n\r\n\x05\x04\x14\x02\0\x04\
-    \x12\x04\xd2\x03\x04\x0c\n\r\n\x05\x04\x14\x02\0\x05\x12\x04\xd2\x03\r\
-    \x13\n\r\n\x05\x04\x14\x02\0\x01\x12\x04\xd2\x03\x14\x1e\n\r\n\x05\x04\
-    \x14\x02\0\x03\x12\x04\xd2\x03!\"\n$\n\x04\x04\x14\x02\x01\x12\x04\xd4\
-    \x03\x04$\x1a\x16\x20Channel\x20permissions.\n\n\r\n\x05\x04\x14\x02\x01\
-    \x04\x12\x04\xd4\x03\x04\x0c\n\r\n\x05\x04\x14\x02\x01\x05\x12\x04\xd4\
-    \x03\r\x13\n\r\n\x05\x04\x14\x02\x01\x01\x12\x04\xd4\x03\x14\x1f\n\r\n\
-    \x05\x04\x14\x02\x01\x03\x12\x04\xd4\x03\"#\nd\n\x04\x04\x14\x02\x02\x12\
-    \x04\xd7\x03\x04.\x1aV\x20True\x20if\x20the\x20client\x20should\x20drop\
-    \x20its\x20current\x20permission\x20information\x20for\x20all\n\x20chann\
-    els.\n\n\r\n\x05\x04\x14\x02\x02\x04\x12\x04\xd7\x03\x04\x0c\n\r\n\x05\
-    \x04\x14\x02\x02\x05\x12\x04\xd7\x03\r\x11\n\r\n\x05\x04\x14\x02\x02\x01\
-    \x12\x04\xd7\x03\x12\x17\n\r\n\x05\x04\x14\x02\x02\x03\x12\x04\xd7\x03\
-    \x1a\x1b\n\r\n\x05\x04\x14\x02\x02\x08\x12\x04\xd7\x03\x1c-\n\r\n\x05\
-    \x04\x14\x02\x02\x07\x12\x04\xd7\x03',\n\xa5\x01\n\x02\x04\x15\x12\x06\
-    \xdc\x03\0\xe4\x03\x01\x1a\x96\x01\x20Sent\x20by\x20the\x20server\x20to\
-    \x20notify\x20the\x20users\x20of\x20the\x20version\x20of\x20the\x20CELT\
-    \x20codec\x20they\n\x20should\x20use.\x20This\x20may\x20change\x20during\
-    \x20the\x20connection\x20when\x20new\x20users\x20join.\n\n\x0b\n\x03\x04\
-    \x15\x01\x12\x04\xdc\x03\x08\x14\n4\n\x04\x04\x15\x02\0\x12\x04\xde\x03\
-    \x04\x1d\x1a&\x20The\x20version\x20of\x20the\x20CELT\x20Alpha\x20codec.\
-    \n\n\r\n\x05\x04\x15\x02\0\x04\x12\x04\xde\x03\x04\x0c\n\r\n\x05\x04\x15\
-    \x02\0\x05\x12\x04\xde\x03\r\x12\n\r\n\x05\x04\x15\x02\0\x01\x12\x04\xde\
-    \x03\x13\x18\n\r\n\x05\x04\x15\x02\0\x03\x12\x04\xde\x03\x1b\x1c\n3\n\
-    \x04\x04\x15\x02\x01\x12\x04\xe0\x03\x04\x1c\x1a%\x20The\x20version\x20o\
-    f\x20the\x20CELT\x20Beta\x20codec.\n\n\r\n\x05\x04\x15\x02\x01\x04\x12\
-    \x04\xe0\x03\x04\x0c\n\r\n\x05\x04\x15\x02\x01\x05\x12\x04\xe0\x03\r\x12\
-    \n\r\n\x05\x04\x15\x02\x01\x01\x12\x04\xe0\x03\x13\x17\n\r\n\x05\x04\x15\
-    \x02\x01\x03\x12\x04\xe0\x03\x1a\x1b\n?\n\x04\x04\x15\x02\x02\x12\x04\
-    \xe2\x03\x044\x1a1\x20True\x20if\x20the\x20user\x20should\x20prefer\x20A\
-    lpha\x20over\x20Beta.\n\n\r\n\x05\x04\x15\x02\x02\x04\x12\x04\xe2\x03\
-    \x04\x0c\n\r\n\x05\x04\x15\x02\x02\x05\x12\x04\xe2\x03\r\x11\n\r\n\x05\
-    \x04\x15\x02\x02\x01\x12\x04\xe2\x03\x12\x1e\n\r\n\x05\x04\x15\x02\x02\
-    \x03\x12\x04\xe2\x03!\"\n\r\n\x05\x04\x15\x02\x02\x08\x12\x04\xe2\x03#3\
-    \n\r\n\x05\x04\x15\x02\x02\x07\x12\x04\xe2\x03.2\n\x0c\n\x04\x04\x15\x02\
-    \x03\x12\x04\xe3\x03\x04-\n\r\n\x05\x04\x15\x02\x03\x04\x12\x04\xe3\x03\
-    \x04\x0c\n\r\n\x05\x04\x15\x02\x03\x05\x12\x04\xe3\x03\r\x11\n\r\n\x05\
-    \x04\x15\x02\x03\x01\x12\x04\xe3\x03\x12\x16\n\r\n\x05\x04\x15\x02\x03\
-    \x03\x12\x04\xe3\x03\x19\x1a\n\r\n\x05\x04\x15\x02\x03\x08\x12\x04\xe3\
-    \x03\x1b,\n\r\n\x05\x04\x15\x02\x03\x07\x12\x04\xe3\x03&+\nN\n\x02\x04\
-    \x16\x12\x06\xe7\x03\0\x9b\x04\x01\x1a@\x20Used\x20to\x20communicate\x20\
-    user\x20stats\x20between\x20the\x20server\x20and\x20clients.\n\n\x0b\n\
-    \x03\x04\x16\x01\x12\x04\xe7\x03\x08\x11\n\x0e\n\x04\x04\x16\x03\0\x12\
-    \x06\xe8\x03\x04\xf1\x03\x05\n\r\n\x05\x04\x16\x03\0\x01\x12\x04\xe8\x03\
-    \x0c\x11\n6\n\x06\x04\x16\x03\0\x02\0\x12\x04\xea\x03\x08!\x1a&\x20The\
-    \x20amount\x20of\x20good\x20packets\x20received.\n\n\x0f\n\x07\x04\x16\
-    \x03\0\x02\0\x04\x12\x04\xea\x03\x08\x10\n\x0f\n\x07\x04\x16\x03\0\x02\0\
-    \x05\x12\x04\xea\x03\x11\x17\n\x0f\n\x07\x04\x16\x03\0\x02\0\x01\x12\x04\
-    \xea\x03\x18\x1c\n\x0f\n\x07\x04\x16\x03\0\x02\0\x03\x12\x04\xea\x03\x1f\
-    \x20\n6\n\x06\x04\x16\x03\0\x02\x01\x12\x04\xec\x03\x08!\x1a&\x20The\x20\
-    amount\x20of\x20late\x20packets\x20received.\n\n\x0f\n\x07\x04\x16\x03\0\
-    \x02\x01\x04\x12\x04\xec\x03\x08\x10\n\x0f\n\x07\x04\x16\x03\0\x02\x01\
-    \x05\x12\x04\xec\x03\x11\x17\n\x0f\n\x07\x04\x16\x03\0\x02\x01\x01\x12\
-    \x04\xec\x03\x18\x1c\n\x0f\n\x07\x04\x16\x03\0\x02\x01\x03\x12\x04\xec\
-    \x03\x1f\x20\n7\n\x06\x04\x16\x03\0\x02\x02\x12\x04\xee\x03\x08!\x1a'\
-    \x20The\x20amount\x20of\x20packets\x20never\x20received.\n\n\x0f\n\x07\
-    \x04\x16\x03\0\x02\x02\x04\x12\x04\xee\x03\x08\x10\n\x0f\n\x07\x04\x16\
-    \x03\0\x02\x02\x05\x12\x04\xee\x03\x11\x17\n\x0f\n\x07\x04\x16\x03\0\x02\
-    \x02\x01\x12\x04\xee\x03\x18\x1c\n\x0f\n\x07\x04\x16\x03\0\x02\x02\x03\
-    \x12\x04\xee\x03\x1f\x20\n.\n\x06\x04\x16\x03\0\x02\x03\x12\x04\xf0\x03\
-    \x08#\x1a\x1e\x20The\x20amount\x20of\x20nonce\x20resyncs.\n\n\x0f\n\x07\
-    \x04\x16\x03\0\x02\x03\x04\x12\x04\xf0\x03\x08\x10\n\x0f\n\x07\x04\x16\
-    \x03\0\x02\x03\x05\x12\x04\xf0\x03\x11\x17\n\x0f\n\x07\x04\x16\x03\0\x02\
-    \x03\x01\x12\x04\xf0\x03\x18\x1e\n\x0f\n\x07\x04\x16\x03\0\x02\x03\x03\
-    \x12\x04\xf0\x03!\"\n+\n\x04\x04\x16\x02\0\x12\x04\xf4\x03\x04\x20\x1a\
-    \x1d\x20User\x20whose\x20stats\x20these\x20are.\n\n\r\n\x05\x04\x16\x02\
-    \0\x04\x12\x04\xf4\x03\x04\x0c\n\r\n\x05\x04\x16\x02\0\x05\x12\x04\xf4\
-    \x03\r\x13\n\r\n\x05\x04\x16\x02\0\x01\x12\x04\xf4\x03\x14\x1b\n\r\n\x05\
-    \x04\x16\x02\0\x03\x12\x04\xf4\x03\x1e\x1f\nP\n\x04\x04\x16\x02\x01\x12\
-    \x04\xf6\x03\x043\x1aB\x20True\x20if\x20the\x20message\x20contains\x20on\
-    ly\x20mutable\x20stats\x20(packets,\x20ping).\n\n\r\n\x05\x04\x16\x02\
-    \x01\x04\x12\x04\xf6\x03\x04\x0c\n\r\n\x05\x04\x16\x02\x01\x05\x12\x04\
-    \xf6\x03\r\x11\n\r\n\x05\x04\x16\x02\x01\x01\x12\x04\xf6\x03\x12\x1c\n\r\
-    \n\x05\x04\x16\x02\x01\x03\x12\x04\xf6\x03\x1f\x20\n\r\n\x05\x04\x16\x02\
-    \x01\x08\x12\x04\xf6\x03!2\n\r\n\x05\x04\x16\x02\x01\x07\x12\x04\xf6\x03\
-    ,1\nR\n\x04\x04\x16\x02\x02\x12\x04\xf8\x03\x04$\x1aD\x20Full\x20user\
-    \x20certificate\x20chain\x20of\x20the\x20user\x20certificate\x20in\x20DE\
-    R\x20format.\n\n\r\n\x05\x04\x16\x02\x02\x04\x12\x04\xf8\x03\x04\x0c\n\r\
-    \n\x05\x04\x16\x02\x02\x05\x12\x04\xf8\x03\r\x12\n\r\n\x05\x04\x16\x02\
-    \x02\x01\x12\x04\xf8\x03\x13\x1f\n\r\n\x05\x04\x16\x02\x02\x03\x12\x04\
-    \xf8\x03\"#\nG\n\x04\x04\x16\x02\x03\x12\x04\xfa\x03\x04#\x1a9\x20Packet\
-    \x20statistics\x20for\x20packets\x20received\x20from\x20the\x20client.\n\
-    \n\r\n\x05\x04\x16\x02\x03\x04\x12\x04\xfa\x03\x04\x0c\n\r\n\x05\x04\x16\
-    \x02\x03\x06\x12\x04\xfa\x03\r\x12\n\r\n\x05\x04\x16\x02\x03\x01\x12\x04\
-    \xfa\x03\x13\x1e\n\r\n\x05\x04\x16\x02\x03\x03\x12\x04\xfa\x03!\"\nA\n\
-    \x04\x04\x16\x02\x04\x12\x04\xfc\x03\x04#\x1a3\x20Packet\x20statistics\
-    \x20for\x20packets\x20sent\x20by\x20the\x20server.\n\n\r\n\x05\x04\x16\
-    \x02\x04\x04\x12\x04\xfc\x03\x04\x0c\n\r\n\x05\x04\x16\x02\x04\x06\x12\
-    \x04\xfc\x03\r\x12\n\r\n\x05\x04\x16\x02\x04\x01\x12\x04\xfc\x03\x13\x1e\
-    \n\r\n\x05\x04\x16\x02\x04\x03\x12\x04\xfc\x03!\"\n+\n\x04\x04\x16\x02\
-    \x05\x12\x04\xff\x03\x04$\x1a\x1d\x20Amount\x20of\x20UDP\x20packets\x20s\
-    ent.\n\n\r\n\x05\x04\x16\x02\x05\x04\x12\x04\xff\x03\x04\x0c\n\r\n\x05\
-    \x04\x16\x02\x05\x05\x12\x04\xff\x03\r\x13\n\r\n\x05\x04\x16\x02\x05\x01\
-    \x12\x04\xff\x03\x14\x1f\n\r\n\x05\x04\x16\x02\x05\x03\x12\x04\xff\x03\"\
-    #\n+\n\x04\x04\x16\x02\x06\x12\x04\x81\x04\x04$\x1a\x1d\x20Amount\x20of\
-    \x20TCP\x20packets\x20sent.\n\n\r\n\x05\x04\x16\x02\x06\x04\x12\x04\x81\
-    \x04\x04\x0c\n\r\n\x05\x04\x16\x02\x06\x05\x12\x04\x81\x04\r\x13\n\r\n\
-    \x05\x04\x16\x02\x06\x01\x12\x04\x81\x04\x14\x1f\n\r\n\x05\x04\x16\x02\
-    \x06\x03\x12\x04\x81\x04\"#\n!\n\x04\x04\x16\x02\x07\x12\x04\x83\x04\x04\
-    $\x1a\x13\x20UDP\x20ping\x20average.\n\n\r\n\x05\x04\x16\x02\x07\x04\x12\
-    \x04\x83\x04\x04\x0c\n\r\n\x05\x04\x16\x02\x07\x05\x12\x04\x83\x04\r\x12\
-    \n\r\n\x05\x04\x16\x02\x07\x01\x12\x04\x83\x04\x13\x1f\n\r\n\x05\x04\x16\
-    \x02\x07\x03\x12\x04\x83\x04\"#\n\"\n\x04\x04\x16\x02\x08\x12\x04\x85\
-    \x04\x04$\x1a\x14\x20UDP\x20ping\x20variance.\n\n\r\n\x05\x04\x16\x02\
-    \x08\x04\x12\x04\x85\x04\x04\x0c\n\r\n\x05\x04\x16\x02\x08\x05\x12\x04\
-    \x85\x04\r\x12\n\r\n\x05\x04\x16\x02\x08\x01\x12\x04\x85\x04\x13\x1f\n\r\
-    \n\x05\x04\x16\x02\x08\x03\x12\x04\x85\x04\"#\n!\n\x04\x04\x16\x02\t\x12\
-    \x04\x87\x04\x04%\x1a\x13\x20TCP\x20ping\x20average.\n\n\r\n\x05\x04\x16\
-    \x02\t\x04\x12\x04\x87\x04\x04\x0c\n\r\n\x05\x04\x16\x02\t\x05\x12\x04\
-    \x87\x04\r\x12\n\r\n\x05\x04\x16\x02\t\x01\x12\x04\x87\x04\x13\x1f\n\r\n\
-    \x05\x04\x16\x02\t\x03\x12\x04\x87\x04\"$\n\"\n\x04\x04\x16\x02\n\x12\
-    \x04\x89\x04\x04%\x1a\x14\x20TCP\x20ping\x20variance.\n\n\r\n\x05\x04\
-    \x16\x02\n\x04\x12\x04\x89\x04\x04\x0c\n\r\n\x05\x04\x16\x02\n\x05\x12\
-    \x04\x89\x04\r\x12\n\r\n\x05\x04\x16\x02\n\x01\x12\x04\x89\x04\x13\x1f\n\
-    \r\n\x05\x04\x16\x02\n\x03\x12\x04\x89\x04\"$\n\x1f\n\x04\x04\x16\x02\
-    \x0b\x12\x04\x8c\x04\x04\"\x1a\x11\x20Client\x20version.\n\n\r\n\x05\x04\
-    \x16\x02\x0b\x04\x12\x04\x8c\x04\x04\x0c\n\r\n\x05\x04\x16\x02\x0b\x06\
-    \x12\x04\x8c\x04\r\x14\n\r\n\x05\x04\x16\x02\x0b\x01\x12\x04\x8c\x04\x15\
-    \x1c\n\r\n\x05\x04\x16\x02\x0b\x03\x12\x04\x8c\x04\x1f!\na\n\x04\x04\x16\
-    \x02\x0c\x12\x04\x8f\x04\x04&\x1aS\x20A\x20list\x20of\x20CELT\x20bitstre\
-    am\x20version\x20constants\x20supported\x20by\x20the\x20client\x20of\x20\
-    this\n\x20user.\n\n\r\n\x05\x04\x16\x02\x0c\x04\x12\x04\x8f\x04\x04\x0c\
-    \n\r\n\x05\x04\x16\x02\x0c\x05\x12\x04\x8f\x04\r\x12\n\r\n\x05\x04\x16\
-    \x02\x0c\x01\x12\x04\x8f\x04\x13\x20\n\r\n\x05\x04\x16\x02\x0c\x03\x12\
-    \x04\x8f\x04#%\n\"\n\x04\x04\x16\x02\r\x12\x04\x91\x04\x04\x20\x1a\x14\
-    \x20Client\x20IP\x20address.\n\n\r\n\x05\x04\x16\x02\r\x04\x12\x04\x91\
-    \x04\x04\x0c\n\r\n\x05\x04\x16\x02\r\x05\x12\x04\x91\x04\r\x12\n\r\n\x05\
-    \x04\x16\x02\r\x01\x12\x04\x91\x04\x13\x1a\n\r\n\x05\x04\x16\x02\r\x03\
-    \x12\x04\x91\x04\x1d\x1f\n.\n\x04\x04\x16\x02\x0e\x12\x04\x93\x04\x04#\
-    \x1a\x20\x20Bandwidth\x20used\x20by\x20this\x20client.\n\n\r\n\x05\x04\
-    \x16\x02\x0e\x04\x12\x04\x93\x04\x04\x0c\n\r\n\x05\x04\x16\x02\x0e\x05\
-    \x12\x04\x93\x04\r\x13\n\r\n\x05\x04\x16\x02\x0e\x01\x12\x04\x93\x04\x14\
-    \x1d\n\r\n\x05\x04\x16\x02\x0e\x03\x12\x04\x93\x04\x20\"\n$\n\x04\x04\
-    \x16\x02\x0f\x12\x04\x95\x04\x04$\x1a\x16\x20Connection\x20duration.\n\n\
-    \r\n\x05\x04\x16\x02\x0f\x04\x12\x04\x95\x04\x04\x0c\n\r\n\x05\x04\x16\
-    \x02\x0f\x05\x12\x04\x95\x04\r\x13\n\r\n\x05\x04\x16\x02\x0f\x01\x12\x04\
-    \x95\x04\x14\x1e\n\r\n\x05\x04\x16\x02\x0f\x03\x12\x04\x95\x04!#\n-\n\
-    \x04\x04\x16\x02\x10\x12\x04\x97\x04\x04\"\x1a\x1f\x20Duration\x20since\
-    \x20last\x20activity.\n\n\r\n\x05\x04\x16\x02\x10\x04\x12\x04\x97\x04\
-    \x04\x0c\n\r\n\x05\x04\x16\x02\x10\x05\x12\x04\x97\x04\r\x13\n\r\n\x05\
-    \x04\x16\x02\x10\x01\x12\x04\x97\x04\x14\x1c\n\r\n\x05\x04\x16\x02\x10\
-    \x03\x12\x04\x97\x04\x1f!\n:\n\x04\x04\x16\x02\x11\x12\x04\x99\x04\x04<\
-    \x1a,\x20True\x20if\x20the\x20user\x20has\x20a\x20strong\x20certificate.\
-    \n\n\r\n\x05\x04\x16\x02\x11\x04\x12\x04\x99\x04\x04\x0c\n\r\n\x05\x04\
-    \x16\x02\x11\x05\x12\x04\x99\x04\r\x11\n\r\n\x05\x04\x16\x02\x11\x01\x12\
-    \x04\x99\x04\x12$\n\r\n\x05\x04\x16\x02\x11\x03\x12\x04\x99\x04')\n\r\n\
-    \x05\x04\x16\x02\x11\x08\x12\x04\x99\x04*;\n\r\n\x05\x04\x16\x02\x11\x07\
-    \x12\x04\x99\x045:\n\x0c\n\x04\x04\x16\x02\x12\x12\x04\x9a\x04\x04.\n\r\
-    \n\x05\x04\x16\x02\x12\x04\x12\x04\x9a\x04\x04\x0c\n\r\n\x05\x04\x16\x02\
-    \x12\x05\x12\x04\x9a\x04\r\x11\n\r\n\x05\x04\x16\x02\x12\x01\x12\x04\x9a\
-    \x04\x12\x16\n\r\n\x05\x04\x16\x02\x12\x03\x12\x04\x9a\x04\x19\x1b\n\r\n\
-    \x05\x04\x16\x02\x12\x08\x12\x04\x9a\x04\x1c-\n\r\n\x05\x04\x16\x02\x12\
-    \x07\x12\x04\x9a\x04',\n\xb8\x04\n\x02\x04\x17\x12\x06\xa5\x04\0\xac\x04\
-    \x01\x1a\xa9\x04\x20Used\x20by\x20the\x20client\x20to\x20request\x20bina\
-    ry\x20data\x20from\x20the\x20server.\x20By\x20default\x20large\n\x20comm\
-    ents\x20or\x20textures\x20are\x20not\x20sent\x20within\x20standard\x20me\
-    ssages\x20but\x20instead\x20the\n\x20hash\x20is.\x20If\x20the\x20client\
-    \x20does\x20not\x20recognize\x20the\x20hash\x20it\x20may\x20request\x20t\
-    he\n\x20resource\x20when\x20it\x20needs\x20it.\x20The\x20client\x20does\
-    \x20so\x20by\x20sending\x20a\x20RequestBlob\n\x20message\x20with\x20the\
-    \x20correct\x20fields\x20filled\x20with\x20the\x20user\x20sessions\x20or\
-    \x20channel_ids\n\x20it\x20wants\x20to\x20receive.\x20The\x20server\x20r\
-    eplies\x20to\x20this\x20by\x20sending\x20a\x20new\n\x20UserState/Channel\
-    State\x20message\x20with\x20the\x20resources\x20filled\x20even\x20if\x20\
-    they\x20would\n\x20normally\x20be\x20transmitted\x20as\x20hashes.\n\n\
-    \x0b\n\x03\x04\x17\x01\x12\x04\xa5\x04\x08\x13\n=\n\x04\x04\x17\x02\0\
-    \x12\x04\xa7\x04\x04(\x1a/\x20sessions\x20of\x20the\x20requested\x20User\
-    State\x20textures.\n\n\r\n\x05\x04\x17\x02\0\x04\x12\x04\xa7\x04\x04\x0c\
-    \n\r\n\x05\x04\x17\x02\0\x05\x12\x04\xa7\x04\r\x13\n\r\n\x05\x04\x17\x02\
-    \0\x01\x12\x04\xa7\x04\x14#\n\r\n\x05\x04\x17\x02\0\x03\x12\x04\xa7\x04&\
-    '\n=\n\x04\x04\x17\x02\x01\x12\x04\xa9\x04\x04(\x1a/\x20sessions\x20of\
-    \x20the\x20requested\x20UserState\x20comments.\n\n\r\n\x05\x04\x17\x02\
-    \x01\x04\x12\x04\xa9\x04\x04\x0c\n\r\n\x05\x04\x17\x02\x01\x05\x12\x04\
-    \xa9\x04\r\x13\n\r\n\x05\x04\x17\x02\x01\x01\x12\x04\xa9\x04\x14#\n\r\n\
-    \x05\x04\x17\x02\x01\x03\x12\x04\xa9\x04&'\nG\n\x04\x04\x17\x02\x02\x12\
-    \x04\xab\x04\x04,\x1a9\x20channel_ids\x20of\x20the\x20requested\x20Chann\
-    elState\x20descriptions.\n\n\r\n\x05\x04\x17\x02\x02\x04\x12\x04\xab\x04\
-    \x04\x0c\n\r\n\x05\x04\x17\x02\x02\x05\x12\x04\xab\x04\r\x13\n\r\n\x05\
-    \x04\x17\x02\x02\x01\x12\x04\xab\x04\x14'\n\r\n\x05\x04\x17\x02\x02\x03\
-    \x12\x04\xab\x04*+\n`\n\x02\x04\x18\x12\x06\xb0\x04\0\xbd\x04\x01\x1aR\
-    \x20Sent\x20by\x20the\x20server\x20when\x20it\x20informs\x20the\x20clien\
-    ts\x20on\x20server\x20configuration\n\x20details.\n\n\x0b\n\x03\x04\x18\
-    \x01\x12\x04\xb0\x04\x08\x14\n=\n\x04\x04\x18\x02\0\x12\x04\xb2\x04\x04&\
-    \x1a/\x20The\x20maximum\x20bandwidth\x20the\x20clients\x20should\x20use.\
-    \n\n\r\n\x05\x04\x18\x02\0\x04\x12\x04\xb2\x04\x04\x0c\n\r\n\x05\x04\x18\
-    \x02\0\x05\x12\x04\xb2\x04\r\x13\n\r\n\x05\x04\x18\x02\0\x01\x12\x04\xb2\
-    \x04\x14!\n\r\n\x05\x04\x18\x02\0\x03\x12\x04\xb2\x04$%\n$\n\x04\x04\x18\
-    \x02\x01\x12\x04\xb4\x04\x04%\x1a\x16\x20Server\x20welcome\x20text.\n\n\
-    \r\n\x05\x04\x18\x02\x01\x04\x12\x04\xb4\x04\x04\x0c\n\r\n\x05\x04\x18\
-    \x02\x01\x05\x12\x04\xb4\x04\r\x13\n\r\n\x05\x04\x18\x02\x01\x01\x12\x04\
-    \xb4\x04\x14\x20\n\r\n\x05\x04\x18\x02\x01\x03\x12\x04\xb4\x04#$\n/\n\
-    \x04\x04\x18\x02\x02\x12\x04\xb6\x04\x04!\x1a!\x20True\x20if\x20the\x20s\
-    erver\x20allows\x20HTML.\n\n\r\n\x05\x04\x18\x02\x02\x04\x12\x04\xb6\x04\
-    \x04\x0c\n\r\n\x05\x04\x18\x02\x02\x05\x12\x04\xb6\x04\r\x11\n\r\n\x05\
-    \x04\x18\x02\x02\x01\x12\x04\xb6\x04\x12\x1c\n\r\n\x05\x04\x18\x02\x02\
-    \x03\x12\x04\xb6\x04\x1f\x20\n,\n\x04\x04\x18\x02\x03\x12\x04\xb8\x04\
-    \x04'\x1a\x1e\x20Maximum\x20text\x20message\x20length.\n\n\r\n\x05\x04\
-    \x18\x02\x03\x04\x12\x04\xb8\x04\x04\x0c\n\r\n\x05\x04\x18\x02\x03\x05\
-    \x12\x04\xb8\x04\r\x13\n\r\n\x05\x04\x18\x02\x03\x01\x12\x04\xb8\x04\x14\
-    \"\n\r\n\x05\x04\x18\x02\x03\x03\x12\x04\xb8\x04%&\n-\n\x04\x04\x18\x02\
-    \x04\x12\x04\xba\x04\x04-\x1a\x1f\x20Maximum\x20image\x20message\x20leng\
-    th.\n\n\r\n\x05\x04\x18\x02\x04\x04\x12\x04\xba\x04\x04\x0c\n\r\n\x05\
-    \x04\x18\x02\x04\x05\x12\x04\xba\x04\r\x13\n\r\n\x05\x04\x18\x02\x04\x01\
-    \x12\x04\xba\x04\x14(\n\r\n\x05\x04\x18\x02\x04\x03\x12\x04\xba\x04+,\nB\
-    \n\x04\x04\x18\x02\x05\x12\x04\xbc\x04\x04\"\x1a4\x20The\x20maximum\x20n\
-    umber\x20of\x20users\x20allowed\x20on\x20the\x20server.\n\n\r\n\x05\x04\
-    \x18\x02\x05\x04\x12\x04\xbc\x04\x04\x0c\n\r\n\x05\x04\x18\x02\x05\x05\
-    \x12\x04\xbc\x04\r\x13\n\r\n\x05\x04\x18\x02\x05\x01\x12\x04\xbc\x04\x14\
-    \x1d\n\r\n\x05\x04\x18\x02\x05\x03\x12\x04\xbc\x04\x20!\n\x82\x01\n\x02\
-    \x04\x19\x12\x06\xc1\x04\0\xc9\x04\x01\x1at\x20Sent\x20by\x20the\x20serv\
-    er\x20to\x20inform\x20the\x20clients\x20of\x20suggested\x20client\x20con\
-    figuration\n\x20specified\x20by\x20the\x20server\x20administrator.\n\n\
-    \x0b\n\x03\x04\x19\x01\x12\x04\xc1\x04\x08\x15\n)\n\x04\x04\x19\x02\0\
-    \x12\x04\xc3\x04\x04\x20\x1a\x1b\x20Suggested\x20client\x20version.\n\n\
-    \r\n\x05\x04\x19\x02\0\x04\x12\x04\xc3\x04\x04\x0c\n\r\n\x05\x04\x19\x02\
-    \0\x05\x12\x04\xc3\x04\r\x13\n\r\n\x05\x04\x19\x02\0\x01\x12\x04\xc3\x04\
-    \x14\x1b\n\r\n\x05\x04\x19\x02\0\x03\x12\x04\xc3\x04\x1e\x1f\n_\n\x04\
-    \x04\x19\x02\x01\x12\x04\xc6\x04\x04!\x1aQ\x20True\x20if\x20the\x20admin\
-    istrator\x20suggests\x20positional\x20audio\x20to\x20be\x20used\x20on\
-    \x20this\n\x20server.\n\n\r\n\x05\x04\x19\x02\x01\x04\x12\x04\xc6\x04\
-    \x04\x0c\n\r\n\x05\x04\x19\x02\x01\x05\x12\x04\xc6\x04\r\x11\n\r\n\x05\
-    \x04\x19\x02\x01\x01\x12\x04\xc6\x04\x12\x1c\n\r\n\x05\x04\x19\x02\x01\
-    \x03\x12\x04\xc6\x04\x1f\x20\nZ\n\x04\x04\x19\x02\x02\x12\x04\xc8\x04\
-    \x04#\x1aL\x20True\x20if\x20the\x20administrator\x20suggests\x20push\x20\
-    to\x20talk\x20to\x20be\x20used\x20on\x20this\x20server.\n\n\r\n\x05\x04\
-    \x19\x02\x02\x04\x12\x04\xc8\x04\x04\x0c\n\r\n\x05\x04\x19\x02\x02\x05\
-    \x12\x04\xc8\x04\r\x11\n\r\n\x05\x04\x19\x02\x02\x01\x12\x04\xc8\x04\x12\
-    \x1e\n\r\n\x05\x04\x19\x02\x02\x03\x12\x04\xc8\x04!\"\
+    \n\x0cmumble.proto\x12\x0bMumbleProto\"\x9c\x01\n\x07Version\x12\x1f\n\n\
+    version_v1\x18\x01\x20\x01(\rR\tversionV1B\0\x12\x1f\n\nversion_v2\x18\
+    \x05\x20\x01(\x04R\tversionV2B\0\x12\x1a\n\x07release\x18\x02\x20\x01(\t\
+    R\x07releaseB\0\x12\x10\n\x02os\x18\x03\x20\x01(\tR\x02osB\0\x12\x1f\n\n\
+    os_version\x18\x04\x20\x01(\tR\tosVersionB\0:\0\"'\n\tUDPTunnel\x12\x18\
+    \n\x06packet\x18\x01\x20\x02(\x0cR\x06packetB\0:\0\"\xaa\x01\n\x0cAuthen\
+    ticate\x12\x1c\n\x08username\x18\x01\x20\x01(\tR\x08usernameB\0\x12\x1c\
+    \n\x08password\x18\x02\x20\x01(\tR\x08passwordB\0\x12\x18\n\x06tokens\
+    \x18\x03\x20\x03(\tR\x06tokensB\0\x12%\n\rcelt_versions\x18\x04\x20\x03(\
+    \x05R\x0cceltVersionsB\0\x12\x1b\n\x04opus\x18\x05\x20\x01(\x08:\x05fals\
+    eR\x04opusB\0:\0\"\xda\x02\n\x04Ping\x12\x1e\n\ttimestamp\x18\x01\x20\
+    \x01(\x04R\ttimestampB\0\x12\x14\n\x04good\x18\x02\x20\x01(\rR\x04goodB\
+    \0\x12\x14\n\x04late\x18\x03\x20\x01(\rR\x04lateB\0\x12\x14\n\x04lost\
+    \x18\x04\x20\x01(\rR\x04lostB\0\x12\x18\n\x06resync\x18\x05\x20\x01(\rR\
+    \x06resyncB\0\x12!\n\x0budp_packets\x18\x06\x20\x01(\rR\nudpPacketsB\0\
+    \x12!\n\x0btcp_packets\x18\x07\x20\x01(\rR\ntcpPacketsB\0\x12\"\n\x0cudp\
+    _ping_avg\x18\x08\x20\x01(\x02R\nudpPingAvgB\0\x12\"\n\x0cudp_ping_var\
+    \x18\t\x20\x01(\x02R\nudpPingVarB\0\x12\"\n\x0ctcp_ping_avg\x18\n\x20\
+    \x01(\x02R\ntcpPingAvgB\0\x12\"\n\x0ctcp_ping_var\x18\x0b\x20\x01(\x02R\
+    \ntcpPingVarB\0:\0\"\x8d\x02\n\x06Reject\x124\n\x04type\x18\x01\x20\x01(\
+    \x0e2\x1e.MumbleProto.Reject.RejectTypeR\x04typeB\0\x12\x18\n\x06reason\
+    \x18\x02\x20\x01(\tR\x06reasonB\0\"\xb0\x01\n\nRejectType\x12\x08\n\x04N\
+    one\x10\0\x12\x10\n\x0cWrongVersion\x10\x01\x12\x13\n\x0fInvalidUsername\
+    \x10\x02\x12\x0f\n\x0bWrongUserPW\x10\x03\x12\x11\n\rWrongServerPW\x10\
+    \x04\x12\x11\n\rUsernameInUse\x10\x05\x12\x0e\n\nServerFull\x10\x06\x12\
+    \x11\n\rNoCertificate\x10\x07\x12\x15\n\x11AuthenticatorFail\x10\x08\x1a\
+    \0:\0\"\x9a\x01\n\nServerSync\x12\x1a\n\x07session\x18\x01\x20\x01(\rR\
+    \x07sessionB\0\x12%\n\rmax_bandwidth\x18\x02\x20\x01(\rR\x0cmaxBandwidth\
+    B\0\x12#\n\x0cwelcome_text\x18\x03\x20\x01(\tR\x0bwelcomeTextB\0\x12\"\n\
+    \x0bpermissions\x18\x04\x20\x01(\x04R\x0bpermissionsB\0:\0\"2\n\rChannel\
+    Remove\x12\x1f\n\nchannel_id\x18\x01\x20\x02(\rR\tchannelIdB\0:\0\"\xc6\
+    \x03\n\x0cChannelState\x12\x1f\n\nchannel_id\x18\x01\x20\x01(\rR\tchanne\
+    lIdB\0\x12\x18\n\x06parent\x18\x02\x20\x01(\rR\x06parentB\0\x12\x14\n\
+    \x04name\x18\x03\x20\x01(\tR\x04nameB\0\x12\x16\n\x05links\x18\x04\x20\
+    \x03(\rR\x05linksB\0\x12\"\n\x0bdescription\x18\x05\x20\x01(\tR\x0bdescr\
+    iptionB\0\x12\x1d\n\tlinks_add\x18\x06\x20\x03(\rR\x08linksAddB\0\x12#\n\
+    \x0clinks_remove\x18\x07\x20\x03(\rR\x0blinksRemoveB\0\x12%\n\ttemporary\
+    \x18\x08\x20\x01(\x08:\x05falseR\ttemporaryB\0\x12\x1f\n\x08position\x18\
+    \t\x20\x01(\x05:\x010R\x08positionB\0\x12+\n\x10description_hash\x18\n\
+    \x20\x01(\x0cR\x0fdescriptionHashB\0\x12\x1d\n\tmax_users\x18\x0b\x20\
+    \x01(\rR\x08maxUsersB\0\x120\n\x13is_enter_restricted\x18\x0c\x20\x01(\
+    \x08R\x11isEnterRestrictedB\0\x12\x1d\n\tcan_enter\x18\r\x20\x01(\x08R\
+    \x08canEnterB\0:\0\"p\n\nUserRemove\x12\x1a\n\x07session\x18\x01\x20\x02\
+    (\rR\x07sessionB\0\x12\x16\n\x05actor\x18\x02\x20\x01(\rR\x05actorB\0\
+    \x12\x18\n\x06reason\x18\x03\x20\x01(\tR\x06reasonB\0\x12\x12\n\x03ban\
+    \x18\x04\x20\x01(\x08R\x03banB\0:\0\"\x80\x06\n\tUserState\x12\x1a\n\x07\
+    session\x18\x01\x20\x01(\rR\x07sessionB\0\x12\x16\n\x05actor\x18\x02\x20\
+    \x01(\rR\x05actorB\0\x12\x14\n\x04name\x18\x03\x20\x01(\tR\x04nameB\0\
+    \x12\x19\n\x07user_id\x18\x04\x20\x01(\rR\x06userIdB\0\x12\x1f\n\nchanne\
+    l_id\x18\x05\x20\x01(\rR\tchannelIdB\0\x12\x14\n\x04mute\x18\x06\x20\x01\
+    (\x08R\x04muteB\0\x12\x14\n\x04deaf\x18\x07\x20\x01(\x08R\x04deafB\0\x12\
+    \x1c\n\x08suppress\x18\x08\x20\x01(\x08R\x08suppressB\0\x12\x1d\n\tself_\
+    mute\x18\t\x20\x01(\x08R\x08selfMuteB\0\x12\x1d\n\tself_deaf\x18\n\x20\
+    \x01(\x08R\x08selfDeafB\0\x12\x1a\n\x07texture\x18\x0b\x20\x01(\x0cR\x07\
+    textureB\0\x12'\n\x0eplugin_context\x18\x0c\x20\x01(\x0cR\rpluginContext\
+    B\0\x12)\n\x0fplugin_identity\x18\r\x20\x01(\tR\x0epluginIdentityB\0\x12\
+    \x1a\n\x07comment\x18\x0e\x20\x01(\tR\x07commentB\0\x12\x14\n\x04hash\
+    \x18\x0f\x20\x01(\tR\x04hashB\0\x12#\n\x0ccomment_hash\x18\x10\x20\x01(\
+    \x0cR\x0bcommentHashB\0\x12#\n\x0ctexture_hash\x18\x11\x20\x01(\x0cR\x0b\
+    textureHashB\0\x12+\n\x10priority_speaker\x18\x12\x20\x01(\x08R\x0fprior\
+    itySpeakerB\0\x12\x1e\n\trecording\x18\x13\x20\x01(\x08R\trecordingB\0\
+    \x128\n\x17temporary_access_tokens\x18\x14\x20\x03(\tR\x15temporaryAcces\
+    sTokensB\0\x124\n\x15listening_channel_add\x18\x15\x20\x03(\rR\x13listen\
+    ingChannelAddB\0\x12:\n\x18listening_channel_remove\x18\x16\x20\x03(\rR\
+    \x16listeningChannelRemoveB\0:\0\"\x9c\x02\n\x07BanList\x123\n\x04bans\
+    \x18\x01\x20\x03(\x0b2\x1d.MumbleProto.BanList.BanEntryR\x04bansB\0\x12\
+    \x1d\n\x05query\x18\x02\x20\x01(\x08:\x05falseR\x05queryB\0\x1a\xba\x01\
+    \n\x08BanEntry\x12\x1a\n\x07address\x18\x01\x20\x02(\x0cR\x07addressB\0\
+    \x12\x14\n\x04mask\x18\x02\x20\x02(\rR\x04maskB\0\x12\x14\n\x04name\x18\
+    \x03\x20\x01(\tR\x04nameB\0\x12\x14\n\x04hash\x18\x04\x20\x01(\tR\x04has\
+    hB\0\x12\x18\n\x06reason\x18\x05\x20\x01(\tR\x06reasonB\0\x12\x16\n\x05s\
+    tart\x18\x06\x20\x01(\tR\x05startB\0\x12\x1c\n\x08duration\x18\x07\x20\
+    \x01(\rR\x08durationB\0:\0:\0\"\x9b\x01\n\x0bTextMessage\x12\x16\n\x05ac\
+    tor\x18\x01\x20\x01(\rR\x05actorB\0\x12\x1a\n\x07session\x18\x02\x20\x03\
+    (\rR\x07sessionB\0\x12\x1f\n\nchannel_id\x18\x03\x20\x03(\rR\tchannelIdB\
+    \0\x12\x19\n\x07tree_id\x18\x04\x20\x03(\rR\x06treeIdB\0\x12\x1a\n\x07me\
+    ssage\x18\x05\x20\x02(\tR\x07messageB\0:\0\"\xeb\x03\n\x10PermissionDeni\
+    ed\x12\x20\n\npermission\x18\x01\x20\x01(\rR\npermissionB\0\x12\x1f\n\nc\
+    hannel_id\x18\x02\x20\x01(\rR\tchannelIdB\0\x12\x1a\n\x07session\x18\x03\
+    \x20\x01(\rR\x07sessionB\0\x12\x18\n\x06reason\x18\x04\x20\x01(\tR\x06re\
+    asonB\0\x12<\n\x04type\x18\x05\x20\x01(\x0e2&.MumbleProto.PermissionDeni\
+    ed.DenyTypeR\x04typeB\0\x12\x14\n\x04name\x18\x06\x20\x01(\tR\x04nameB\0\
+    \"\x87\x02\n\x08DenyType\x12\x08\n\x04Text\x10\0\x12\x0e\n\nPermission\
+    \x10\x01\x12\r\n\tSuperUser\x10\x02\x12\x0f\n\x0bChannelName\x10\x03\x12\
+    \x0f\n\x0bTextTooLong\x10\x04\x12\x07\n\x03H9K\x10\x05\x12\x14\n\x10Temp\
+    oraryChannel\x10\x06\x12\x16\n\x12MissingCertificate\x10\x07\x12\x0c\n\
+    \x08UserName\x10\x08\x12\x0f\n\x0bChannelFull\x10\t\x12\x10\n\x0cNesting\
+    Limit\x10\n\x12\x15\n\x11ChannelCountLimit\x10\x0b\x12\x18\n\x14ChannelL\
+    istenerLimit\x10\x0c\x12\x15\n\x11UserListenerLimit\x10\r\x1a\0:\0\"\xb0\
+    \x05\n\x03ACL\x12\x1f\n\nchannel_id\x18\x01\x20\x02(\rR\tchannelIdB\0\
+    \x12)\n\x0cinherit_acls\x18\x02\x20\x01(\x08:\x04trueR\x0binheritAclsB\0\
+    \x124\n\x06groups\x18\x03\x20\x03(\x0b2\x1a.MumbleProto.ACL.ChanGroupR\
+    \x06groupsB\0\x12.\n\x04acls\x18\x04\x20\x03(\x0b2\x18.MumbleProto.ACL.C\
+    hanACLR\x04aclsB\0\x12\x1d\n\x05query\x18\x05\x20\x01(\x08:\x05falseR\
+    \x05queryB\0\x1a\xf2\x01\n\tChanGroup\x12\x14\n\x04name\x18\x01\x20\x02(\
+    \tR\x04nameB\0\x12$\n\tinherited\x18\x02\x20\x01(\x08:\x04trueR\tinherit\
+    edB\0\x12\x20\n\x07inherit\x18\x03\x20\x01(\x08:\x04trueR\x07inheritB\0\
+    \x12(\n\x0binheritable\x18\x04\x20\x01(\x08:\x04trueR\x0binheritableB\0\
+    \x12\x12\n\x03add\x18\x05\x20\x03(\rR\x03addB\0\x12\x18\n\x06remove\x18\
+    \x06\x20\x03(\rR\x06removeB\0\x12-\n\x11inherited_members\x18\x07\x20\
+    \x03(\rR\x10inheritedMembersB\0:\0\x1a\xe0\x01\n\x07ChanACL\x12%\n\nappl\
+    y_here\x18\x01\x20\x01(\x08:\x04trueR\tapplyHereB\0\x12%\n\napply_subs\
+    \x18\x02\x20\x01(\x08:\x04trueR\tapplySubsB\0\x12$\n\tinherited\x18\x03\
+    \x20\x01(\x08:\x04trueR\tinheritedB\0\x12\x19\n\x07user_id\x18\x04\x20\
+    \x01(\rR\x06userIdB\0\x12\x16\n\x05group\x18\x05\x20\x01(\tR\x05groupB\0\
+    \x12\x16\n\x05grant\x18\x06\x20\x01(\rR\x05grantB\0\x12\x14\n\x04deny\
+    \x18\x07\x20\x01(\rR\x04denyB\0:\0:\0\":\n\nQueryUsers\x12\x12\n\x03ids\
+    \x18\x01\x20\x03(\rR\x03idsB\0\x12\x16\n\x05names\x18\x02\x20\x03(\tR\
+    \x05namesB\0:\0\"l\n\nCryptSetup\x12\x12\n\x03key\x18\x01\x20\x01(\x0cR\
+    \x03keyB\0\x12#\n\x0cclient_nonce\x18\x02\x20\x01(\x0cR\x0bclientNonceB\
+    \0\x12#\n\x0cserver_nonce\x18\x03\x20\x01(\x0cR\x0bserverNonceB\0:\0\"\
+    \x83\x02\n\x13ContextActionModify\x12\x18\n\x06action\x18\x01\x20\x02(\t\
+    R\x06actionB\0\x12\x14\n\x04text\x18\x02\x20\x01(\tR\x04textB\0\x12\x1a\
+    \n\x07context\x18\x03\x20\x01(\rR\x07contextB\0\x12J\n\toperation\x18\
+    \x04\x20\x01(\x0e2*.MumbleProto.ContextActionModify.OperationR\toperatio\
+    nB\0\".\n\x07Context\x12\n\n\x06Server\x10\x01\x12\x0b\n\x07Channel\x10\
+    \x02\x12\x08\n\x04User\x10\x04\x1a\0\"\"\n\tOperation\x12\x07\n\x03Add\
+    \x10\0\x12\n\n\x06Remove\x10\x01\x1a\0:\0\"h\n\rContextAction\x12\x1a\n\
+    \x07session\x18\x01\x20\x01(\rR\x07sessionB\0\x12\x1f\n\nchannel_id\x18\
+    \x02\x20\x01(\rR\tchannelIdB\0\x12\x18\n\x06action\x18\x03\x20\x02(\tR\
+    \x06actionB\0:\0\"\xbf\x01\n\x08UserList\x122\n\x05users\x18\x01\x20\x03\
+    (\x0b2\x1a.MumbleProto.UserList.UserR\x05usersB\0\x1a}\n\x04User\x12\x19\
+    \n\x07user_id\x18\x01\x20\x02(\rR\x06userIdB\0\x12\x14\n\x04name\x18\x02\
+    \x20\x01(\tR\x04nameB\0\x12\x1d\n\tlast_seen\x18\x03\x20\x01(\tR\x08last\
+    SeenB\0\x12#\n\x0clast_channel\x18\x04\x20\x01(\rR\x0blastChannelB\0:\0:\
+    \0\"\x84\x02\n\x0bVoiceTarget\x12\x10\n\x02id\x18\x01\x20\x01(\rR\x02idB\
+    \0\x12;\n\x07targets\x18\x02\x20\x03(\x0b2\x1f.MumbleProto.VoiceTarget.T\
+    argetR\x07targetsB\0\x1a\xa3\x01\n\x06Target\x12\x1a\n\x07session\x18\
+    \x01\x20\x03(\rR\x07sessionB\0\x12\x1f\n\nchannel_id\x18\x02\x20\x01(\rR\
+    \tchannelIdB\0\x12\x16\n\x05group\x18\x03\x20\x01(\tR\x05groupB\0\x12\
+    \x1d\n\x05links\x18\x04\x20\x01(\x08:\x05falseR\x05linksB\0\x12#\n\x08ch\
+    ildren\x18\x05\x20\x01(\x08:\x05falseR\x08childrenB\0:\0:\0\"w\n\x0fPerm\
+    issionQuery\x12\x1f\n\nchannel_id\x18\x01\x20\x01(\rR\tchannelIdB\0\x12\
+    \"\n\x0bpermissions\x18\x02\x20\x01(\rR\x0bpermissionsB\0\x12\x1d\n\x05f\
+    lush\x18\x03\x20\x01(\x08:\x05falseR\x05flushB\0:\0\"\x86\x01\n\x0cCodec\
+    Version\x12\x16\n\x05alpha\x18\x01\x20\x02(\x05R\x05alphaB\0\x12\x14\n\
+    \x04beta\x18\x02\x20\x02(\x05R\x04betaB\0\x12)\n\x0cprefer_alpha\x18\x03\
+    \x20\x02(\x08:\x04trueR\x0bpreferAlphaB\0\x12\x1b\n\x04opus\x18\x04\x20\
+    \x01(\x08:\x05falseR\x04opusB\0:\0\"\xe0\x06\n\tUserStats\x12\x1a\n\x07s\
+    ession\x18\x01\x20\x01(\rR\x07sessionB\0\x12&\n\nstats_only\x18\x02\x20\
+    \x01(\x08:\x05falseR\tstatsOnlyB\0\x12$\n\x0ccertificates\x18\x03\x20\
+    \x03(\x0cR\x0ccertificatesB\0\x12?\n\x0bfrom_client\x18\x04\x20\x01(\x0b\
+    2\x1c.MumbleProto.UserStats.StatsR\nfromClientB\0\x12?\n\x0bfrom_server\
+    \x18\x05\x20\x01(\x0b2\x1c.MumbleProto.UserStats.StatsR\nfromServerB\0\
+    \x12!\n\x0budp_packets\x18\x06\x20\x01(\rR\nudpPacketsB\0\x12!\n\x0btcp_\
+    packets\x18\x07\x20\x01(\rR\ntcpPacketsB\0\x12\"\n\x0cudp_ping_avg\x18\
+    \x08\x20\x01(\x02R\nudpPingAvgB\0\x12\"\n\x0cudp_ping_var\x18\t\x20\x01(\
+    \x02R\nudpPingVarB\0\x12\"\n\x0ctcp_ping_avg\x18\n\x20\x01(\x02R\ntcpPin\
+    gAvgB\0\x12\"\n\x0ctcp_ping_var\x18\x0b\x20\x01(\x02R\ntcpPingVarB\0\x12\
+    0\n\x07version\x18\x0c\x20\x01(\x0b2\x14.MumbleProto.VersionR\x07version\
+    B\0\x12%\n\rcelt_versions\x18\r\x20\x03(\x05R\x0cceltVersionsB\0\x12\x1a\
+    \n\x07address\x18\x0e\x20\x01(\x0cR\x07addressB\0\x12\x1e\n\tbandwidth\
+    \x18\x0f\x20\x01(\rR\tbandwidthB\0\x12\x20\n\nonlinesecs\x18\x10\x20\x01\
+    (\rR\nonlinesecsB\0\x12\x1c\n\x08idlesecs\x18\x11\x20\x01(\rR\x08idlesec\
+    sB\0\x126\n\x12strong_certificate\x18\x12\x20\x01(\x08:\x05falseR\x11str\
+    ongCertificateB\0\x12\x1b\n\x04opus\x18\x13\x20\x01(\x08:\x05falseR\x04o\
+    pusB\0\x1ae\n\x05Stats\x12\x14\n\x04good\x18\x01\x20\x01(\rR\x04goodB\0\
+    \x12\x14\n\x04late\x18\x02\x20\x01(\rR\x04lateB\0\x12\x14\n\x04lost\x18\
+    \x03\x20\x01(\rR\x04lostB\0\x12\x18\n\x06resync\x18\x04\x20\x01(\rR\x06r\
+    esyncB\0:\0:\0\"\x98\x01\n\x0bRequestBlob\x12)\n\x0fsession_texture\x18\
+    \x01\x20\x03(\rR\x0esessionTextureB\0\x12)\n\x0fsession_comment\x18\x02\
+    \x20\x03(\rR\x0esessionCommentB\0\x121\n\x13channel_description\x18\x03\
+    \x20\x03(\rR\x12channelDescriptionB\0:\0\"\xf9\x01\n\x0cServerConfig\x12\
+    %\n\rmax_bandwidth\x18\x01\x20\x01(\rR\x0cmaxBandwidthB\0\x12#\n\x0cwelc\
+    ome_text\x18\x02\x20\x01(\tR\x0bwelcomeTextB\0\x12\x1f\n\nallow_html\x18\
+    \x03\x20\x01(\x08R\tallowHtmlB\0\x12'\n\x0emessage_length\x18\x04\x20\
+    \x01(\rR\rmessageLengthB\0\x122\n\x14image_message_length\x18\x05\x20\
+    \x01(\rR\x12imageMessageLengthB\0\x12\x1d\n\tmax_users\x18\x06\x20\x01(\
+    \rR\x08maxUsersB\0:\0\"\x99\x01\n\rSuggestConfig\x12\x1f\n\nversion_v1\
+    \x18\x01\x20\x01(\rR\tversionV1B\0\x12\x1f\n\nversion_v2\x18\x04\x20\x01\
+    (\x04R\tversionV2B\0\x12\x20\n\npositional\x18\x02\x20\x01(\x08R\npositi\
+    onalB\0\x12\"\n\x0cpush_to_talk\x18\x03\x20\x01(\x08R\npushToTalkB\0:\0\
+    \"\xa2\x01\n\x16PluginDataTransmission\x12&\n\rsenderSession\x18\x01\x20\
+    \x01(\rR\rsenderSessionB\0\x12.\n\x10receiverSessions\x18\x02\x20\x03(\r\
+    R\x10receiverSessionsB\x02\x10\x01\x12\x14\n\x04data\x18\x03\x20\x01(\
+    \x0cR\x04dataB\0\x12\x18\n\x06dataID\x18\x04\x20\x01(\tR\x06dataIDB\0:\0\
+    B\0b\x06proto2\
 ";
 
 static file_descriptor_proto_lazy: ::protobuf::rt::LazyV2<::protobuf::descriptor::FileDescriptorProto> = ::protobuf::rt::LazyV2::INIT;
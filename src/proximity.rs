@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Proximity voice settings of a channel, when set speech sent to the channel is only heard by
+/// listeners within the radius of the speaker
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, ToSchema)]
+pub struct ProximitySettings {
+    /// Distance at which a listener starts hearing the speaker, in game units
+    pub radius: f32,
+    /// Extra distance a listener can move away before they stop hearing the speaker, this avoids
+    /// audio cutting in and out for listeners standing at the edge of the radius
+    #[serde(default)]
+    pub hysteresis: f32,
+}
+
+impl ProximitySettings {
+    pub fn is_valid(&self) -> bool {
+        self.radius.is_finite() && self.radius > 0.0 && self.hysteresis.is_finite() && self.hysteresis >= 0.0
+    }
+
+    /// Checks if the listener can hear the speaker, `was_audible` is whether they heard the
+    /// previous packet of this speaker
+    pub fn is_audible(&self, speaker: [f32; 3], listener: [f32; 3], was_audible: bool) -> bool {
+        let range = if was_audible { self.radius + self.hysteresis } else { self.radius };
+
+        distance_squared(speaker, listener) <= range * range
+    }
+}
+
+fn distance_squared(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a.iter().zip(b.iter()).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETTINGS: ProximitySettings = ProximitySettings {
+        radius: 10.0,
+        hysteresis: 2.0,
+    };
+
+    #[test]
+    fn audible_within_radius() {
+        assert!(SETTINGS.is_audible([0.0, 0.0, 0.0], [6.0, 8.0, 0.0], false));
+        assert!(SETTINGS.is_audible([1.0, 1.0, 1.0], [1.0, 1.0, 11.0], false));
+        assert!(!SETTINGS.is_audible([0.0, 0.0, 0.0], [6.0, 8.0, 0.1], false));
+    }
+
+    #[test]
+    fn hysteresis_only_keeps_listeners_that_heard_the_speaker() {
+        let listener = [0.0, 11.5, 0.0];
+
+        assert!(!SETTINGS.is_audible([0.0, 0.0, 0.0], listener, false));
+        assert!(SETTINGS.is_audible([0.0, 0.0, 0.0], listener, true));
+        assert!(!SETTINGS.is_audible([0.0, 0.0, 0.0], [0.0, 12.5, 0.0], true));
+    }
+
+    #[test]
+    fn invalid_settings() {
+        assert!(SETTINGS.is_valid());
+        assert!(!ProximitySettings {
+            radius: 0.0,
+            hysteresis: 0.0
+        }
+        .is_valid());
+        assert!(!ProximitySettings {
+            radius: f32::NAN,
+            hysteresis: 0.0
+        }
+        .is_valid());
+        assert!(!ProximitySettings {
+            radius: 10.0,
+            hysteresis: -1.0
+        }
+        .is_valid());
+        assert!(!ProximitySettings {
+            radius: f32::INFINITY,
+            hysteresis: 0.0
+        }
+        .is_valid());
+    }
+}
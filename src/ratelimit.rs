@@ -0,0 +1,97 @@
+use std::time::Instant;
+
+/// Token bucket used to limit how often something can happen, refills at `rate` tokens per
+/// second up to `burst` tokens.
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64, burst: f64) -> Self {
+        Self {
+            rate,
+            burst,
+            tokens: burst,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Returns true if the bucket refilled completely, meaning it behaves like a new one
+    pub fn is_full(&self) -> bool {
+        let elapsed = Instant::now().duration_since(self.last_refill).as_secs_f64();
+
+        self.tokens + elapsed * self.rate >= self.burst
+    }
+
+    /// Takes a token from the bucket, returns false if the bucket is empty
+    pub fn try_acquire(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.last_refill = now;
+
+        if self.tokens < 1.0 {
+            return false;
+        }
+
+        self.tokens -= 1.0;
+
+        true
+    }
+}
+
+/// Parses a rate in events per second for the command line, a rate that isn't above zero never
+/// refills its bucket
+pub fn parse_rate(rate: &str) -> Result<f64, String> {
+    let rate = rate.trim().parse::<f64>().map_err(|e| format!("invalid rate {}: {}", rate, e))?;
+
+    if !rate.is_finite() || rate <= 0.0 {
+        return Err(format!("the rate must be a number above 0, got {}", rate));
+    }
+
+    Ok(rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_is_used_up() {
+        let mut limiter = RateLimiter::new(1.0, 3.0);
+
+        assert!(limiter.is_full());
+        assert!(limiter.try_acquire());
+        assert!(limiter.try_acquire());
+        assert!(limiter.try_acquire());
+        assert!(!limiter.try_acquire());
+        assert!(!limiter.is_full());
+    }
+
+    #[test]
+    fn refills_over_time() {
+        let mut limiter = RateLimiter::new(1000.0, 1.0);
+
+        assert!(limiter.try_acquire());
+        assert!(!limiter.try_acquire());
+
+        std::thread::sleep(std::time::Duration::from_millis(5));
+
+        assert!(limiter.is_full());
+        assert!(limiter.try_acquire());
+    }
+
+    #[test]
+    fn rate_must_be_positive() {
+        assert_eq!(parse_rate("2.5"), Ok(2.5));
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("-1").is_err());
+        assert!(parse_rate("NaN").is_err());
+        assert!(parse_rate("inf").is_err());
+        assert!(parse_rate("fast").is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 /// TODO: Add these to a server.toml file so its easier to configure
 /// The amount of players the server can support
 pub const MAX_CLIENTS: usize = 4096;
@@ -7,3 +9,89 @@ pub const MAX_CLIENTS: usize = 4096;
 pub const MAX_BANDWIDTH_IN_BITS: u32 = 144_000;
 
 pub const MAX_BANDWIDTH_IN_BYTES: usize = MAX_BANDWIDTH_IN_BITS as usize / 8;
+
+/// Maximum size of the data in a plugin message, mirrors mumble's own plugin limit
+pub const MAX_PLUGIN_DATA_SIZE: usize = 1000;
+
+/// Maximum size of the data id in a plugin message
+pub const MAX_PLUGIN_DATA_ID_SIZE: usize = 100;
+
+/// Maximum number of receivers of a plugin message
+pub const MAX_PLUGIN_DATA_RECEIVERS: usize = 128;
+
+/// How many plugin messages a client can send per second, once its burst is used up
+pub const PLUGIN_MESSAGE_RATE: f64 = 4.0;
+
+/// How many plugin messages a client can send in a burst
+pub const PLUGIN_MESSAGE_BURST: f64 = 15.0;
+
+/// How many triggered context actions are kept around for the game server to pick up
+pub const MAX_CONTEXT_ACTION_EVENTS: usize = 1000;
+
+/// How long other speakers stay ducked after the last packet of a priority speaker, long enough
+/// to cover the gaps between packets
+pub const PRIORITY_SPEAKER_DUCKING_DURATION: Duration = Duration::from_millis(500);
+
+/// A client stops talking when we didn't get a voice packet for this long, for clients that don't
+/// send the end of transmission bit or when it got lost
+pub const TALKING_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How many events are kept for consumers of the event stream that reconnect
+pub const MAX_EVENT_HISTORY: usize = 1000;
+
+/// How many events a slow consumer of the event stream can fall behind before it gets disconnected
+pub const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// How often a comment is sent on an idle event stream so proxies keep the connection open
+pub const EVENT_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How many webhook deliveries can wait for a single url before new events get dropped
+pub const WEBHOOK_QUEUE_SIZE: usize = 256;
+
+/// How long a webhook url has to answer a delivery
+pub const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many times a webhook delivery is tried before it gets dropped
+pub const WEBHOOK_MAX_ATTEMPTS: u32 = 5;
+
+/// Wait before the first retry of a failed webhook delivery, doubled after every attempt
+pub const WEBHOOK_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Crypt resets in a row after which a webhook is sent for the client
+pub const CRYPT_RESET_WEBHOOK_THRESHOLD: u32 = 3;
+
+/// The UDP loop counts as stalled when it didn't receive anything for this long while clients are
+/// connected over UDP, clients ping every few seconds
+pub const UDP_LOOP_STALE_AFTER: Duration = Duration::from_secs(15);
+
+/// Longest the clean loop sleeps between runs, it wakes up earlier for a client deadline
+pub const CLEAN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Shortest the clean loop sleeps between runs, client deadlines closer than this are handled
+/// together
+pub const DEADLINE_RESOLUTION: Duration = Duration::from_millis(100);
+
+/// How often the clean loop refreshes the channel gauges, this needs a look at every channel
+pub const CLIENT_METRICS_INTERVAL: Duration = Duration::from_secs(15);
+
+/// The clean loop runs every second, it counts as stalled when it didn't run for this long
+pub const CLEAN_LOOP_STALE_AFTER: Duration = Duration::from_secs(10);
+
+/// How many audit entries are kept in memory for the audit endpoint
+pub const MAX_AUDIT_HISTORY: usize = 10000;
+
+/// How many rotated audit log files are kept next to the current one
+pub const AUDIT_LOG_ROTATIONS: usize = 5;
+
+/// How often the shutdown checks if every client left during the drain
+pub const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long a client still connected after the drain has to receive what is left for it
+pub const SHUTDOWN_FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long the moderation and channel settings restored from a replaced process or the state file
+/// wait for their user or channel to come back
+pub const RESTORED_STATE_TTL: Duration = Duration::from_secs(300);
+
+/// How long each step of handing the sockets over to a new process can take
+pub const HANDOVER_TIMEOUT: Duration = Duration::from_secs(10);
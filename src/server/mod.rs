@@ -1,6 +1,8 @@
 pub mod constants;
+mod position;
 mod tcp;
 mod udp;
 
+pub use position::create_position_server;
 pub use tcp::create_tcp_server;
 pub use udp::create_udp_server;
@@ -0,0 +1,52 @@
+use crate::position::PositionUpdates;
+use crate::state::ServerStateRef;
+use tokio::net::UdpSocket;
+
+/// Receives JSON position updates from the game server, there is no authentication on this socket
+/// so it should only listen on a local or private address
+pub async fn create_position_server(listen: String, state: ServerStateRef) {
+    let socket = match UdpSocket::bind(&listen).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            tracing::error!("cannot bind position server on {}: {}", listen, e);
+
+            return;
+        }
+    };
+
+    let mut buffer = vec![0u8; 65536];
+
+    loop {
+        let (size, addr) = match socket.recv_from(&mut buffer).await {
+            Ok(received) => received,
+            Err(e) => {
+                tracing::error!("position server error: {}", e);
+
+                continue;
+            }
+        };
+
+        crate::metrics::MESSAGES_TOTAL
+            .with_label_values(&["udp", "input", "PositionUpdate"])
+            .inc();
+
+        crate::metrics::MESSAGES_BYTES
+            .with_label_values(&["udp", "input", "PositionUpdate"])
+            .inc_by(size as u64);
+
+        let updates = match serde_json::from_slice::<PositionUpdates>(&buffer[..size]) {
+            Ok(updates) => updates,
+            Err(e) => {
+                tracing::warn!("invalid position update from {}: {}", addr, e);
+
+                continue;
+            }
+        };
+
+        for update in updates.into_vec() {
+            if !state.apply_position_update(&update) {
+                tracing::debug!("ignoring position update for unknown client: {:?}", update);
+            }
+        }
+    }
+}
@@ -1,10 +1,13 @@
+use crate::certificate::CertificateHash;
 use crate::client::{Client, ClientRef};
 use crate::handler::MessageHandler;
 use crate::message::ClientMessage;
-use crate::proto::mumble::Version;
-use crate::proto::MessageKind;
-use crate::server::constants::{MAX_BANDWIDTH_IN_BYTES, MAX_BANDWIDTH_IN_BITS, MAX_CLIENTS};
+use crate::proto::mumble::{Reject, Reject_RejectType, Version};
+use crate::proto::{send_message, MessageKind};
+use crate::server::constants::MAX_BANDWIDTH_IN_BYTES;
 use crate::state::ServerStateRef;
+use crate::version::{format_version, ClientVersion};
+use crate::webhook::{DisconnectReason, RejectReason, WebhookEvent};
 use actix_server::Server;
 use actix_service::fn_service;
 use anyhow::{anyhow, Context};
@@ -16,7 +19,9 @@ use tokio::sync::mpsc::Receiver;
 use tokio_rustls::{server::TlsStream, TlsAcceptor};
 
 pub fn create_tcp_server(tcp_listener: TcpListener, acceptor: TlsAcceptor, server_version: Version, state: ServerStateRef) -> Server {
+    // the shutdown drains the clients before stopping the server, it handles the signals itself
     Server::build()
+        .disable_signals()
         .listen(
             "mumble-tcp",
             tcp_listener.into_std().expect("cannot create tcp listener"),
@@ -56,26 +61,87 @@ async fn handle_new_client(
 
     let peer_ip = addr.ip();
 
-    if cur_clients >= MAX_CLIENTS {
+    let max_users = state.client_settings.load().get_max_users();
+
+    if cur_clients >= max_users {
         return Err(anyhow!(
             "{:?} tried to join but the server is at maximum capacity ({}/{})",
             addr,
             cur_clients,
-            MAX_CLIENTS
+            max_users
         ));
     }
 
     stream.set_nodelay(true).context("set stream no delay")?;
 
-    let mut stream = acceptor.accept(stream).await.context("accept tls")?;
+    let mut stream = acceptor
+        .accept(stream)
+        .await
+        .inspect_err(|_| crate::metrics::TLS_HANDSHAKE_FAILURES_TOTAL.inc())
+        .context("accept tls")?;
+
+    let certificate_hash = {
+        let (_, connection) = stream.get_ref();
+
+        connection
+            .peer_certificates()
+            .and_then(|certificates| certificates.first())
+            .map(CertificateHash::from_der)
+    };
+
+    if let Some(certificate_hash) = &certificate_hash {
+        if state.is_banned_certificate(certificate_hash) {
+            send_reject(&mut stream, Reject_RejectType::None, "You are banned from this server").await?;
+
+            state.webhooks.send(WebhookEvent::AuthenticationRejected {
+                ip: peer_ip,
+                name: None,
+                certificate_hash: Some(certificate_hash.clone()),
+                reason: RejectReason::Banned,
+            });
+
+            return Err(anyhow!(
+                "{:?} tried to join with banned certificate {}",
+                addr,
+                certificate_hash.sha1
+            ));
+        }
+    }
 
     let (version, authenticate, crypt_state) = Client::init(&mut stream, server_version).await.context("init client")?;
 
+    if let Some(min_client_version) = state.config.min_client_version {
+        let client_version = ClientVersion::from(&version);
+
+        if client_version.protocol_version() < min_client_version {
+            let reason = format!(
+                "Your client is too old, version {} or newer is required",
+                format_version(min_client_version)
+            );
+
+            send_reject(&mut stream, Reject_RejectType::WrongVersion, &reason).await?;
+
+            state.webhooks.send(WebhookEvent::AuthenticationRejected {
+                ip: peer_ip,
+                name: Some(authenticate.get_username().to_string()),
+                certificate_hash: certificate_hash.clone(),
+                reason: RejectReason::WrongVersion,
+            });
+
+            return Err(anyhow!(
+                "{:?} tried to join with outdated client {} ({})",
+                addr,
+                format_version(client_version.protocol_version()),
+                client_version.release
+            ));
+        }
+    }
+
     let (read, write) = io::split(stream);
     let (tx, rx) = mpsc::channel(MAX_BANDWIDTH_IN_BYTES);
 
     let username = authenticate.get_username().to_string();
-    let client = state.add_client(version, authenticate, crypt_state, write, tx, peer_ip);
+    let client = state.add_client(version, authenticate, crypt_state, write, tx, peer_ip, certificate_hash);
 
     tracing::info!("TCP new client {} connected {}", username, addr);
 
@@ -86,7 +152,17 @@ async fn handle_new_client(
 
     tracing::info!("client {} disconnected", username);
 
-    state.disconnect(client.session_id);
+    state.disconnect(client.session_id, DisconnectReason::Closed);
+
+    Ok(())
+}
+
+async fn send_reject(stream: &mut TlsStream<TcpStream>, reject_type: Reject_RejectType, reason: &str) -> Result<(), anyhow::Error> {
+    let mut reject = Reject::new();
+    reject.set_field_type(reject_type);
+    reject.set_reason(reason.to_string());
+
+    send_message(MessageKind::Reject, &reject, stream).await?;
 
     Ok(())
 }
@@ -109,9 +185,13 @@ pub async fn client_run(
 
             e
         })?;
+        let settings = state.client_settings.load_full();
+
         client.send_my_user_state().await?;
-        client.send_server_sync().await?;
-        client.send_server_config().await?;
+        client.send_server_sync(&settings).await?;
+        client.send_server_config(&settings).await?;
+        client.send_suggest_config(&settings).await?;
+        client.send_context_actions(&state).await?;
     }
 
     let user_state = { client.get_user_state() };
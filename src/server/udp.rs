@@ -1,35 +1,43 @@
 use crate::error::DecryptError;
 use crate::message::ClientMessage;
+use crate::proto::mumble::Version;
+use crate::proto::mumble_udp::Ping;
 use crate::state::ServerStateRef;
-use crate::voice::VoicePacket;
+use crate::voice::{VoicePacket, PROTOBUF_PING};
 
 use anyhow::anyhow;
 
-use byteorder::{ReadBytesExt, WriteBytesExt};
+use byteorder::WriteBytesExt;
 use bytes::BytesMut;
+use protobuf::Message;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::io::Cursor;
 use tokio::net::UdpSocket;
+use tokio::sync::mpsc::error::TrySendError;
 
-use super::constants::{MAX_BANDWIDTH_IN_BITS, MAX_CLIENTS};
+use super::constants::MAX_BANDWIDTH_IN_BITS;
+
+pub async fn create_udp_server(server_version: Version, socket: Arc<UdpSocket>, state: ServerStateRef) {
+    let server_version = Arc::new(server_version);
 
-pub async fn create_udp_server(protocol_version: u32, socket: Arc<UdpSocket>, state: ServerStateRef) {
     loop {
-        match udp_server_run(protocol_version, socket.clone(), state.clone()).await {
+        match udp_server_run(server_version.clone(), socket.clone(), state.clone()).await {
             Ok(_) => (),
             Err(e) => tracing::error!("udp server error: {:?}", e),
         }
     }
 }
 
-async fn udp_server_run(protocol_version: u32, socket: Arc<UdpSocket>, state: ServerStateRef) -> Result<(), anyhow::Error> {
+async fn udp_server_run(server_version: Arc<Version>, socket: Arc<UdpSocket>, state: ServerStateRef) -> Result<(), anyhow::Error> {
     let mut buffer = BytesMut::zeroed(1024);
     let (size, addr) = socket.recv_from(&mut buffer).await?;
     buffer.resize(size, 0);
 
+    state.health.udp_received();
+
     tokio::spawn(async move {
-        match handle_packet(buffer, size, addr, protocol_version, socket, state).await {
+        match handle_packet(buffer, size, addr, server_version, socket, state).await {
             Ok(_) => (),
             Err(e) => tracing::error!("udp server handle packet error: {:?}", e),
         }
@@ -42,43 +50,17 @@ async fn handle_packet(
     mut buffer: BytesMut,
     size: usize,
     addr: SocketAddr,
-    protocol_version: u32,
+    server_version: Arc<Version>,
     socket: Arc<UdpSocket>,
     state: ServerStateRef,
 ) -> Result<(), anyhow::Error> {
     if size <= 1 {
         return Err(anyhow!("Invalid packet"));
     }
-    let mut cursor = Cursor::new(&buffer[..size]);
-    let kind = cursor.read_u32::<byteorder::LittleEndian>()?;
-
-    // respond to the server list ping packet
-    if size == 12 && kind == 0 {
-        let timestamp = cursor.read_u64::<byteorder::LittleEndian>()?;
-
-        // TODO: actually read version and follow the mumble spec for using UDP protobufs here
-        let mut send = Cursor::new(vec![0u8; 24]);
-        // server version
-        send.write_u32::<byteorder::BigEndian>(protocol_version)?;
-        // timestamp
-        send.write_u64::<byteorder::LittleEndian>(timestamp)?;
-        // user count
-        send.write_u32::<byteorder::BigEndian>(state.clients.len() as u32)?;
-        // max user count
-        send.write_u32::<byteorder::BigEndian>(MAX_CLIENTS as u32)?;
-        // max bandwidth per user
-        send.write_u32::<byteorder::BigEndian>(MAX_BANDWIDTH_IN_BITS)?;
-
-        socket.send_to(send.get_ref().as_slice(), addr).await?;
 
-        crate::metrics::MESSAGES_TOTAL
-            .with_label_values(&["udp", "input", "PingAnonymous"])
-            .inc();
-
-        crate::metrics::MESSAGES_BYTES
-            .with_label_values(&["udp", "input", "PingAnonymous"])
-            .inc_by(size as u64);
+    let client_opt = state.get_client_by_socket(&addr);
 
+    if handle_server_list_ping(&buffer[..size], addr, client_opt.is_some(), &server_version, &socket, &state).await? {
         return Ok(());
     }
 
@@ -92,15 +74,13 @@ async fn handle_packet(
     //     return Err(anyhow!("Not a valid peer"));
     // }
 
-    let client_opt = state.get_client_by_socket(&addr);
-
     let (client, packet) = match client_opt {
         Some(client) => {
             // Send decrypt packet
 
             let decrypt_result = {
                 let mut crypt_state = client.crypt_state.lock();
-                crypt_state.decrypt(&mut buffer)
+                crypt_state.decrypt(&mut buffer, client.udp_format)
             };
 
             match decrypt_result {
@@ -116,6 +96,15 @@ async fn handle_packet(
                         .with_label_values(&["udp", "input", "VoicePacket"])
                         .inc_by(size as u64);
 
+                    let reason = match err {
+                        DecryptError::Late => "late",
+                        DecryptError::Repeat => "repeat",
+                        DecryptError::Mac => "mac",
+                        _ => "invalid",
+                    };
+
+                    crate::metrics::VOICE_DROPPED_TOTAL.with_label_values(&[reason]).inc();
+
                     let restart_crypt = match err {
                         DecryptError::Late => {
                             let late = { client.crypt_state.lock().late };
@@ -167,6 +156,8 @@ async fn handle_packet(
         }
     };
 
+    state.health.decrypted();
+
     let session_id = client.session_id;
     let client_packet = packet.into_client_bound(session_id);
 
@@ -184,7 +175,7 @@ async fn handle_packet(
 
             {
                 let mut crypt = client.crypt_state.lock();
-                crypt.encrypt(&client_packet, &mut dest);
+                crypt.encrypt(&client_packet, &mut dest, client.udp_format);
             }
 
             let buf = &dest.freeze()[..];
@@ -219,6 +210,10 @@ async fn handle_packet(
                 Ok(_) => (),
                 Err(err) => {
                     tracing::error!("cannot send voice packet to client: {}", err);
+
+                    if let TrySendError::Full(_) = err {
+                        crate::metrics::VOICE_DROPPED_TOTAL.with_label_values(&["queue_full"]).inc();
+                    }
                 }
             }
         }
@@ -226,3 +221,78 @@ async fn handle_packet(
 
     Ok(())
 }
+
+/// Answers the unencrypted pings mumble clients use for their server list, both the legacy 12 byte
+/// ping and the protobuf ping asking for extended information.
+///
+/// Returns false if the packet isn't a server list ping.
+async fn handle_server_list_ping(
+    buffer: &[u8],
+    addr: SocketAddr,
+    known_client: bool,
+    server_version: &Version,
+    socket: &UdpSocket,
+    state: &ServerStateRef,
+) -> Result<bool, anyhow::Error> {
+    let user_count = state.clients.len() as u32;
+    let max_users = state.client_settings.load().get_max_users() as u32;
+
+    let (reply, kind) = if buffer.len() == 12 && buffer[..4] == [0; 4] {
+        // the first 4 bytes are the request type, 0 is the only one that exists
+        let timestamp = u64::from_le_bytes(buffer[4..12].try_into()?);
+
+        let mut send = Cursor::new(vec![0u8; 24]);
+        // server version
+        send.write_u32::<byteorder::BigEndian>(server_version.get_version_v1())?;
+        // timestamp
+        send.write_u64::<byteorder::LittleEndian>(timestamp)?;
+        // user count
+        send.write_u32::<byteorder::BigEndian>(user_count)?;
+        // max user count
+        send.write_u32::<byteorder::BigEndian>(max_users)?;
+        // max bandwidth per user
+        send.write_u32::<byteorder::BigEndian>(MAX_BANDWIDTH_IN_BITS)?;
+
+        (send.into_inner(), "PingAnonymous")
+    } else if !known_client && buffer[0] == PROTOBUF_PING {
+        // connected clients send their pings encrypted, so only unknown addresses can send these
+        let ping = match Ping::parse_from_bytes(&buffer[1..]) {
+            Ok(ping) if ping.get_request_extended_information() => ping,
+            _ => return Ok(false),
+        };
+
+        let mut reply = Ping::new();
+        reply.set_timestamp(ping.get_timestamp());
+        reply.set_server_version_v2(server_version.get_version_v2());
+        reply.set_user_count(user_count);
+        reply.set_max_user_count(max_users);
+        reply.set_max_bandwidth_per_user(MAX_BANDWIDTH_IN_BITS);
+
+        let mut send = vec![PROTOBUF_PING];
+        reply.write_to_vec(&mut send)?;
+
+        (send, "PingExtended")
+    } else {
+        return Ok(false);
+    };
+
+    crate::metrics::MESSAGES_TOTAL.with_label_values(&["udp", "input", kind]).inc();
+
+    crate::metrics::MESSAGES_BYTES
+        .with_label_values(&["udp", "input", kind])
+        .inc_by(buffer.len() as u64);
+
+    if !state.allow_server_list_ping(addr.ip()) {
+        tracing::debug!("dropping server list ping from {}, rate limit reached", addr);
+
+        crate::metrics::MESSAGES_TOTAL
+            .with_label_values(&["udp", "input", "PingRateLimited"])
+            .inc();
+
+        return Ok(true);
+    }
+
+    socket.send_to(&reply, addr).await?;
+
+    Ok(true)
+}
@@ -0,0 +1,111 @@
+use crate::client::ClientRef;
+use crate::proto::mumble::TextMessage;
+use crate::proto::MessageKind;
+use crate::server::constants::{DRAIN_POLL_INTERVAL, SHUTDOWN_FLUSH_TIMEOUT};
+use crate::state::ServerState;
+use crate::webhook::DisconnectReason;
+use actix_server::ServerHandle;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::time::timeout;
+
+/// Resolves when the process is asked to stop
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate = signal(SignalKind::terminate()).expect("failed to listen for SIGTERM");
+
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => (),
+            _ = terminate.recv() => (),
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+fn connected_clients(state: &ServerState) -> Vec<ClientRef> {
+    let mut clients = Vec::new();
+
+    state.clients.scan(|_, client| {
+        clients.push(client.clone());
+    });
+
+    clients
+}
+
+/// Stops taking new clients, persists the state when a state file is configured, tells the
+/// connected ones why the server goes away and gives them up to `drain_timeout` to leave before
+/// their connections are flushed and closed, then stops the servers
+pub async fn graceful_shutdown(
+    state: &ServerState,
+    tcp_server: ServerHandle,
+    servers: Vec<ServerHandle>,
+    drain_timeout: Duration,
+    message: String,
+    state_file: Option<PathBuf>,
+) {
+    tracing::info!(
+        "shutting down, draining {} clients for up to {}s",
+        state.clients.len(),
+        drain_timeout.as_secs()
+    );
+
+    state.health.set_ready(false);
+    tcp_server.pause().await;
+
+    // taken before the drain, the moderation of the users leaves with them
+    if let Some(path) = state_file {
+        match state.snapshot().save(&path).await {
+            Ok(_) => tracing::info!("state saved to {}", path.display()),
+            Err(e) => tracing::error!("failed to save the state to {}: {}", path.display(), e),
+        }
+    }
+
+    // a text message rather than a UserRemove, clients treat the latter as a kick and don't
+    // reconnect by themselves
+    let mut text_message = TextMessage::new();
+    text_message.set_message(message);
+
+    let text_message = &text_message;
+
+    // a stalled client holds its write lock, it mustn't keep the others from being told
+    futures::future::join_all(connected_clients(state).into_iter().map(|client| async move {
+        match timeout(SHUTDOWN_FLUSH_TIMEOUT, client.send_message(MessageKind::TextMessage, text_message)).await {
+            Ok(Ok(_)) => (),
+            Ok(Err(e)) => tracing::warn!("failed to send the shutdown message to {}: {}", client, e),
+            Err(_) => tracing::warn!("timed out sending the shutdown message to {}", client),
+        }
+    }))
+    .await;
+
+    let deadline = Instant::now() + drain_timeout;
+
+    while !state.clients.is_empty() && Instant::now() < deadline {
+        tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+    }
+
+    futures::future::join_all(connected_clients(state).into_iter().map(|client| async move {
+        match timeout(SHUTDOWN_FLUSH_TIMEOUT, client.close()).await {
+            Ok(Ok(_)) => (),
+            Ok(Err(e)) => tracing::warn!("failed to close the connection of {}: {}", client, e),
+            Err(_) => tracing::warn!("timed out flushing the connection of {}", client),
+        }
+
+        state.disconnect(client.session_id, DisconnectReason::Shutdown);
+    }))
+    .await;
+
+    tcp_server.stop(true).await;
+
+    for server in servers {
+        server.stop(true).await;
+    }
+
+    state.audit.sync().await;
+
+    tracing::info!("shutdown complete");
+}
@@ -0,0 +1,95 @@
+use crate::config::ClientSettings;
+use crate::context_action::ContextActionDefinition;
+use crate::proximity::ProximitySettings;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Moderation of a user, given back to the user with the same name when it reconnects to the new
+/// process
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UserSnapshot {
+    pub name: String,
+    pub mute: bool,
+    pub deaf: bool,
+    pub priority_speaker: bool,
+    pub instance: i32,
+    #[serde(default)]
+    pub global_targets: Vec<u8>,
+}
+
+/// Settings of a channel, given back to the channel with the same name when a client creates it
+/// again in the new process
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChannelSnapshot {
+    pub name: String,
+    pub proximity: Option<ProximitySettings>,
+    pub global: bool,
+}
+
+/// State that survives an upgrade or a restart with a state file, sessions and channel ids don't
+/// carry over so users and channels are matched by name
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    pub client_settings: ClientSettings,
+    pub context_actions: Vec<ContextActionDefinition>,
+    pub channels: Vec<ChannelSnapshot>,
+    pub users: Vec<UserSnapshot>,
+}
+
+impl StateSnapshot {
+    /// Reads the snapshot a previous run left in the state file, none if there is no file yet
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        match std::fs::read(path) {
+            Ok(content) => Ok(Some(serde_json::from_slice(&content)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Writes the snapshot next to the state file and moves it in place, a crash while writing
+    /// leaves the previous one intact
+    pub async fn save(&self, path: &Path) -> io::Result<()> {
+        let mut staged = path.as_os_str().to_owned();
+        staged.push(".tmp");
+        let staged = PathBuf::from(staged);
+
+        tokio::fs::write(&staged, serde_json::to_vec(self)?).await?;
+        tokio::fs::rename(&staged, path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn saved_state_is_loaded_back() {
+        let path = std::env::temp_dir().join(format!("zumble-state-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        assert!(StateSnapshot::load(&path).unwrap().is_none());
+
+        let snapshot = StateSnapshot {
+            users: vec![UserSnapshot {
+                name: "[1] player".to_string(),
+                mute: true,
+                deaf: false,
+                priority_speaker: false,
+                instance: 3,
+                global_targets: vec![2],
+            }],
+            ..StateSnapshot::default()
+        };
+
+        snapshot.save(&path).await.unwrap();
+
+        let loaded = StateSnapshot::load(&path).unwrap().unwrap();
+        assert_eq!(loaded.users.len(), 1);
+        assert_eq!(loaded.users[0].instance, 3);
+        assert!(loaded.users[0].mute);
+        assert_eq!(loaded.users[0].global_targets, vec![2]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,18 +1,35 @@
+use crate::audit::{AuditAction, AuditActor, AuditLog, AuditTarget};
+use crate::certificate::CertificateHash;
 use crate::channel::{Channel, ChannelRef};
 use crate::client::{Client, ClientRef};
+use crate::config::{ClientSettings, Config};
+use crate::context_action::{ContextActionDefinition, ContextActionEvent};
 use crate::crypt::CryptState;
+use crate::deadline::{DeadlineKind, Deadlines};
 use crate::error::MumbleError;
+use crate::event::{Event, EventBus};
+use crate::health::Health;
 use crate::message::ClientMessage;
-use crate::proto::mumble::{Authenticate, ChannelRemove, ChannelState, CodecVersion, UserRemove, Version};
+use crate::position::PositionUpdate;
+use crate::proto::mumble::{Authenticate, ChannelRemove, ChannelState, CodecVersion, PluginDataTransmission, UserRemove, Version};
 use crate::proto::{message_to_bytes, MessageKind};
-use crate::server::constants::MAX_CLIENTS;
+use crate::ratelimit::RateLimiter;
+use crate::server::constants::{MAX_CLIENTS, MAX_CONTEXT_ACTION_EVENTS, RESTORED_STATE_TTL, TALKING_TIMEOUT};
+use crate::snapshot::{ChannelSnapshot, StateSnapshot, UserSnapshot};
+use crate::talking::TalkingChange;
+use crate::user_ref::UserRef;
 use crate::voice::{ServerBound, VoicePacket};
+use crate::webhook::{DisconnectReason, WebhookEvent, Webhooks};
+use arc_swap::ArcSwap;
 use bytes::BytesMut;
+use parking_lot::Mutex;
 use protobuf::Message;
-use scc::{HashCache, HashMap};
+use scc::{HashCache, HashMap, HashSet};
+use std::collections::VecDeque;
 use std::net::{IpAddr, SocketAddr};
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use tokio::io::WriteHalf;
 use tokio::net::{TcpStream, UdpSocket};
 use tokio::sync::mpsc::Sender;
@@ -63,17 +80,39 @@ pub struct ServerState {
     pub clients: HashMap<u32, ClientRef>,
     pub clients_without_udp: HashMap<u32, ClientRef>,
     pub clients_by_socket: HashMap<SocketAddr, ClientRef>,
+    pub clients_by_server_id: HashMap<u32, ClientRef>,
+    /// Sessions of each username in the order they connected, position updates address players by
+    /// name and get the latest one
+    pub clients_by_name: HashMap<String, Vec<ClientRef>>,
     // pub clients_by_peer: HashMap<IpAddr, AtomicU32>,
     pub channels: HashMap<u32, Arc<Channel>>,
     pub codec_state: Arc<RwLock<CodecState>>,
     pub socket: Arc<UdpSocket>,
+    pub config: Config,
+    pub client_settings: ArcSwap<ClientSettings>,
+    #[allow(dead_code)]
     pub logs: HashCache<SocketAddr, ()>,
+    pub context_actions: HashMap<String, ContextActionDefinition>,
+    pub server_list_ping_limits: HashMap<IpAddr, RateLimiter>,
+    pub events: EventBus,
+    pub webhooks: Webhooks,
+    pub health: Health,
+    pub deadlines: Deadlines,
+    pub audit: AuditLog,
+    /// SHA256 fingerprints of the certificates banned by admins, they last until the server restarts
+    pub banned_certificates: HashSet<String>,
+    /// Moderation of the users of the process this one replaced, applied when they reconnect
+    restored_users: HashMap<String, (Instant, UserSnapshot)>,
+    /// Settings of the channels of the process this one replaced, applied when they are created again
+    restored_channels: HashMap<String, (Instant, ChannelSnapshot)>,
+    context_action_events: Mutex<VecDeque<ContextActionEvent>>,
     session_count: AtomicU32,
     channel_count: AtomicU32,
+    context_action_event_count: AtomicU64,
 }
 
 impl ServerState {
-    pub fn new(socket: Arc<UdpSocket>) -> Self {
+    pub fn new(socket: Arc<UdpSocket>, config: Config, client_settings: ClientSettings, webhooks: Webhooks, audit: AuditLog) -> Self {
         let channels = HashMap::new();
         channels.upsert(
             0,
@@ -87,15 +126,32 @@ impl ServerState {
             logs: HashCache::with_capacity(500, 1000),
             clients_without_udp: HashMap::with_capacity(MAX_CLIENTS),
             clients_by_socket: HashMap::with_capacity(MAX_CLIENTS),
+            clients_by_server_id: HashMap::with_capacity(MAX_CLIENTS),
+            clients_by_name: HashMap::with_capacity(MAX_CLIENTS),
             // clients_by_peer: HashMap::with_capacity(MAX_CLIENTS),
             channels,
             codec_state: Arc::new(RwLock::new(CodecState::default())),
             socket,
+            config,
+            client_settings: ArcSwap::from_pointee(client_settings),
+            context_actions: HashMap::new(),
+            server_list_ping_limits: HashMap::new(),
+            events: EventBus::default(),
+            webhooks,
+            health: Health::default(),
+            deadlines: Deadlines::default(),
+            audit,
+            banned_certificates: HashSet::new(),
+            restored_users: HashMap::new(),
+            restored_channels: HashMap::new(),
+            context_action_events: Mutex::new(VecDeque::with_capacity(MAX_CONTEXT_ACTION_EVENTS)),
             session_count: AtomicU32::new(1),
             channel_count: AtomicU32::new(1),
+            context_action_event_count: AtomicU64::new(1),
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn add_client(
         &self,
         version: Version,
@@ -104,22 +160,71 @@ impl ServerState {
         write: WriteHalf<TlsStream<TcpStream>>,
         publisher: Sender<ClientMessage>,
         peer_ip: IpAddr,
+        certificate_hash: Option<CertificateHash>,
     ) -> ClientRef {
+        // like murmur, a user connecting again with the same name and certificate replaces its old
+        // session instead of showing up twice
+        if let Some(hash) = &certificate_hash {
+            let replaced = self
+                .clients_by_name
+                .read(authenticate.get_username(), |_, sessions| {
+                    sessions
+                        .iter()
+                        .filter(|other| other.certificate_hash.as_ref().is_some_and(|other| other.sha256 == hash.sha256))
+                        .map(|other| other.session_id)
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+
+            for session_id in replaced {
+                self.disconnect(session_id, DisconnectReason::Replaced);
+            }
+        }
+
         let session_id = self.get_free_session_id();
+        let server_id = self.config.get_server_id(authenticate.get_username());
+
+        let admin = certificate_hash.as_ref().is_some_and(|hash| self.config.is_admin_certificate(hash));
 
         let client = Arc::new(Client::new(
             version,
             authenticate,
             session_id,
+            server_id,
             0,
             crypt_state,
             write,
             Arc::clone(&self.socket),
             publisher,
+            certificate_hash,
+            admin,
+            peer_ip,
         ));
 
+        if let Some((_, (_, restored))) = self.restored_users.remove(client.get_name().as_str()) {
+            client.set_mute(restored.mute);
+            client.set_deaf(restored.deaf);
+            client.set_priority_speaker(restored.priority_speaker);
+            client.set_instance(restored.instance);
+
+            for target in restored.global_targets {
+                client.set_global_target(target, true);
+            }
+        }
+
         crate::metrics::CLIENTS_TOTAL.inc();
+        crate::metrics::client_version_connected(&client.version.metric_label());
         self.clients.upsert(session_id, Arc::clone(&client));
+
+        if let Some(server_id) = server_id {
+            self.clients_by_server_id.upsert(server_id, Arc::clone(&client));
+        }
+
+        self.clients_by_name
+            .entry(client.get_name().as_ref().clone())
+            .or_default()
+            .get_mut()
+            .push(Arc::clone(&client));
         // if let Some(ref_count) = self.clients_by_peer.get(&peer_ip) {
         //     ref_count.fetch_add(1, Ordering::SeqCst);
         // } else {
@@ -128,9 +233,73 @@ impl ServerState {
 
         self.clients_without_udp.upsert(session_id, Arc::clone(&client));
 
+        self.deadlines.schedule(
+            client.connected_at + self.config.ping_timeout.min(self.config.udp_timeout),
+            session_id,
+            DeadlineKind::Liveness,
+        );
+
+        self.emit(Event::UserConnected {
+            session: session_id,
+            name: client.get_name().as_ref().clone(),
+        });
+
+        self.webhooks.send(WebhookEvent::UserConnected {
+            session: session_id,
+            name: client.get_name().as_ref().clone(),
+            ip: client.peer_ip,
+            release: client.version.release.clone(),
+            certificate_hash: client.certificate_hash.clone(),
+            admin: client.admin,
+        });
+
         client
     }
 
+    pub fn emit(&self, event: Event) {
+        self.events.emit(event);
+    }
+
+    pub fn emit_talking_change(&self, client: &Client, change: TalkingChange) {
+        self.emit(match change {
+            TalkingChange::Started(target) => {
+                self.deadlines
+                    .schedule(Instant::now() + TALKING_TIMEOUT, client.session_id, DeadlineKind::Talking);
+
+                Event::TalkingStarted {
+                    session: client.session_id,
+                    target,
+                }
+            }
+            TalkingChange::Stopped(talk_time) => Event::TalkingStopped {
+                session: client.session_id,
+                talk_time: talk_time.as_millis(),
+            },
+        });
+    }
+
+    pub fn set_client_mute(&self, client: &Client, mute: bool) {
+        if client.is_muted() != mute {
+            client.set_mute(mute);
+
+            self.emit(Event::MuteChanged {
+                session: client.session_id,
+                mute,
+            });
+        }
+    }
+
+    pub fn set_client_deaf(&self, client: &Client, deaf: bool) {
+        if client.is_deaf() != deaf {
+            client.set_deaf(deaf);
+
+            self.emit(Event::DeafChanged {
+                session: client.session_id,
+                deaf,
+            });
+        }
+    }
+
     pub fn add_channel(&self, state: &ChannelState) -> ChannelRef {
         let channel_id = self.get_free_channel_id();
         let channel = Arc::new(Channel::new(
@@ -143,22 +312,36 @@ impl ServerState {
 
         tracing::debug!("Created channel {} with name {}", channel_id, state.get_name().to_string());
 
+        if let Some((_, (_, restored))) = self.restored_channels.remove(&channel.name) {
+            channel.proximity.store(restored.proximity.map(Arc::new));
+            channel.set_global(restored.global);
+        }
+
         self.channels.upsert(channel_id, channel.clone());
 
+        self.emit(Event::ChannelCreated {
+            channel_id,
+            parent_id: channel.parent_id,
+            name: channel.name.clone(),
+        });
+
+        self.webhooks.send(WebhookEvent::ChannelCreated {
+            channel_id,
+            parent_id: channel.parent_id,
+            name: channel.name.clone(),
+        });
+
         channel
     }
 
     pub fn get_client_by_name(&self, name: &str) -> Option<ClientRef> {
-        let client = self.clients.any_entry(|_k, client| client.authenticate.get_username() == name);
-
-        if let Some(cl) = client {
-            return Some(cl.clone());
-        }
-
-        None
+        self.clients_by_name.read(name, |_, sessions| sessions.last().cloned()).flatten()
     }
 
     pub fn set_client_socket(&self, client: ClientRef, addr: SocketAddr) {
+        // the udp connection is back, so the crypt resets don't count as repeated anymore
+        client.crypt_resets.store(0, Ordering::Relaxed);
+
         let socket_lock = client.udp_socket_addr.swap(Some(Arc::new(addr)));
         if let Some(exiting_addr) = socket_lock {
             self.clients_by_socket.remove(exiting_addr.as_ref());
@@ -187,14 +370,195 @@ impl ServerState {
         Ok(())
     }
 
+    /// Relays plugin data to the receivers listed in the message, unknown sessions and the sender
+    /// itself are skipped
+    pub fn send_plugin_data(&self, message: &PluginDataTransmission) -> Result<(), MumbleError> {
+        let mut receivers = message.get_receiverSessions().to_vec();
+        receivers.sort_unstable();
+        receivers.dedup();
+
+        // like murmur, receivers don't get to know who else got the data
+        let mut relayed = message.clone();
+        relayed.clear_receiverSessions();
+
+        let bytes = message_to_bytes(MessageKind::PluginDataTransmission, &relayed)?;
+
+        for session in receivers {
+            if session == message.get_senderSession() {
+                continue;
+            }
+
+            if let Some(client) = self.clients.get(&session) {
+                match client.publisher.try_send(ClientMessage::SendMessage {
+                    kind: MessageKind::PluginDataTransmission,
+                    payload: bytes.clone(),
+                }) {
+                    Ok(_) => {}
+                    Err(err) => {
+                        tracing::error!("failed to send plugin data to {}: {}", client.get(), err);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Changes the client settings and sends the new ServerConfig and SuggestConfig to every
+    /// client, returns the previous and the new settings. The update can run more than once when
+    /// another update races it
+    pub fn update_client_settings(
+        &self,
+        update: impl Fn(&mut ClientSettings),
+    ) -> Result<(Arc<ClientSettings>, Arc<ClientSettings>), MumbleError> {
+        let mut updated = None;
+
+        let previous = self.client_settings.rcu(|current| {
+            let mut settings = current.as_ref().clone();
+            update(&mut settings);
+
+            let settings = Arc::new(settings);
+            updated = Some(Arc::clone(&settings));
+
+            settings
+        });
+
+        let settings = updated.unwrap_or_else(|| Arc::clone(&previous));
+
+        self.broadcast_message(MessageKind::ServerConfig, &settings.get_server_config())?;
+
+        // an empty SuggestConfig takes back the suggestions that got removed
+        let suggest_config = settings.get_suggest_config().unwrap_or_default();
+        self.broadcast_message(MessageKind::SuggestConfig, &suggest_config)?;
+
+        Ok((previous, settings))
+    }
+
+    /// Captures what should survive an upgrade to a new process
+    pub fn snapshot(&self) -> StateSnapshot {
+        let mut snapshot = StateSnapshot {
+            client_settings: self.client_settings.load().as_ref().clone(),
+            ..StateSnapshot::default()
+        };
+
+        self.context_actions.scan(|_, definition| {
+            snapshot.context_actions.push(definition.clone());
+        });
+
+        self.channels.scan(|_, channel| {
+            snapshot.channels.push(ChannelSnapshot {
+                name: channel.name.clone(),
+                proximity: channel.proximity.load_full().as_deref().copied(),
+                global: channel.is_global(),
+            });
+        });
+
+        self.clients.scan(|_, client| {
+            snapshot.users.push(UserSnapshot {
+                name: client.get_name().as_ref().clone(),
+                mute: client.is_muted(),
+                deaf: client.is_deaf(),
+                priority_speaker: client.is_priority_speaker(),
+                instance: client.get_instance(),
+                global_targets: client.get_global_targets(),
+            });
+        });
+
+        snapshot
+    }
+
+    /// Restores the state of the process this one replaced or of the previous run, this happens
+    /// before any client connects
+    pub fn restore(&self, snapshot: StateSnapshot) {
+        let now = Instant::now();
+
+        self.client_settings.store(Arc::new(snapshot.client_settings));
+
+        for definition in snapshot.context_actions {
+            self.context_actions.upsert(definition.action.clone(), definition);
+        }
+
+        for channel in snapshot.channels {
+            self.restored_channels.upsert(channel.name.clone(), (now, channel));
+        }
+
+        for user in snapshot.users {
+            self.restored_users.upsert(user.name.clone(), (now, user));
+        }
+    }
+
+    /// Forgets the restored users and channels that didn't come back in time
+    pub fn clear_expired_restores(&self) {
+        self.restored_users
+            .retain(|_, (restored_at, _)| restored_at.elapsed() < RESTORED_STATE_TTL);
+        self.restored_channels
+            .retain(|_, (restored_at, _)| restored_at.elapsed() < RESTORED_STATE_TTL);
+    }
+
+    /// Registers (or replaces) a context action and pushes it to every client
+    pub fn add_context_action(&self, definition: ContextActionDefinition) -> Result<(), MumbleError> {
+        let modify = definition.get_context_action_add();
+
+        self.context_actions.upsert(definition.action.clone(), definition);
+
+        self.broadcast_message(MessageKind::ContextActionModify, &modify)
+    }
+
+    /// Removes a context action from every client, returns false if it wasn't registered
+    pub fn remove_context_action(&self, action: &str) -> Result<bool, MumbleError> {
+        match self.context_actions.remove(action) {
+            Some((_, definition)) => {
+                self.broadcast_message(MessageKind::ContextActionModify, &definition.get_context_action_remove())?;
+
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub fn push_context_action_event(&self, action: &str, actor: &ClientRef, target: Option<&ClientRef>, channel_id: Option<u32>) {
+        // the id is taken under the lock so the queue stays ordered by id, pollers asking for the
+        // events after the last id they saw would miss one queued out of order
+        let mut events = self.context_action_events.lock();
+
+        let event = ContextActionEvent {
+            id: self.context_action_event_count.fetch_add(1, Ordering::SeqCst),
+            action: action.to_string(),
+            actor_session: actor.session_id,
+            actor_name: actor.get_name().as_ref().clone(),
+            actor_admin: actor.admin,
+            target_session: target.map(|target| target.session_id),
+            target_name: target.map(|target| target.get_name().as_ref().clone()),
+            channel_id,
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis(),
+        };
+
+        // drop the oldest events if nobody picked them up
+        if events.len() >= MAX_CONTEXT_ACTION_EVENTS {
+            events.pop_front();
+        }
+
+        events.push_back(event);
+    }
+
+    /// Returns the queued context action events with an id greater than `after`
+    pub fn get_context_action_events(&self, after: u64) -> Vec<ContextActionEvent> {
+        let events = self.context_action_events.lock();
+
+        events.iter().filter(|event| event.id > after).cloned().collect()
+    }
+
     fn handle_client_left_channel(&self, client_session: u32, leave_channel_id: u32) -> Option<u32> {
+        self.emit(Event::ChannelLeft {
+            session: client_session,
+            channel_id: leave_channel_id,
+        });
+
         if let Some(channel) = self.channels.get(&leave_channel_id) {
             // remove the client from the channel
             channel.clients.remove(&client_session);
 
-            if channel.parent_id.is_none() {
-                return None;
-            };
+            channel.parent_id?;
 
             // if the channel isn't temporary then we want to keep it
             if !channel.temporary || !channel.get_clients().is_empty() {
@@ -208,6 +572,14 @@ impl ServerState {
 
         self.channels.remove(&leave_channel_id);
 
+        self.emit(Event::ChannelRemoved {
+            channel_id: leave_channel_id,
+        });
+
+        self.webhooks.send(WebhookEvent::ChannelRemoved {
+            channel_id: leave_channel_id,
+        });
+
         match self.broadcast_message(MessageKind::ChannelRemove, &channel_remove) {
             Ok(_) => (),
             Err(e) => tracing::error!("failed to send channel remove: {:?}", e),
@@ -232,6 +604,16 @@ impl ServerState {
             return Err(MumbleError::ChannelDoesntExist);
         }
 
+        if leave_channel_id.is_some() {
+            // who heard the speaker in the previous channel says nothing about the new one
+            client.proximity_listeners.lock().clear();
+
+            self.emit(Event::ChannelJoined {
+                session: client.session_id,
+                channel_id: channel,
+            });
+        }
+
         // Broadcast new user state
         let user_state = client.get_user_state();
         match self.broadcast_message(MessageKind::UserState, &user_state) {
@@ -250,6 +632,96 @@ impl ServerState {
         Ok(())
     }
 
+    /// Changes the priority speaker status of the client and tells everyone about it, the mumble
+    /// clients lower the volume of other speakers while a priority speaker talks
+    pub fn set_client_priority_speaker(&self, client: &ClientRef, priority_speaker: bool) -> Result<(), MumbleError> {
+        if client.is_priority_speaker() == priority_speaker {
+            return Ok(());
+        }
+
+        tracing::info!("Client: {} priority speaker set to {}", client.session_id, priority_speaker);
+
+        client.set_priority_speaker(priority_speaker);
+
+        let user_state = client.get_user_state();
+
+        self.broadcast_message(MessageKind::UserState, &user_state)
+    }
+
+    /// Checks the certificates banned at startup and the ones banned by admins since
+    pub fn is_banned_certificate(&self, hash: &CertificateHash) -> bool {
+        self.config.is_banned_certificate(hash) || self.banned_certificates.contains(&hash.sha256)
+    }
+
+    /// Kicks the client off the server on behalf of an admin, when banning its certificate can't
+    /// connect again until the server restarts
+    pub fn remove_client(&self, actor: &Client, client: &Client, reason: &str, ban: bool) -> Result<(), MumbleError> {
+        let mut remove = UserRemove::new();
+        remove.set_session(client.session_id);
+        remove.set_actor(actor.session_id);
+        remove.set_reason(reason.to_string());
+        remove.set_ban(ban);
+
+        // the client is told why before the disconnect closes its connection
+        let _ = client.publisher.try_send(ClientMessage::SendMessage {
+            kind: MessageKind::UserRemove,
+            payload: message_to_bytes(MessageKind::UserRemove, &remove)?,
+        });
+
+        let (action, event, disconnect_reason) = if ban {
+            match &client.certificate_hash {
+                Some(hash) => {
+                    let _ = self.banned_certificates.insert(hash.sha256.clone());
+                }
+                None => tracing::warn!("client {} has no certificate, the ban only kicks it", client),
+            }
+
+            (
+                AuditAction::Ban,
+                WebhookEvent::UserBanned {
+                    session: client.session_id,
+                    name: client.get_name().as_ref().clone(),
+                    certificate_hash: client.certificate_hash.clone(),
+                    actor_session: actor.session_id,
+                    actor_name: actor.get_name().as_ref().clone(),
+                    reason: reason.to_string(),
+                },
+                DisconnectReason::Banned,
+            )
+        } else {
+            (
+                AuditAction::Kick,
+                WebhookEvent::UserKicked {
+                    session: client.session_id,
+                    name: client.get_name().as_ref().clone(),
+                    actor_session: actor.session_id,
+                    actor_name: actor.get_name().as_ref().clone(),
+                    reason: reason.to_string(),
+                },
+                DisconnectReason::Kicked,
+            )
+        };
+
+        tracing::info!("client {} removed by {} (ban: {}): {}", client, actor, ban, reason);
+
+        self.audit.record(
+            AuditActor::Client {
+                session: actor.session_id,
+                name: actor.get_name().as_ref().clone(),
+            },
+            action,
+            AuditTarget::user(client),
+            (),
+            reason,
+        );
+
+        self.webhooks.send(event);
+
+        self.disconnect(client.session_id, disconnect_reason);
+
+        Ok(())
+    }
+
     pub fn get_channel_by_name(&self, name: &str) -> Option<ChannelRef> {
         let client = self.channels.any_entry(|_k, channel| channel.name == name);
 
@@ -308,6 +780,49 @@ impl ServerState {
         Ok(None)
     }
 
+    pub fn get_client(&self, user: &UserRef) -> Option<ClientRef> {
+        match user {
+            UserRef::Session(session) => self.clients.get(session).map(|client| client.get().clone()),
+            UserRef::ServerId(server_id) => self.clients_by_server_id.get(server_id).map(|client| client.get().clone()),
+            UserRef::Name(name) => self.get_client_by_name(name),
+        }
+    }
+
+    /// Stores a position pushed by the game server, returns false if the client doesn't exist or
+    /// the position is invalid
+    pub fn apply_position_update(&self, update: &PositionUpdate) -> bool {
+        let Some(coordinates) = update.coordinates() else {
+            return false;
+        };
+
+        match update.user().and_then(|user| self.get_client(&user)) {
+            Some(client) => {
+                client.set_server_position(coordinates, update.routing_bucket);
+
+                if let Some(routing_bucket) = update.routing_bucket {
+                    client.set_instance(routing_bucket);
+                }
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Checks the per ip rate limit of the server list ping, always allowed without a limit
+    pub fn allow_server_list_ping(&self, ip: IpAddr) -> bool {
+        let Some(rate) = self.config.ping_rate_limit else {
+            return true;
+        };
+
+        let mut limiter = self
+            .server_list_ping_limits
+            .entry(ip)
+            .or_insert_with(|| RateLimiter::new(rate, rate.max(1.0)));
+
+        limiter.get_mut().try_acquire()
+    }
+
     pub fn get_client_by_socket(&self, socket_addr: &SocketAddr) -> Option<ClientRef> {
         self.clients_by_socket.get(socket_addr).map(|client| client.clone())
     }
@@ -334,7 +849,7 @@ impl ServerState {
             let mut try_buf = bytes.clone();
             let decrypt_result = {
                 let mut crypt_state = c.crypt_state.lock();
-                crypt_state.decrypt(&mut try_buf)
+                crypt_state.decrypt(&mut try_buf, c.udp_format)
             };
 
             match decrypt_result {
@@ -362,6 +877,8 @@ impl ServerState {
     /// Resets the clients crypt state and removes their udp socket so we no longer take invalid
     /// data from the UDP stream
     pub async fn reset_client_crypt(&self, client: ClientRef) -> Result<(), MumbleError> {
+        crate::metrics::CRYPT_RESETS_TOTAL.inc();
+
         self.clients_without_udp.upsert(client.session_id, Arc::clone(&client));
 
         // swap out the clients socket with none so we don't try to reuse the old socket
@@ -372,12 +889,14 @@ impl ServerState {
             self.remove_client_by_socket(&address);
         }
 
+        self.emit(Event::CryptReset {
+            session: client.session_id,
+        });
+
         client.send_crypt_setup(true).await
     }
 
-    pub fn disconnect(&self, client_session: u32) {
-        crate::metrics::CLIENTS_TOTAL.dec();
-
+    pub fn disconnect(&self, client_session: u32, reason: DisconnectReason) {
         let client = self.clients.remove(&client_session);
         self.clients_without_udp.remove(&client_session);
 
@@ -387,11 +906,23 @@ impl ServerState {
         });
 
         if let Some((_, client)) = client {
-            tracing::info!("Removing client {}", client);
+            tracing::info!("Removing client {} ({:?})", client, reason);
+
+            // a session can be disconnected twice, e.g. by the clean loop and then when its
+            // connection ends, it is only counted once
+            crate::metrics::CLIENTS_TOTAL.dec();
+
+            crate::metrics::client_version_disconnected(&client.version.metric_label());
+
+            let talking_change = { client.talking.lock().finish() };
+
+            if let Some(change) = talking_change {
+                self.emit_talking_change(&client, change);
+            }
 
             // This is a hack to get the publisher out of its loop, if its already out of its loop
             // then we don't care and we can just ignore it
-            let _ = client.publisher.try_send(ClientMessage::Disconnect); 
+            let _ = client.publisher.try_send(ClientMessage::Disconnect);
 
             let socket = client.udp_socket_addr.swap(None);
             // let mut should_remove = false;
@@ -409,9 +940,34 @@ impl ServerState {
                 // }
             }
 
+            if let Some(server_id) = client.server_id {
+                // the player may already be connected again with a new session
+                self.clients_by_server_id
+                    .remove_if(&server_id, |other| other.session_id == client_session);
+            }
+
+            // the other sessions with the same name stay reachable, the entry goes with the last one
+            self.clients_by_name.remove_if(client.get_name().as_str(), |sessions| {
+                sessions.retain(|other| other.session_id != client_session);
+
+                sessions.is_empty()
+            });
+
             let channel_id = client.channel_id.load(Ordering::Relaxed);
 
             self.broadcast_client_delete(client_session, channel_id);
+
+            self.emit(Event::UserDisconnected {
+                session: client_session,
+                name: client.get_name().as_ref().clone(),
+            });
+
+            self.webhooks.send(WebhookEvent::UserDisconnected {
+                session: client_session,
+                name: client.get_name().as_ref().clone(),
+                connected_for: client.connected_at.elapsed().as_secs(),
+                reason,
+            });
         }
     }
 
@@ -434,3 +990,86 @@ impl ServerState {
         self.channel_count.fetch_add(1, Ordering::SeqCst)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{connect_client, connect_with_certificate, test_state};
+
+    fn certificate(fingerprint: &str) -> Option<CertificateHash> {
+        Some(CertificateHash {
+            sha1: fingerprint.to_string(),
+            sha256: fingerprint.repeat(2),
+        })
+    }
+
+    #[actix_web::test]
+    async fn name_lookup_follows_the_latest_session() {
+        let state = test_state().await;
+        let name = UserRef::Name("[12] player".to_string());
+        let old = connect_client(&state, "[12] player").await;
+        let new = connect_client(&state, "[12] player").await;
+
+        assert_eq!(state.get_client(&name).unwrap().session_id, new.session_id);
+
+        state.disconnect(old.session_id, DisconnectReason::Closed);
+        assert_eq!(state.get_client(&name).unwrap().session_id, new.session_id);
+
+        state.disconnect(new.session_id, DisconnectReason::Closed);
+        assert!(state.get_client(&name).is_none());
+    }
+
+    #[actix_web::test]
+    async fn name_lookup_falls_back_to_an_older_session() {
+        let state = test_state().await;
+        let name = UserRef::Name("[12] player".to_string());
+        let old = connect_client(&state, "[12] player").await;
+        let new = connect_client(&state, "[12] player").await;
+
+        state.disconnect(new.session_id, DisconnectReason::Closed);
+        assert_eq!(state.get_client(&name).unwrap().session_id, old.session_id);
+
+        state.disconnect(old.session_id, DisconnectReason::Closed);
+        assert!(state.get_client(&name).is_none());
+    }
+
+    #[actix_web::test]
+    async fn same_name_and_certificate_replaces_the_session() {
+        let state = test_state().await;
+        let (old, _old_messages) = connect_with_certificate(&state, "player", certificate("aa")).await;
+        let (other, _other_messages) = connect_with_certificate(&state, "player", certificate("bb")).await;
+        let (new, _new_messages) = connect_with_certificate(&state, "player", certificate("aa")).await;
+
+        assert!(!state.clients.contains(&old.session_id));
+        assert!(state.clients.contains(&other.session_id));
+        let name = UserRef::Name("player".to_string());
+        assert_eq!(state.get_client(&name).unwrap().session_id, new.session_id);
+    }
+
+    #[actix_web::test]
+    async fn ban_tells_the_client_and_keeps_its_certificate_out() {
+        let state = test_state().await;
+        let admin = connect_client(&state, "admin").await;
+        let (target, mut messages) = connect_with_certificate(&state, "player", certificate("aa")).await;
+
+        state.remove_client(&admin, &target, "griefing", true).unwrap();
+
+        assert!(!state.clients.contains(&target.session_id));
+        assert!(state.is_banned_certificate(&certificate("aa").unwrap()));
+        assert!(!state.is_banned_certificate(&certificate("bb").unwrap()));
+
+        let mut told = false;
+
+        while let Ok(message) = messages.try_recv() {
+            told |= matches!(
+                message,
+                ClientMessage::SendMessage {
+                    kind: MessageKind::UserRemove,
+                    ..
+                }
+            );
+        }
+
+        assert!(told);
+    }
+}
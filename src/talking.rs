@@ -0,0 +1,112 @@
+use crate::server::constants::TALKING_TIMEOUT;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+pub enum TalkingChange {
+    /// Started talking to the given target
+    Started(u8),
+    /// Stopped talking after the given time
+    Stopped(Duration),
+}
+
+/// Tracks when a client talks, a talk spurt ends with the end of transmission bit of the voice
+/// packet or when no packet was received for [TALKING_TIMEOUT]
+#[derive(Debug, Default)]
+pub struct TalkingState {
+    talking_since: Option<Instant>,
+    last_voice_at: Option<Instant>,
+    last_target: Option<u8>,
+    total_talk_time: Duration,
+}
+
+impl TalkingState {
+    /// Records a voice packet and returns the changes it caused, in order
+    pub fn on_voice_packet(&mut self, target: u8, end_of_transmission: bool) -> Vec<TalkingChange> {
+        let now = Instant::now();
+        let mut changes = Vec::new();
+
+        if !self.is_talking() {
+            // the previous spurt timed out without anyone noticing yet
+            changes.extend(self.finish());
+
+            self.talking_since = Some(now);
+            changes.push(TalkingChange::Started(target));
+
+            crate::metrics::CLIENTS_TALKING.inc();
+            crate::metrics::TALK_SPURTS_TOTAL.inc();
+        }
+
+        self.last_voice_at = Some(now);
+        self.last_target = Some(target);
+
+        if end_of_transmission {
+            changes.extend(self.finish());
+        }
+
+        changes
+    }
+
+    pub fn is_talking(&self) -> bool {
+        self.talking_since.is_some() && self.last_voice_at.is_some_and(|at| at.elapsed() < TALKING_TIMEOUT)
+    }
+
+    /// When the current spurt times out if no other voice packet comes in
+    pub fn timeout_at(&self) -> Option<Instant> {
+        self.talking_since.and(self.last_voice_at).map(|at| at + TALKING_TIMEOUT)
+    }
+
+    /// Ends the spurt if it timed out
+    pub fn check_timeout(&mut self) -> Option<TalkingChange> {
+        if self.talking_since.is_some() && !self.is_talking() {
+            return self.finish();
+        }
+
+        None
+    }
+
+    /// Ends the current spurt, the talk time counts until the last voice packet
+    pub fn finish(&mut self) -> Option<TalkingChange> {
+        let (Some(since), Some(last_voice_at)) = (self.talking_since.take(), self.last_voice_at) else {
+            return None;
+        };
+
+        let talk_time = last_voice_at.duration_since(since);
+        self.total_talk_time += talk_time;
+
+        crate::metrics::CLIENTS_TALKING.dec();
+        crate::metrics::TALK_TIME_SECONDS_TOTAL.inc_by(talk_time.as_secs_f64());
+
+        Some(TalkingChange::Stopped(talk_time))
+    }
+
+    pub fn get_info(&self) -> TalkingInfo {
+        let talking = self.is_talking();
+        let current_talk_time = match (talking, self.talking_since) {
+            (true, Some(since)) => since.elapsed(),
+            _ => Duration::ZERO,
+        };
+
+        TalkingInfo {
+            talking,
+            current_talk_time: current_talk_time.as_millis(),
+            total_talk_time: (self.total_talk_time + current_talk_time).as_millis(),
+            last_target: self.last_target,
+            last_voice: self.last_voice_at.map(|at| at.elapsed().as_millis()),
+        }
+    }
+}
+
+/// Talking state of a client as returned by the http api, durations are in milliseconds
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TalkingInfo {
+    pub talking: bool,
+    /// Length of the current talk spurt
+    pub current_talk_time: u128,
+    /// Time spent talking since the client connected, including the current spurt
+    pub total_talk_time: u128,
+    /// Voice target of the last packet, 0 is the channel and 31 the loopback
+    pub last_target: Option<u8>,
+    /// Time since the last voice packet
+    pub last_voice: Option<u128>,
+}
@@ -0,0 +1,131 @@
+use crate::audit::AuditLog;
+use crate::certificate::CertificateHash;
+use crate::client::ClientRef;
+use crate::config::{ClientSettings, Config};
+use crate::crypt::CryptState;
+use crate::message::ClientMessage;
+use crate::proto::mumble::{Authenticate, Version};
+use crate::state::{ServerState, ServerStateRef};
+use crate::webhook::Webhooks;
+use rcgen::CertifiedKey;
+use regex::Regex;
+use rustls::crypto::{self, CryptoProvider};
+use rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer, ServerName};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::mpsc;
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+pub fn test_config() -> Config {
+    Config {
+        admin_certificates: Vec::new(),
+        banned_certificates: Vec::new(),
+        min_client_version: None,
+        ping_rate_limit: None,
+        ping_timeout: Duration::from_secs(30),
+        udp_timeout: Duration::from_secs(8),
+        position_ttl: Duration::from_secs(5),
+        trusted_positions_only: false,
+        priority_speaker_ducking: false,
+        server_id_pattern: Regex::new(r"^\[(\d+)\]").unwrap(),
+    }
+}
+
+/// A server state without clients, this has to be called from the actix runtime
+pub async fn test_state() -> ServerStateRef {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+    Arc::new(ServerState::new(
+        Arc::new(socket),
+        test_config(),
+        ClientSettings::default(),
+        Webhooks::new(Vec::new(), None),
+        AuditLog::default(),
+    ))
+}
+
+/// Adds a client with a real TLS connection over loopback, what the server sends or publishes to it
+/// is read and thrown away
+pub async fn connect_client(state: &ServerState, name: &str) -> ClientRef {
+    let (client, mut messages) = connect_listener(state, name).await;
+
+    // the server treats a client whose publisher is closed as gone
+    actix_rt::spawn(async move { while messages.recv().await.is_some() {} });
+
+    client
+}
+
+/// Adds a client like [connect_client] and returns what is published to it, like the voice packets
+/// routed to it
+pub async fn connect_listener(state: &ServerState, name: &str) -> (ClientRef, mpsc::Receiver<ClientMessage>) {
+    connect_with_certificate(state, name, None).await
+}
+
+/// Adds a client like [connect_listener] that presented a certificate with the given fingerprints
+pub async fn connect_with_certificate(
+    state: &ServerState,
+    name: &str,
+    certificate_hash: Option<CertificateHash>,
+) -> (ClientRef, mpsc::Receiver<ClientMessage>) {
+    let _ = CryptoProvider::install_default(crypto::ring::default_provider());
+
+    let CertifiedKey { cert, key_pair } = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_pair.serialize_der()));
+
+    let server_config = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(vec![cert.der().clone()], key)
+        .unwrap();
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(cert.der().clone()).unwrap();
+
+    let client_config = rustls::ClientConfig::builder().with_root_certificates(roots).with_no_client_auth();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let (accepted, connected) = tokio::join!(
+        async {
+            let (stream, _) = listener.accept().await.unwrap();
+
+            TlsAcceptor::from(Arc::new(server_config)).accept(stream).await.unwrap()
+        },
+        async {
+            let stream = TcpStream::connect(address).await.unwrap();
+
+            TlsConnector::from(Arc::new(client_config))
+                .connect(ServerName::try_from("localhost").unwrap(), stream)
+                .await
+                .unwrap()
+        }
+    );
+
+    actix_rt::spawn(async move {
+        let mut connected = connected;
+        let mut buffer = [0u8; 1024];
+
+        while matches!(connected.read(&mut buffer).await, Ok(read) if read > 0) {}
+    });
+
+    let (_, write) = tokio::io::split(accepted);
+    let (publisher, messages) = mpsc::channel(64);
+
+    let mut authenticate = Authenticate::new();
+    authenticate.set_username(name.to_string());
+
+    let client = state.add_client(
+        Version::new(),
+        authenticate,
+        CryptState::default(),
+        write,
+        publisher,
+        IpAddr::V4(Ipv4Addr::LOCALHOST),
+        certificate_hash,
+    );
+
+    (client, messages)
+}
@@ -0,0 +1,228 @@
+use crate::server::constants::HANDOVER_TIMEOUT;
+use crate::snapshot::StateSnapshot;
+use crate::state::ServerState;
+use actix_server::ServerHandle;
+use nix::sys::socket::{recvmsg, sendmsg, ControlMessage, ControlMessageOwned, MsgFlags};
+use std::fs::{DirBuilder, Permissions};
+use std::io::{self, IoSlice, IoSliceMut, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, UdpSocket, UnixListener, UnixStream};
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+
+/// Sent along the sockets, and back by the new process once it has them and the snapshot
+const HANDOVER_ACK: u8 = 1;
+
+/// What a new process takes over from the one it replaces
+pub struct TakenOver {
+    pub tcp_listener: TcpListener,
+    pub udp_socket: UdpSocket,
+    pub snapshot: StateSnapshot,
+}
+
+/// Connection to the process being replaced, it closes it once it let go of the listeners that
+/// aren't handed over
+pub struct Predecessor {
+    stream: UnixStream,
+}
+
+impl Predecessor {
+    /// Waits until the http, metrics and position listeners of the previous process are closed so
+    /// this process can bind them
+    pub async fn wait_for_release(mut self) -> io::Result<()> {
+        let mut rest = Vec::new();
+
+        timeout(HANDOVER_TIMEOUT, self.stream.read_to_end(&mut rest))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "the previous process didn't release its listeners"))??;
+
+        Ok(())
+    }
+}
+
+/// Connects to the upgrade socket of the running process and takes over its mumble TCP listener,
+/// its UDP socket and a snapshot of its state
+pub fn take_over(path: &Path) -> io::Result<(TakenOver, Predecessor)> {
+    let mut stream = std::os::unix::net::UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(HANDOVER_TIMEOUT))?;
+
+    let mut marker = [0u8; 1];
+    let mut iov = [IoSliceMut::new(&mut marker)];
+    let mut cmsg = nix::cmsg_space!([RawFd; 2]);
+
+    let message = recvmsg::<()>(stream.as_raw_fd(), &mut iov, Some(&mut cmsg), MsgFlags::empty())?;
+
+    let mut fds = Vec::new();
+
+    for cmsg in message.cmsgs()? {
+        if let ControlMessageOwned::ScmRights(received) = cmsg {
+            // SAFETY: the descriptors were just received from the previous process, nothing else
+            // in this process owns them. Owning them right away closes them on any error below
+            fds.extend(received.into_iter().map(|fd| unsafe { OwnedFd::from_raw_fd(fd) }));
+        }
+    }
+
+    let received = fds.len();
+
+    let Ok([tcp_fd, udp_fd]) = <[OwnedFd; 2]>::try_from(fds) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("expected a tcp and a udp socket, got {} descriptors", received),
+        ));
+    };
+
+    let tcp_listener = std::net::TcpListener::from(tcp_fd);
+    let udp_socket = std::net::UdpSocket::from(udp_fd);
+
+    tcp_listener.set_nonblocking(true)?;
+    udp_socket.set_nonblocking(true)?;
+
+    let mut length = [0u8; 4];
+    stream.read_exact(&mut length)?;
+
+    let mut snapshot = vec![0u8; u32::from_be_bytes(length) as usize];
+    stream.read_exact(&mut snapshot)?;
+
+    let snapshot = serde_json::from_slice(&snapshot)?;
+
+    stream.write_all(&[HANDOVER_ACK])?;
+    stream.set_nonblocking(true)?;
+
+    Ok((
+        TakenOver {
+            tcp_listener: TcpListener::from_std(tcp_listener)?,
+            udp_socket: UdpSocket::from_std(udp_socket)?,
+            snapshot,
+        },
+        Predecessor {
+            stream: UnixStream::from_std(stream)?,
+        },
+    ))
+}
+
+/// Waits for a new process on the upgrade socket and hands it the mumble listeners and a snapshot
+/// of the state, then closes the listeners it has to bind again (http, metrics and positions) and
+/// stops receiving voice. Returns once the clients of this process have to be closed so they
+/// reconnect to the new one, never when the upgrade socket can't be used.
+///
+/// Only the listening sockets are handed over, not the live sessions: the TLS connections and the
+/// crypt states stay in this process, so its clients drop and reconnect to the new one. What the
+/// handover saves is the downtime of the listeners, new and reconnecting clients are accepted
+/// without a gap, and the moderation and channel settings carried by the snapshot.
+pub async fn serve_handover(
+    path: PathBuf,
+    state: &ServerState,
+    (tcp_fd, udp_fd): (RawFd, RawFd),
+    tcp_server: &ServerHandle,
+    servers: &[ServerHandle],
+    tasks: &[JoinHandle<()>],
+) {
+    let successor = match accept_successor(&path, state, tcp_fd, udp_fd, tcp_server).await {
+        Ok(successor) => successor,
+        Err(e) => {
+            tracing::error!("upgrade socket {} error: {}", path.display(), e);
+
+            return futures::future::pending().await;
+        }
+    };
+
+    for server in servers {
+        server.stop(false).await;
+    }
+
+    // the udp socket is shared with the new process now, the voice it receives belongs to it
+    for task in tasks {
+        task.abort();
+    }
+
+    tracing::info!("handed the sockets over to the new process");
+
+    // closing the connection tells the new process it can bind the released listeners
+    drop(successor);
+}
+
+/// Binds the upgrade socket so that only this user can ever connect to it, whoever connects gets
+/// the sockets of the server
+fn bind_private(path: &Path) -> io::Result<UnixListener> {
+    // a socket left behind by a previous run would make the bind fail
+    if std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        let _ = std::fs::remove_file(path);
+    }
+
+    // the socket is created with the permissions of the umask, it is bound in a directory only this
+    // user can enter and moved in place once it is restricted
+    let mut staging = path.as_os_str().to_owned();
+    staging.push(format!(".{}", std::process::id()));
+    let staging = PathBuf::from(staging);
+
+    DirBuilder::new().mode(0o700).create(&staging)?;
+
+    let staged = staging.join("socket");
+    let result = UnixListener::bind(&staged).and_then(|listener| {
+        std::fs::set_permissions(&staged, Permissions::from_mode(0o600))?;
+        std::fs::rename(&staged, path)?;
+
+        Ok(listener)
+    });
+
+    let _ = std::fs::remove_file(&staged);
+    let _ = std::fs::remove_dir(&staging);
+
+    result
+}
+
+async fn accept_successor(
+    path: &Path,
+    state: &ServerState,
+    tcp_fd: RawFd,
+    udp_fd: RawFd,
+    tcp_server: &ServerHandle,
+) -> io::Result<UnixStream> {
+    let listener = bind_private(path)?;
+
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+
+        tracing::info!("a new process connected on the upgrade socket, handing over");
+
+        // once the new process has the listener, a client this process accepts would be closed
+        // right away with the others
+        tcp_server.pause().await;
+
+        match timeout(HANDOVER_TIMEOUT, hand_over(&mut stream, state, tcp_fd, udp_fd)).await {
+            Ok(Ok(_)) => return Ok(stream),
+            Ok(Err(e)) => tracing::error!("handover failed: {}", e),
+            Err(_) => tracing::error!("handover timed out"),
+        }
+
+        tcp_server.resume().await;
+    }
+}
+
+async fn hand_over(stream: &mut UnixStream, state: &ServerState, tcp_fd: RawFd, udp_fd: RawFd) -> io::Result<()> {
+    let snapshot = serde_json::to_vec(&state.snapshot())?;
+
+    stream.writable().await?;
+
+    sendmsg::<()>(
+        stream.as_raw_fd(),
+        &[IoSlice::new(&[HANDOVER_ACK])],
+        &[ControlMessage::ScmRights(&[tcp_fd, udp_fd])],
+        MsgFlags::empty(),
+        None,
+    )?;
+
+    stream.write_u32(snapshot.len() as u32).await?;
+    stream.write_all(&snapshot).await?;
+
+    match stream.read_u8().await? {
+        HANDOVER_ACK => Ok(()),
+        answer => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected handover answer {}", answer),
+        )),
+    }
+}
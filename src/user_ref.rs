@@ -0,0 +1,149 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::ops::Deref;
+use std::str::FromStr;
+use utoipa::openapi::{ObjectBuilder, RefOr, Schema, Type};
+use utoipa::{PartialSchema, ToSchema};
+
+/// How the http api addresses a client: `session:<id>`, `server:<id>` for the FiveM server id, or
+/// the exact username
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UserRef {
+    Session(u32),
+    ServerId(u32),
+    Name(String),
+}
+
+impl FromStr for UserRef {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_id = |prefix: &str| s.strip_prefix(prefix).and_then(|id| id.parse::<u32>().ok());
+
+        if let Some(session) = parse_id("session:") {
+            return Ok(UserRef::Session(session));
+        }
+
+        if let Some(server_id) = parse_id("server:") {
+            return Ok(UserRef::ServerId(server_id));
+        }
+
+        Ok(UserRef::Name(s.to_string()))
+    }
+}
+
+impl Display for UserRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UserRef::Session(session) => write!(f, "session:{}", session),
+            UserRef::ServerId(server_id) => write!(f, "server:{}", server_id),
+            UserRef::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+impl Serialize for UserRef {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for UserRef {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+
+        Ok(value.parse().unwrap_or_else(|never| match never {}))
+    }
+}
+
+impl PartialSchema for UserRef {
+    fn schema() -> RefOr<Schema> {
+        ObjectBuilder::new()
+            .schema_type(Type::String)
+            .description(Some("`session:<id>`, `server:<id>` for the FiveM server id, or the exact username"))
+            .examples(["session:12", "server:34", "John Doe"])
+            .into()
+    }
+}
+
+impl ToSchema for UserRef {
+    fn name() -> Cow<'static, str> {
+        Cow::Borrowed("UserRef")
+    }
+}
+
+/// User in the path of the routes that took a session id before users could be addressed in other
+/// ways, a bare number stays a session id there instead of being a username
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SessionUserRef(pub UserRef);
+
+impl FromStr for SessionUserRef {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<u32>() {
+            Ok(session) => Ok(SessionUserRef(UserRef::Session(session))),
+            Err(_) => s.parse().map(SessionUserRef),
+        }
+    }
+}
+
+impl Deref for SessionUserRef {
+    type Target = UserRef;
+
+    fn deref(&self) -> &UserRef {
+        &self.0
+    }
+}
+
+impl<'de> Deserialize<'de> for SessionUserRef {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+
+        Ok(value.parse().unwrap_or_else(|never| match never {}))
+    }
+}
+
+impl PartialSchema for SessionUserRef {
+    fn schema() -> RefOr<Schema> {
+        ObjectBuilder::new()
+            .schema_type(Type::String)
+            .description(Some(
+                "The session id, `session:<id>`, `server:<id>` for the FiveM server id, or the exact username",
+            ))
+            .examples(["12", "server:34", "John Doe"])
+            .into()
+    }
+}
+
+impl ToSchema for SessionUserRef {
+    fn name() -> Cow<'static, str> {
+        Cow::Borrowed("SessionUserRef")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_number_is_a_name() {
+        assert_eq!("12".parse::<UserRef>().unwrap(), UserRef::Name("12".to_string()));
+        assert_eq!("session:12".parse::<UserRef>().unwrap(), UserRef::Session(12));
+        assert_eq!("server:34".parse::<UserRef>().unwrap(), UserRef::ServerId(34));
+    }
+
+    #[test]
+    fn bare_number_is_a_session_for_session_routes() {
+        assert_eq!("12".parse::<SessionUserRef>().unwrap(), SessionUserRef(UserRef::Session(12)));
+        assert_eq!(
+            "server:34".parse::<SessionUserRef>().unwrap(),
+            SessionUserRef(UserRef::ServerId(34))
+        );
+        assert_eq!(
+            "John Doe".parse::<SessionUserRef>().unwrap(),
+            SessionUserRef(UserRef::Name("John Doe".to_string()))
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use crate::proto::mumble::Version;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Highest major, minor and patch versions that get their own metric series
+const MAX_LABEL_MAJOR: u64 = 9;
+const MAX_LABEL_MINOR: u64 = 20;
+const MAX_LABEL_PATCH: u64 = 9999;
+
+/// Version information a client sent when connecting
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ClientVersion {
+    pub release: String,
+    pub os: String,
+    pub os_version: String,
+    /// Legacy version format (`major << 16 | minor << 8 | patch`)
+    pub version_v1: Option<u32>,
+    /// New version format (`major << 48 | minor << 32 | patch << 16`), sent by mumble 1.5 and newer
+    pub version_v2: Option<u64>,
+}
+
+impl From<&Version> for ClientVersion {
+    fn from(version: &Version) -> Self {
+        Self {
+            release: version.get_release().to_string(),
+            os: version.get_os().to_string(),
+            os_version: version.get_os_version().to_string(),
+            version_v1: version.has_version_v1().then(|| version.get_version_v1()),
+            version_v2: version.has_version_v2().then(|| version.get_version_v2()),
+        }
+    }
+}
+
+impl ClientVersion {
+    /// Returns the protocol version in the v2 format, converting the legacy version if needed, or 0
+    /// if the client didn't send any version
+    pub fn protocol_version(&self) -> u64 {
+        match (self.version_v2, self.version_v1) {
+            (Some(version), _) => version,
+            (None, Some(version)) => v1_to_v2(version),
+            (None, None) => 0,
+        }
+    }
+
+    /// Label of the client version metrics, the `major.minor.patch` the release starts with (like
+    /// `1.5.634` of `1.5.634 (Linux)` or `1.3.0-rc2`). The release string is chosen by the client, so
+    /// anything else or implausibly high numbers are `other`
+    pub fn metric_label(&self) -> String {
+        let version = self.release.split_whitespace().next().unwrap_or_default();
+        let version = version.split(['-', '+']).next().unwrap_or_default();
+        let parts = version.split('.').map(|part| part.parse::<u64>().ok()).collect::<Vec<_>>();
+
+        match parts[..] {
+            [Some(major @ 1..=MAX_LABEL_MAJOR), Some(minor @ 0..=MAX_LABEL_MINOR), Some(patch @ 0..=MAX_LABEL_PATCH)] => {
+                format!("{}.{}.{}", major, minor, patch)
+            }
+            _ => "other".to_string(),
+        }
+    }
+
+    pub fn to_version(&self) -> Version {
+        let mut version = Version::new();
+        version.set_release(self.release.clone());
+        version.set_os(self.os.clone());
+        version.set_os_version(self.os_version.clone());
+
+        if let Some(version_v1) = self.version_v1 {
+            version.set_version_v1(version_v1);
+        }
+
+        if let Some(version_v2) = self.version_v2 {
+            version.set_version_v2(version_v2);
+        }
+
+        version
+    }
+}
+
+pub fn v1_to_v2(version: u32) -> u64 {
+    let major = (version >> 16) as u64;
+    let minor = ((version >> 8) & 0xff) as u64;
+    let patch = (version & 0xff) as u64;
+
+    major << 48 | minor << 32 | patch << 16
+}
+
+/// Converts a v2 version to the legacy format, the patch level is capped at 255
+pub fn v2_to_v1(version: u64) -> u32 {
+    let major = ((version >> 48) & 0xffff) as u32;
+    let minor = ((version >> 32) & 0xffff) as u32;
+    let patch = ((version >> 16) & 0xffff) as u32;
+
+    major << 16 | minor.min(0xff) << 8 | patch.min(0xff)
+}
+
+pub fn format_version(version: u64) -> String {
+    format!(
+        "{}.{}.{}",
+        (version >> 48) & 0xffff,
+        (version >> 32) & 0xffff,
+        (version >> 16) & 0xffff
+    )
+}
+
+/// Parses a `major.minor.patch` version into the v2 format, used for cli arguments
+pub fn parse_version(version: &str) -> Result<u64, String> {
+    let mut parts = version.trim().split('.');
+    let mut next = |name: &str| -> Result<u64, String> {
+        match parts.next() {
+            Some(part) => part
+                .parse::<u16>()
+                .map(u64::from)
+                .map_err(|_| format!("invalid {} version in {}", name, version)),
+            None => Ok(0),
+        }
+    };
+
+    let major = next("major")?;
+    let minor = next("minor")?;
+    let patch = next("patch")?;
+
+    Ok(major << 48 | minor << 32 | patch << 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_version(release: &str) -> ClientVersion {
+        ClientVersion {
+            release: release.to_string(),
+            // the label doesn't depend on the protocol version
+            version_v2: Some(parse_version("1.5.0").unwrap()),
+            ..ClientVersion::default()
+        }
+    }
+
+    #[test]
+    fn metric_label_is_bounded() {
+        assert_eq!(client_version("1.5.634").metric_label(), "1.5.634");
+        assert_eq!(client_version("1.4.287 (Linux)").metric_label(), "1.4.287");
+        assert_eq!(client_version("1.3.0-rc2").metric_label(), "1.3.0");
+        assert_eq!(client_version("1.5").metric_label(), "other");
+        assert_eq!(client_version("1.5.634.1").metric_label(), "other");
+        assert_eq!(client_version("1.300.0").metric_label(), "other");
+        assert_eq!(client_version("4242.1.0").metric_label(), "other");
+        assert_eq!(client_version("1.5.100000").metric_label(), "other");
+        assert_eq!(client_version("my custom client").metric_label(), "other");
+        assert_eq!(ClientVersion::default().metric_label(), "other");
+    }
+}
@@ -1,6 +1,7 @@
 //! Voice channel packets and codecs
 
 use crate::error::DecryptError;
+use crate::proto::mumble_udp::{Audio, Ping};
 use byteorder::ReadBytesExt;
 use bytes::Buf;
 use bytes::BufMut;
@@ -11,6 +12,8 @@ use std::io;
 use std::io::{Cursor, Read};
 use std::marker::PhantomData;
 
+use protobuf::Message;
+
 use super::varint::BufMutExt;
 use super::varint::ReadExt;
 
@@ -62,6 +65,52 @@ pub enum VoicePacketPayload {
     Opus(Bytes, bool),
 }
 
+/// The first protocol version (in the v2 format) that uses protobuf for voice packets.
+pub const PROTOBUF_UDP_VERSION: u64 = 1 << 48 | 5 << 32;
+
+/// Message type byte that starts every protobuf voice packet.
+pub const PROTOBUF_AUDIO: u8 = 0;
+pub const PROTOBUF_PING: u8 = 1;
+
+/// Context of a protobuf audio packet sent to clients.
+const PROTOBUF_CONTEXT_NORMAL: u32 = 0;
+const PROTOBUF_CONTEXT_WHISPER: u32 = 2;
+
+/// How voice packets are encoded on the wire, decided per client from the version it sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoicePacketFormat {
+    /// Binary format used before mumble 1.5.
+    Legacy,
+    /// `MumbleUDP` protobuf messages prefixed with their type, used since mumble 1.5.
+    Protobuf,
+}
+
+impl VoicePacketFormat {
+    /// Returns the format a client with the given protocol version (in the v2 format) uses.
+    pub fn for_version(version: u64) -> Self {
+        if version >= PROTOBUF_UDP_VERSION {
+            VoicePacketFormat::Protobuf
+        } else {
+            VoicePacketFormat::Legacy
+        }
+    }
+
+    pub fn decode<DecodeDst: VoicePacketDst>(&self, buf: &mut BytesMut) -> Result<VoicePacket<DecodeDst>, DecryptError> {
+        match self {
+            VoicePacketFormat::Legacy => decode_voice_packet(buf),
+            VoicePacketFormat::Protobuf => decode_voice_packet_protobuf(buf),
+        }
+    }
+
+    /// Encodes the packet, callers should check [VoicePacket::supports_format] first.
+    pub fn encode<EncodeDst: VoicePacketDst>(&self, item: &VoicePacket<EncodeDst>, dst: &mut BytesMut) {
+        match self {
+            VoicePacketFormat::Legacy => encode_voice_packet(item, dst),
+            VoicePacketFormat::Protobuf => encode_voice_packet_protobuf(item, dst),
+        }
+    }
+}
+
 /// Zero-sized struct indicating server-bound packet direction.
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct ServerBound;
@@ -79,6 +128,14 @@ pub trait VoicePacketDst: Default + PartialEq {
     fn read_session_id<T: Read + Sized>(buf: &mut T) -> Result<Self::SessionId, io::Error>;
     /// Writes session id to packets traveling in this direction.
     fn write_session_id(buf: &mut BytesMut, session_id: &Self::SessionId);
+    /// Reads the target (server-bound) or context (client-bound) of a protobuf audio packet.
+    fn read_protobuf_target(audio: &Audio) -> u32;
+    /// Writes the target (server-bound) or context (client-bound) of a protobuf audio packet.
+    fn write_protobuf_target(audio: &mut Audio, target: u8);
+    /// Reads session id of protobuf audio packets traveling in this direction.
+    fn read_protobuf_session_id(audio: &Audio) -> Self::SessionId;
+    /// Writes session id to protobuf audio packets traveling in this direction.
+    fn write_protobuf_session_id(audio: &mut Audio, session_id: &Self::SessionId);
 }
 
 impl VoicePacketDst for ServerBound {
@@ -89,6 +146,18 @@ impl VoicePacketDst for ServerBound {
     }
 
     fn write_session_id(_buf: &mut BytesMut, _session_id: &Self::SessionId) {}
+
+    fn read_protobuf_target(audio: &Audio) -> u32 {
+        audio.get_target()
+    }
+
+    fn write_protobuf_target(audio: &mut Audio, target: u8) {
+        audio.set_target(u32::from(target))
+    }
+
+    fn read_protobuf_session_id(_audio: &Audio) -> Self::SessionId {}
+
+    fn write_protobuf_session_id(_audio: &mut Audio, _session_id: &Self::SessionId) {}
 }
 
 impl VoicePacketDst for ClientBound {
@@ -101,6 +170,29 @@ impl VoicePacketDst for ClientBound {
     fn write_session_id(buf: &mut BytesMut, session_id: &Self::SessionId) {
         buf.put_varint(u64::from(*session_id))
     }
+
+    fn read_protobuf_target(audio: &Audio) -> u32 {
+        audio.get_context()
+    }
+
+    /// The protobuf format only tells the client how the audio reached it, so every voice target
+    /// (and loopback) is mapped onto a context.
+    fn write_protobuf_target(audio: &mut Audio, target: u8) {
+        let context = match target {
+            0 | 31 => PROTOBUF_CONTEXT_NORMAL,
+            _ => PROTOBUF_CONTEXT_WHISPER,
+        };
+
+        audio.set_context(context)
+    }
+
+    fn read_protobuf_session_id(audio: &Audio) -> Self::SessionId {
+        audio.get_sender_session()
+    }
+
+    fn write_protobuf_session_id(audio: &mut Audio, session_id: &Self::SessionId) {
+        audio.set_sender_session(*session_id)
+    }
 }
 
 impl VoicePacket<ServerBound> {
@@ -125,6 +217,136 @@ impl VoicePacket<ServerBound> {
     }
 }
 
+impl<Dst: VoicePacketDst> VoicePacket<Dst> {
+    /// Only opus audio can be sent in the protobuf format, everything can be sent in the legacy one.
+    pub fn supports_format(&self, format: VoicePacketFormat) -> bool {
+        match (self, format) {
+            (VoicePacket::Audio { payload, .. }, VoicePacketFormat::Protobuf) => matches!(payload, VoicePacketPayload::Opus(_, _)),
+            _ => true,
+        }
+    }
+}
+
+/// Parses the `[f32; 3]` position of a legacy packet, as written by mumble (native, so little
+/// endian, floats).
+pub fn parse_position_info(position_info: &[u8]) -> Option<[f32; 3]> {
+    if position_info.len() < 12 {
+        return None;
+    }
+
+    let mut position = [0f32; 3];
+
+    for (i, chunk) in position_info[..12].chunks_exact(4).enumerate() {
+        position[i] = f32::from_le_bytes(chunk.try_into().ok()?);
+    }
+
+    Some(position)
+}
+
+fn encode_position_info(position: &[f32]) -> Bytes {
+    let mut bytes = BytesMut::with_capacity(position.len() * 4);
+
+    for value in position {
+        bytes.put_f32_le(*value);
+    }
+
+    bytes.freeze()
+}
+
+fn invalid_data(message: &str) -> DecryptError {
+    DecryptError::Io(io::Error::new(io::ErrorKind::InvalidData, message.to_string()))
+}
+
+pub fn decode_voice_packet_protobuf<DecodeDst: VoicePacketDst>(buf_mut: &mut BytesMut) -> Result<VoicePacket<DecodeDst>, DecryptError> {
+    if buf_mut.is_empty() {
+        return Err(DecryptError::Eof);
+    }
+
+    let kind = buf_mut[0];
+    let body = buf_mut.split().split_off(1);
+
+    let result = match kind {
+        PROTOBUF_AUDIO => {
+            let mut audio = Audio::parse_from_bytes(&body).map_err(|_| invalid_data("invalid protobuf audio packet"))?;
+
+            let target = DecodeDst::read_protobuf_target(&audio);
+
+            if target > 31 {
+                return Err(invalid_data("unsupported voice target"));
+            }
+
+            let position_info = if audio.get_positional_data().len() >= 3 {
+                Some(encode_position_info(&audio.get_positional_data()[..3]))
+            } else {
+                None
+            };
+
+            VoicePacket::Audio {
+                _dst: PhantomData,
+                target: target as u8,
+                session_id: DecodeDst::read_protobuf_session_id(&audio),
+                seq_num: audio.get_frame_number(),
+                payload: VoicePacketPayload::Opus(Bytes::from(audio.take_opus_data()), audio.get_is_terminator()),
+                position_info,
+            }
+        }
+        PROTOBUF_PING => {
+            let ping = Ping::parse_from_bytes(&body).map_err(|_| invalid_data("invalid protobuf ping packet"))?;
+
+            VoicePacket::Ping {
+                timestamp: ping.get_timestamp(),
+            }
+        }
+        _ => {
+            return Err(invalid_data("unknown voice packet type"));
+        }
+    };
+
+    Ok(result)
+}
+
+pub fn encode_voice_packet_protobuf<EncodeDst: VoicePacketDst>(item: &VoicePacket<EncodeDst>, dst: &mut BytesMut) {
+    let (kind, bytes) = match item {
+        VoicePacket::Ping { timestamp } => {
+            let mut ping = Ping::new();
+            ping.set_timestamp(*timestamp);
+
+            (PROTOBUF_PING, ping.write_to_bytes())
+        }
+        VoicePacket::Audio {
+            target,
+            session_id,
+            seq_num,
+            payload,
+            position_info,
+            ..
+        } => {
+            let mut audio = Audio::new();
+            EncodeDst::write_protobuf_target(&mut audio, *target);
+            EncodeDst::write_protobuf_session_id(&mut audio, session_id);
+            audio.set_frame_number(*seq_num);
+
+            if let VoicePacketPayload::Opus(frame, termination_bit) = payload {
+                audio.set_opus_data(frame.to_vec());
+                audio.set_is_terminator(*termination_bit);
+            }
+
+            if let Some(position) = position_info.as_deref().and_then(parse_position_info) {
+                audio.set_positional_data(position.to_vec());
+            }
+
+            (PROTOBUF_AUDIO, audio.write_to_bytes())
+        }
+    };
+
+    // writing to a vec can only fail on messages with missing required fields, proto3 has none
+    let bytes = bytes.expect("protobuf voice packets always serialize");
+
+    dst.reserve(1 + bytes.len());
+    dst.put_u8(kind);
+    dst.put_slice(&bytes);
+}
+
 pub fn decode_voice_packet<DecodeDst: VoicePacketDst>(buf_mut: &mut BytesMut) -> Result<VoicePacket<DecodeDst>, DecryptError> {
     let mut buf = Cursor::new(&buf_mut);
     let header = buf.read_u8()?;
@@ -245,3 +467,97 @@ pub fn encode_voice_packet<EncodeDst: VoicePacketDst>(item: &VoicePacket<EncodeD
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POSITION: [f32; 3] = [1.5, -2.0, 300.25];
+
+    fn opus<Dst: VoicePacketDst>(target: u8, session_id: Dst::SessionId, terminator: bool, position: bool) -> VoicePacket<Dst> {
+        VoicePacket::Audio {
+            _dst: PhantomData,
+            target,
+            session_id,
+            seq_num: 4242,
+            payload: VoicePacketPayload::Opus(Bytes::from_static(&[1, 2, 3, 4, 5]), terminator),
+            position_info: position.then(|| encode_position_info(&POSITION)),
+        }
+    }
+
+    /// Encodes the packet in one format, decodes it and does the same in the other format.
+    fn through<Dst: VoicePacketDst>(packet: &VoicePacket<Dst>, first: VoicePacketFormat, second: VoicePacketFormat) -> VoicePacket<Dst> {
+        let mut buf = BytesMut::new();
+        first.encode(packet, &mut buf);
+        let packet = first.decode::<Dst>(&mut buf).unwrap();
+
+        let mut buf = BytesMut::new();
+        second.encode(&packet, &mut buf);
+        second.decode(&mut buf).unwrap()
+    }
+
+    #[test]
+    fn opus_round_trips_between_legacy_and_protobuf() {
+        for terminator in [false, true] {
+            for position in [false, true] {
+                let server_bound = opus::<ServerBound>(5, (), terminator, position);
+                let client_bound = opus::<ClientBound>(0, 7, terminator, position);
+
+                for (first, second) in [
+                    (VoicePacketFormat::Legacy, VoicePacketFormat::Protobuf),
+                    (VoicePacketFormat::Protobuf, VoicePacketFormat::Legacy),
+                ] {
+                    assert_eq!(through(&server_bound, first, second), server_bound);
+                    assert_eq!(through(&client_bound, first, second), client_bound);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_protobuf_is_an_error() {
+        let mut valid = BytesMut::new();
+        encode_voice_packet_protobuf(&opus::<ClientBound>(0, 7, true, true), &mut valid);
+
+        let invalid: [&[u8]; 6] = [
+            &[],
+            &valid[..valid.len() - 1],
+            &[PROTOBUF_AUDIO, 0x2a, 0x10, 1, 2],
+            &[PROTOBUF_AUDIO, 0xff],
+            &[PROTOBUF_PING, 0x08],
+            &[7, 0x08, 0x01],
+        ];
+
+        for bytes in invalid {
+            assert!(
+                decode_voice_packet_protobuf::<ClientBound>(&mut BytesMut::from(bytes)).is_err(),
+                "{bytes:?}"
+            );
+        }
+
+        // every prefix must decode or fail, never panic
+        for len in 0..valid.len() {
+            let _ = decode_voice_packet_protobuf::<ClientBound>(&mut BytesMut::from(&valid[..len]));
+        }
+    }
+
+    #[test]
+    fn protobuf_target_above_31_is_an_error() {
+        let mut buf = BytesMut::new();
+        let mut audio = Audio::new();
+        audio.set_target(32);
+        buf.put_u8(PROTOBUF_AUDIO);
+        buf.put_slice(&audio.write_to_bytes().unwrap());
+
+        assert!(decode_voice_packet_protobuf::<ServerBound>(&mut buf).is_err());
+    }
+
+    #[test]
+    fn position_is_little_endian() {
+        let bytes = [0x00, 0x00, 0x80, 0x3f, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x80, 0xbf, 0xff];
+
+        assert_eq!(parse_position_info(&bytes), Some([1.0, 2.0, -1.0]));
+        assert_eq!(parse_position_info(&bytes[..11]), None);
+        assert_eq!(parse_position_info(&encode_position_info(&POSITION)), Some(POSITION));
+    }
+}
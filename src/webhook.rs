@@ -0,0 +1,226 @@
+use crate::certificate::{to_hex, CertificateHash};
+use crate::server::constants::{WEBHOOK_MAX_ATTEMPTS, WEBHOOK_QUEUE_SIZE, WEBHOOK_RETRY_BACKOFF, WEBHOOK_TIMEOUT};
+use bytes::Bytes;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+/// Header holding the hex HMAC-SHA256 of the body, signed with the webhook secret
+pub const SIGNATURE_HEADER: &str = "X-Zumble-Signature";
+
+/// Why a client wasn't allowed to connect
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectReason {
+    /// The client certificate is banned
+    Banned,
+    /// The client is older than the minimum client version
+    WrongVersion,
+}
+
+/// Why a client left the server
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisconnectReason {
+    /// The client closed its connection or the connection broke
+    Closed,
+    /// The client stopped sending pings
+    Timeout,
+    /// The client connected again with the same name and certificate
+    Replaced,
+    /// An admin kicked the client
+    Kicked,
+    /// An admin banned the client
+    Banned,
+    /// The server shut down
+    Shutdown,
+}
+
+/// Notable things happening on the server, posted to the configured webhook urls
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    UserConnected {
+        session: u32,
+        name: String,
+        ip: IpAddr,
+        release: String,
+        certificate_hash: Option<CertificateHash>,
+        admin: bool,
+    },
+    UserDisconnected {
+        session: u32,
+        name: String,
+        /// How long the client was connected, in seconds
+        connected_for: u64,
+        reason: DisconnectReason,
+    },
+    /// An admin removed the client from the server, it is followed by its disconnection
+    UserKicked {
+        session: u32,
+        name: String,
+        actor_session: u32,
+        actor_name: String,
+        reason: String,
+    },
+    /// Like a kick, and the certificate of the client can't connect again until the server restarts
+    UserBanned {
+        session: u32,
+        name: String,
+        certificate_hash: Option<CertificateHash>,
+        actor_session: u32,
+        actor_name: String,
+        reason: String,
+    },
+    /// The client got rejected before it was added to the server, the name is only known once it
+    /// authenticated
+    AuthenticationRejected {
+        ip: IpAddr,
+        name: Option<String>,
+        certificate_hash: Option<CertificateHash>,
+        reason: RejectReason,
+    },
+    ChannelCreated {
+        channel_id: u32,
+        parent_id: Option<u32>,
+        name: String,
+    },
+    ChannelRemoved {
+        channel_id: u32,
+    },
+    /// The crypt of the client got reset several times in a row without its UDP connection coming
+    /// back, it is most likely stuck on the TCP tunnel
+    CryptResetsRepeated {
+        session: u32,
+        name: String,
+        resets: u32,
+    },
+}
+
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    /// Increasing id, a delivery that got retried keeps the same id
+    id: u64,
+    /// Unix timestamp in milliseconds
+    timestamp: u128,
+    #[serde(flatten)]
+    event: &'a WebhookEvent,
+}
+
+/// Queues the webhook events for every configured url, each url has its own bounded queue and
+/// delivery task so a url that is down doesn't hold back the other ones
+#[derive(Default)]
+pub struct Webhooks {
+    endpoints: Vec<mpsc::Sender<Bytes>>,
+    next_id: AtomicU64,
+}
+
+impl Webhooks {
+    /// Spawns a delivery task for each url, this has to be called from the actix runtime
+    pub fn new(urls: Vec<String>, secret: Option<String>) -> Self {
+        let key = secret.map(|secret| hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()));
+
+        let endpoints = urls
+            .into_iter()
+            .map(|url| {
+                let (sender, receiver) = mpsc::channel(WEBHOOK_QUEUE_SIZE);
+                let key = key.clone();
+
+                actix_rt::spawn(async move {
+                    webhook_loop(url, key, receiver).await;
+                });
+
+                sender
+            })
+            .collect();
+
+        Self {
+            endpoints,
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    pub fn send(&self, event: WebhookEvent) {
+        if self.endpoints.is_empty() {
+            return;
+        }
+
+        let payload = WebhookPayload {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis(),
+            event: &event,
+        };
+
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => Bytes::from(body),
+            Err(e) => {
+                tracing::error!("failed to serialize webhook event: {:?}", e);
+
+                return;
+            }
+        };
+
+        for endpoint in &self.endpoints {
+            match endpoint.try_send(body.clone()) {
+                Ok(_) => (),
+                Err(TrySendError::Full(_)) => {
+                    tracing::warn!("webhook queue is full, dropping event {}", payload.id);
+
+                    crate::metrics::WEBHOOK_DELIVERIES_TOTAL.with_label_values(&["dropped"]).inc();
+                }
+                Err(TrySendError::Closed(_)) => (),
+            }
+        }
+    }
+}
+
+async fn webhook_loop(url: String, key: Option<hmac::Key>, mut receiver: mpsc::Receiver<Bytes>) {
+    let client = awc::Client::builder().timeout(WEBHOOK_TIMEOUT).finish();
+
+    while let Some(body) = receiver.recv().await {
+        let signature = key
+            .as_ref()
+            .map(|key| format!("sha256={}", to_hex(hmac::sign(key, &body).as_ref())));
+
+        let mut backoff = WEBHOOK_RETRY_BACKOFF;
+        let mut attempt = 1;
+
+        loop {
+            let mut request = client.post(url.as_str()).content_type("application/json");
+
+            if let Some(signature) = &signature {
+                request = request.insert_header((SIGNATURE_HEADER, signature.as_str()));
+            }
+
+            match request.send_body(body.clone()).await {
+                Ok(response) if response.status().is_success() => {
+                    crate::metrics::WEBHOOK_DELIVERIES_TOTAL.with_label_values(&["success"]).inc();
+
+                    break;
+                }
+                Ok(response) => {
+                    tracing::warn!("webhook {} answered with {} (attempt {})", url, response.status(), attempt);
+                }
+                Err(e) => {
+                    tracing::warn!("failed to send webhook to {}: {} (attempt {})", url, e, attempt);
+                }
+            }
+
+            if attempt >= WEBHOOK_MAX_ATTEMPTS {
+                tracing::error!("giving up on webhook delivery to {} after {} attempts", url, attempt);
+
+                crate::metrics::WEBHOOK_DELIVERIES_TOTAL.with_label_values(&["failed"]).inc();
+
+                break;
+            }
+
+            tokio::time::sleep(backoff).await;
+
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+}